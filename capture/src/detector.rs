@@ -0,0 +1,118 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use wrapper::{DeviceInterface, ExposureModes, FullWellModes, SLBufferInfo, SLDevice, SLError, ROI};
+
+// How a detector is attached, the wrapper's DeviceInterface can't travel in messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum DetectorInterface {
+    CameraLink,
+    Usb,
+    Pleora,
+    S2iGige,
+    EioUsb,
+}
+
+impl From<DetectorInterface> for DeviceInterface {
+    fn from(interface: DetectorInterface) -> Self {
+        match interface {
+            DetectorInterface::CameraLink => DeviceInterface::CL,
+            DetectorInterface::Usb => DeviceInterface::USB,
+            DetectorInterface::Pleora => DeviceInterface::PLEORA,
+            DetectorInterface::S2iGige => DeviceInterface::S2I_GIGE,
+            DetectorInterface::EioUsb => DeviceInterface::EIO_USB,
+        }
+    }
+}
+
+// The operations the detector actor needs, implemented by the real device and by virtual detectors
+pub trait Detector: Send + 'static {
+    fn acquire_image(&mut self, buffer: &mut [u16], timeout: Option<Duration>) -> Result<SLBufferInfo, SLError>;
+    // Identifies the physical detector, None for virtual ones that have nothing to identify them by
+    fn device_id(&mut self) -> Option<String>;
+    fn get_image_dims(&mut self) -> Result<(u32, u32), SLError>;
+    fn is_connected(&mut self) -> bool;
+    fn open_camera(&mut self) -> Result<(), SLError>;
+    fn close_camera(&mut self) -> Result<(), SLError>;
+    fn measure_temperature(&mut self, sensor: u32) -> Result<f32, SLError>;
+    fn set_dds(&mut self, dds_on: bool) -> Result<(), SLError>;
+    fn set_full_well_mode(&mut self, full_well_mode: FullWellModes) -> Result<(), SLError>;
+    fn set_exposure_time(&mut self, exposure_time: Duration) -> Result<(), SLError>;
+    fn set_exposure_mode(&mut self, exposure_mode: ExposureModes) -> Result<(), SLError>;
+    fn set_roi(&mut self, roi: ROI) -> Result<(), SLError>;
+    fn set_number_of_frames(&mut self, frames: u32) -> Result<(), SLError>;
+    fn set_test_mode(&mut self, test_mode_on: bool) -> Result<(), SLError>;
+    fn software_trigger(&mut self) -> Result<(), SLError>;
+    fn start_stream(&mut self) -> Result<(), SLError>;
+    fn stop_stream(&mut self) -> Result<(), SLError>;
+}
+
+impl Detector for SLDevice {
+    fn acquire_image(&mut self, buffer: &mut [u16], timeout: Option<Duration>) -> Result<SLBufferInfo, SLError> {
+        SLDevice::acquire_image(self, buffer, timeout)
+    }
+
+    fn device_id(&mut self) -> Option<String> {
+        Some(SLDevice::device_id(self)).filter(|id| !id.is_empty())
+    }
+
+    fn get_image_dims(&mut self) -> Result<(u32, u32), SLError> {
+        SLDevice::get_image_dims(self)
+    }
+
+    fn is_connected(&mut self) -> bool {
+        SLDevice::is_connected(self)
+    }
+
+    fn open_camera(&mut self) -> Result<(), SLError> {
+        SLDevice::open_camera(self)
+    }
+
+    fn close_camera(&mut self) -> Result<(), SLError> {
+        SLDevice::close_camera(self)
+    }
+
+    fn measure_temperature(&mut self, sensor: u32) -> Result<f32, SLError> {
+        SLDevice::measure_temperature(self, sensor)
+    }
+
+    fn set_dds(&mut self, dds_on: bool) -> Result<(), SLError> {
+        SLDevice::set_dds(self, dds_on)
+    }
+
+    fn set_full_well_mode(&mut self, full_well_mode: FullWellModes) -> Result<(), SLError> {
+        SLDevice::set_full_well_mode(self, full_well_mode)
+    }
+
+    fn set_exposure_time(&mut self, exposure_time: Duration) -> Result<(), SLError> {
+        SLDevice::set_exposure_time(self, exposure_time)
+    }
+
+    fn set_exposure_mode(&mut self, exposure_mode: ExposureModes) -> Result<(), SLError> {
+        SLDevice::set_exposure_mode(self, exposure_mode)
+    }
+
+    fn set_roi(&mut self, roi: ROI) -> Result<(), SLError> {
+        SLDevice::set_roi(self, roi)
+    }
+
+    fn set_number_of_frames(&mut self, frames: u32) -> Result<(), SLError> {
+        SLDevice::set_number_of_frames(self, frames)
+    }
+
+    fn set_test_mode(&mut self, test_mode_on: bool) -> Result<(), SLError> {
+        SLDevice::set_test_mode(self, test_mode_on)
+    }
+
+    fn software_trigger(&mut self) -> Result<(), SLError> {
+        SLDevice::software_trigger(self)
+    }
+
+    fn start_stream(&mut self) -> Result<(), SLError> {
+        SLDevice::start_stream(self)
+    }
+
+    fn stop_stream(&mut self) -> Result<(), SLError> {
+        SLDevice::stop_stream(self)
+    }
+}
@@ -7,13 +7,17 @@ use tokio::sync::{mpsc, oneshot};
 use wrapper::{scan_cameras, DeviceInterface, ExposureModes, FullWellModes, SLBufferInfo, SLDevice, SLError, SLImage, ROI};
 use uuid::Uuid;
 
+use crate::detector::Detector;
+
 const HEARTBEAT_PERIOD_MILLIS: u64 = 500;
 
 enum DetectorMessage {
     AcquireImage(Arc<Mutex<Vec<u16>>>, Option<Duration>, oneshot::Sender<Result<SLBufferInfo, SLError>>),
     CloseCamera(oneshot::Sender<Result<(), SLError>>),
+    DeviceId(oneshot::Sender<Option<String>>),
     GetImageDims(oneshot::Sender<Result<(u32, u32), SLError>>),
     IsConnected(oneshot::Sender<bool>),
+    MeasureTemperature(u32, oneshot::Sender<Result<f32, SLError>>),
     OpenCamera(oneshot::Sender<Result<(), SLError>>),
     SetDDS(bool, oneshot::Sender<Result<(), SLError>>),
     SetFullWellMode(FullWellModes, oneshot::Sender<Result<(), SLError>>),
@@ -28,18 +32,20 @@ enum DetectorMessage {
 }
 
 struct DetectorActor {
-    detector: SLDevice,
+    detector: Box<dyn Detector>,
 }
 
 impl DetectorActor {
-    async fn run(mut self, mut receiver: mpsc::Receiver<DetectorMessage>) {
-        while let Some(message) = receiver.recv().await {
+    fn run(mut self, mut receiver: mpsc::Receiver<DetectorMessage>) {
+        while let Some(message) = receiver.blocking_recv() {
             match message {
                 DetectorMessage::AcquireImage(buffer, timeout, sender) => sender.send(self.detector.acquire_image(buffer.lock().unwrap().as_mut_slice(), timeout)).unwrap(),
+                DetectorMessage::DeviceId(sender) => sender.send(self.detector.device_id()).unwrap(),
                 DetectorMessage::GetImageDims(sender) => sender.send(self.detector.get_image_dims()).unwrap(),
                 DetectorMessage::IsConnected(sender) => sender.send(self.detector.is_connected()).unwrap(), 
                 DetectorMessage::OpenCamera(sender) => sender.send(self.detector.open_camera()).unwrap(),
                 DetectorMessage::CloseCamera(sender) => sender.send(self.detector.close_camera()).unwrap(),
+                DetectorMessage::MeasureTemperature(sensor, sender) => sender.send(self.detector.measure_temperature(sensor)).unwrap(),
                 DetectorMessage::SetDDS(dds_on, sender) => sender.send(self.detector.set_dds(dds_on)).unwrap(),
                 DetectorMessage::SetFullWellMode(full_well_mode, sender) => sender.send(self.detector.set_full_well_mode(full_well_mode)).unwrap(),
                 DetectorMessage::SetExposureTime(exposure_time, sender) => sender.send(self.detector.set_exposure_time(exposure_time)).unwrap(),
@@ -61,20 +67,36 @@ pub struct DetectorHandle {
 }
 
 impl DetectorHandle {
-    pub fn new(interface: DeviceInterface) -> Self {
+    // Fails when the SDK can't create a device for the interface
+    pub fn new(interface: DeviceInterface) -> Result<Self, String> {
+        SLDevice::new(interface).map(Self::from_detector)
+    }
+
+    pub fn from_detector(detector: impl Detector) -> Self {
         let (sender, receiver) = mpsc::channel(8);
-        let detector = DetectorActor { detector: SLDevice::new(interface).unwrap() };
-        tokio::spawn(detector.run(receiver));
+        let detector = DetectorActor { detector: Box::new(detector) };
+        // Detector calls block until a frame arrives, so they get their own thread instead of stalling the async runtime
+        std::thread::Builder::new().name("detector".into()).spawn(move || detector.run(receiver)).unwrap();
 
         Self { sender }
     }
 
+    pub fn acquisition_handle(&self) -> DetectorAcquisitionHandle {
+        DetectorAcquisitionHandle { sender: self.sender.clone() }
+    }
+
     pub async fn get_image_dims(&self) -> Result<(u32, u32), SLError> {
         let (resp_sender, resp_receiver) = oneshot::channel();
         let _ = self.sender.send(DetectorMessage::GetImageDims(resp_sender)).await;
         resp_receiver.await.expect("Actor task has been killed")
     }
 
+    pub async fn device_id(&self) -> Option<String> {
+        let (resp_sender, resp_receiver) = oneshot::channel();
+        let _ = self.sender.send(DetectorMessage::DeviceId(resp_sender)).await;
+        resp_receiver.await.expect("Actor task has been killed")
+    }
+
     pub async fn is_connected(&self) -> bool {
         let (resp_sender, resp_receiver) = oneshot::channel();
         let _ = self.sender.send(DetectorMessage::IsConnected(resp_sender)).await;
@@ -117,8 +139,10 @@ pub struct DetectorController {
 
 impl DetectorController {
     pub async fn new(interface: DeviceInterface, status_tx: mpsc::Sender<DetectorStatus>) -> DetectorController {
-        let detector_handle = DetectorHandle::new(interface);
+        Self::from_handle(DetectorHandle::new(interface).unwrap(), interface, status_tx).await
+    }
 
+    pub async fn from_handle(detector_handle: DetectorHandle, interface: DeviceInterface, status_tx: mpsc::Sender<DetectorStatus>) -> DetectorController {
         let mut detector_status = DetectorStatus::Disconnected;
         let detector_info = detector_handle.open_camera().await
         .map(|_| async {
@@ -192,6 +216,12 @@ impl DetectorAcquisitionHandle {
         resp_receiver.await.expect("Actor task has been killed")
     }
 
+    pub async fn measure_temperature(&self, sensor: u32) -> Result<f32, SLError> {
+        let (resp_sender, resp_receiver) = oneshot::channel();
+        let _ = self.sender.send(DetectorMessage::MeasureTemperature(sensor, resp_sender)).await;
+        resp_receiver.await.expect("Actor task has been killed")
+    }
+
     pub async fn set_dds(&self, dds_on: bool) -> Result<(), SLError> {
         let (resp_sender, resp_receiver) = oneshot::channel();
         let _ = self.sender.send(DetectorMessage::SetDDS(dds_on, resp_sender)).await;
@@ -254,18 +284,18 @@ impl DetectorAcquisitionHandle {
 }
 
 #[derive(Clone, Copy, Debug, Serialize, Deserialize)]
-struct AcquistionSettings {
-    dds_on: bool,
-    full_well_mode: FullWellModes,
-    roi: ROI,
-    test_mode: bool,
-    timeout: Duration,
+pub struct AcquistionSettings {
+    pub dds_on: bool,
+    pub full_well_mode: FullWellModes,
+    pub roi: ROI,
+    pub test_mode: bool,
+    pub timeout: Duration,
 }
 
 #[derive(Debug)]
-enum AcquisitionMessage {
+pub enum AcquisitionMessage {
     Error(SLError),
-    Image(SLBufferInfo),
+    Image(SLBufferInfo, Vec<u16>),
 }
 
 #[async_trait]
@@ -322,6 +352,18 @@ pub struct SequenceAcquisition {
     exposure_time: Duration
 }
 
+impl SequenceAcquisition {
+    pub fn new(acquisition_settings: AcquistionSettings, num_frames: u32, exposure_time: Duration) -> Self {
+        Self { acquisition_settings, num_frames, exposure_time }
+    }
+
+    // Frames arrive on the returned channel as they are acquired, it closes once the sequence ends
+    pub async fn start(&self, detector_handle: DetectorAcquisitionHandle) -> Result<mpsc::Receiver<AcquisitionMessage>, SLError> {
+        let (_, control) = mpsc::channel(1);
+        self.run(detector_handle, control).await
+    }
+}
+
 #[async_trait]
 impl Acquisition for SequenceAcquisition {
     async fn run(&self, detector_handle: DetectorAcquisitionHandle, mut rx: mpsc::Receiver<AcquisitionMessage>) -> Result<mpsc::Receiver<AcquisitionMessage>, SLError> {
@@ -337,17 +379,25 @@ impl Acquisition for SequenceAcquisition {
         let (acq_tx, acq_rx) = mpsc::channel(10);
         tokio::spawn(async move {
             let mut count = 0;
-            let data = Arc::new(Mutex::new(vec![0u16; (x * y) as usize]));
+            let frame_len = (x * y) as usize;
+            let data = Arc::new(Mutex::new(vec![0u16; frame_len]));
             while count < num_frames {
                 match detector_handle.acquire_image(Arc::clone(&data), Some(timeout)).await {
                     Ok(buffer_info) => {
-                        acq_tx.send(AcquisitionMessage::Image(buffer_info)).await.unwrap();
+                        // The filled buffer is handed over as is, the next frame is acquired into a fresh one
+                        let frame = std::mem::replace(&mut *data.lock().unwrap(), vec![0u16; frame_len]);
+                        if acq_tx.send(AcquisitionMessage::Image(buffer_info, frame)).await.is_err() {
+                            break;
+                        }
                         count += 1;
                     },
-                    Err(e) => acq_tx.send(AcquisitionMessage::Error(e)).await.unwrap()
+                    Err(e) => {
+                        let _ = acq_tx.send(AcquisitionMessage::Error(e)).await;
+                        break;
+                    }
                 }
             }
-            detector_handle.stop_stream().await;
+            let _ = detector_handle.stop_stream().await;
         });
 
         Ok(acq_rx)
@@ -1,3 +1,13 @@
+mod detector;
 mod detector_controller;
+mod live_capture;
+mod stack_storage;
+mod virtual_detector;
 
-//pub use detector_controller::{DetectorController, DetectorEvent, CaptureMode, CaptureHandle};
\ No newline at end of file
+pub use detector::{Detector, DetectorInterface};
+pub use detector_controller::{AcquisitionMessage, AcquistionSettings, DetectorAcquisitionHandle, DetectorHandle, SequenceAcquisition};
+pub use live_capture::{CaptureHandle, CaptureMode, DetectorEvent};
+pub use stack_storage::{record_sequence, spawn_stack_recorder, StackCompression, StackError, StackMetadata, StackReader, StackWriter, DEFAULT_CHUNK_FRAMES};
+pub use virtual_detector::{RecordingDetector, ReplayDetector, ReplaySpeed};
+
+//pub use detector_controller::DetectorController;
\ No newline at end of file
@@ -0,0 +1,174 @@
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::{self, error::TryRecvError};
+use wrapper::{ExposureModes, SLBufferInfo, SLError};
+
+use crate::detector_controller::DetectorAcquisitionHandle;
+
+const CAPTURE_TIMEOUT_MILLIS: u64 = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum CaptureMode {
+    // Frames are acquired continuously until the capture is stopped
+    Stream { exposure_time_ms: u32 },
+    Sequence { exposure_time_ms: u32, num_frames: u32 },
+    // A frame is acquired for every software trigger sent to the capture
+    SoftwareTrigger { exposure_time_ms: u32 },
+}
+
+impl CaptureMode {
+    pub fn exposure_time_ms(&self) -> u32 {
+        match *self {
+            CaptureMode::Stream { exposure_time_ms } | CaptureMode::Sequence { exposure_time_ms, .. } | CaptureMode::SoftwareTrigger { exposure_time_ms } => exposure_time_ms,
+        }
+    }
+
+    pub fn exposure_mode(&self) -> ExposureModes {
+        match self {
+            CaptureMode::Stream { .. } => ExposureModes::XFPSMode,
+            CaptureMode::Sequence { .. } => ExposureModes::SequenceMode,
+            CaptureMode::SoftwareTrigger { .. } => ExposureModes::TriggerMode,
+        }
+    }
+
+    pub fn frame_limit(&self) -> Option<u32> {
+        match self {
+            CaptureMode::Sequence { num_frames, .. } => Some(*num_frames),
+            _ => None,
+        }
+    }
+}
+
+// Everything a running capture reports back, in the order it happened
+#[derive(Debug)]
+pub enum DetectorEvent {
+    Started(CaptureMode),
+    Frame(SLBufferInfo, Vec<u16>),
+    Error(SLError),
+    Finished { frames: u32 },
+}
+
+enum CaptureControl {
+    SoftwareTrigger,
+    Stop,
+}
+
+// Controls a capture running as a tokio task, dropping the handle stops the capture
+#[derive(Debug)]
+pub struct CaptureHandle {
+    mode: CaptureMode,
+    control: mpsc::UnboundedSender<CaptureControl>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl CaptureHandle {
+    // Must be called from within a tokio runtime
+    pub fn start(detector_handle: DetectorAcquisitionHandle, mode: CaptureMode, events: mpsc::UnboundedSender<DetectorEvent>) -> Self {
+        let (control, control_receiver) = mpsc::unbounded_channel();
+        let task = tokio::spawn(run_capture(detector_handle, mode, control_receiver, events));
+        Self { mode, control, task }
+    }
+
+    pub fn mode(&self) -> CaptureMode {
+        self.mode
+    }
+
+    pub fn software_trigger(&self) {
+        let _ = self.control.send(CaptureControl::SoftwareTrigger);
+    }
+
+    // The capture finishes its current frame before stopping
+    pub fn stop(&self) {
+        let _ = self.control.send(CaptureControl::Stop);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+}
+
+impl Drop for CaptureHandle {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+async fn setup_capture(detector_handle: &DetectorAcquisitionHandle, mode: CaptureMode) -> Result<(u32, u32), SLError> {
+    detector_handle.set_exposure_mode(mode.exposure_mode()).await?;
+    detector_handle.set_exposure_time(Duration::from_millis(mode.exposure_time_ms() as u64)).await?;
+    if let Some(num_frames) = mode.frame_limit() {
+        detector_handle.set_number_of_frames(num_frames).await?;
+    }
+    let image_dims = detector_handle.get_image_dims().await?;
+    detector_handle.start_stream().await?;
+    Ok(image_dims)
+}
+
+async fn run_capture(detector_handle: DetectorAcquisitionHandle, mode: CaptureMode, mut control: mpsc::UnboundedReceiver<CaptureControl>, events: mpsc::UnboundedSender<DetectorEvent>) {
+    let (width, height) = match setup_capture(&detector_handle, mode).await {
+        Ok(image_dims) => image_dims,
+        Err(err) => {
+            let _ = events.send(DetectorEvent::Error(err));
+            let _ = events.send(DetectorEvent::Finished { frames: 0 });
+            return;
+        }
+    };
+    let _ = events.send(DetectorEvent::Started(mode));
+
+    // The frame has to cover the exposure as well as the readout
+    let timeout = Duration::from_millis(CAPTURE_TIMEOUT_MILLIS + mode.exposure_time_ms() as u64);
+    let frame_len = (width * height) as usize;
+    let buffer = Arc::new(Mutex::new(vec![0u16; frame_len]));
+    let mut frames = 0;
+    let mut pending_triggers = 0u32;
+
+    'capture: while mode.frame_limit().map_or(true, |limit| frames < limit) {
+        // Control messages are handled between frames, a triggered capture sleeps until the next trigger
+        loop {
+            let waiting_for_trigger = matches!(mode, CaptureMode::SoftwareTrigger { .. }) && pending_triggers == 0;
+            let message = if waiting_for_trigger {
+                control.recv().await
+            } else {
+                match control.try_recv() {
+                    Ok(message) => Some(message),
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => None,
+                }
+            };
+            match message {
+                Some(CaptureControl::SoftwareTrigger) => match detector_handle.software_trigger().await {
+                    Ok(()) => pending_triggers += 1,
+                    Err(err) => {
+                        let _ = events.send(DetectorEvent::Error(err));
+                    }
+                },
+                Some(CaptureControl::Stop) | None => break 'capture,
+            }
+        }
+
+        match detector_handle.acquire_image(Arc::clone(&buffer), Some(timeout)).await {
+            Ok(buffer_info) => {
+                pending_triggers = pending_triggers.saturating_sub(1);
+                frames += 1;
+                // The filled buffer is handed over as is, the next frame is acquired into a fresh one
+                let frame = std::mem::replace(&mut *buffer.lock().unwrap(), vec![0u16; frame_len]);
+                if events.send(DetectorEvent::Frame(buffer_info, frame)).is_err() {
+                    break;
+                }
+            }
+            // Waiting for a frame isn't a failure, it gives the capture a chance to check for a stop
+            Err(err) if err == SLError::SL_ERROR_TIMEOUT => {}
+            Err(err) => {
+                let _ = events.send(DetectorEvent::Error(err));
+                break;
+            }
+        }
+    }
+
+    if let Err(err) = detector_handle.stop_stream().await {
+        let _ = events.send(DetectorEvent::Error(err));
+    }
+    let _ = events.send(DetectorEvent::Finished { frames });
+}
@@ -0,0 +1,398 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use wrapper::SLBufferInfo;
+
+use crate::detector_controller::{AcquisitionMessage, DetectorAcquisitionHandle, SequenceAcquisition};
+
+const STACK_FORMAT: &str = "cview-stack";
+const STACK_FORMAT_VERSION: u32 = 1;
+const METADATA_FILE: &str = "stack.json";
+const FRAME_INFO_FILE: &str = "frames.jsonl";
+const CHUNK_DIR: &str = "chunks";
+pub const DEFAULT_CHUNK_FRAMES: u32 = 16;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StackError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("corrupt chunk: {0}")]
+    Decompress(#[from] lz4_flex::block::DecompressError),
+    #[error("not a {STACK_FORMAT} v{STACK_FORMAT_VERSION} container")]
+    UnsupportedFormat,
+    #[error("frame has {actual} pixels, expected {expected}")]
+    FrameSizeMismatch { expected: usize, actual: usize },
+    #[error("frame {index} out of range, container has {frame_count} frames")]
+    FrameOutOfRange { index: u32, frame_count: u32 },
+    #[error("acquisition failed after {frame_count} frames: {error:?}")]
+    Acquisition { error: wrapper::SLError, frame_count: u32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StackCompression {
+    None,
+    Lz4,
+}
+
+// Self-describing header written next to the chunks, rewritten after every chunk so an interrupted capture stays readable
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StackMetadata {
+    pub format: String,
+    pub version: u32,
+    pub width: u32,
+    pub height: u32,
+    pub chunk_frames: u32,
+    pub compression: StackCompression,
+    pub frame_count: u32,
+    pub attributes: serde_json::Value,
+}
+
+impl StackMetadata {
+    fn frame_len(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+}
+
+fn chunk_path(root: &Path, chunk_index: u32) -> PathBuf {
+    root.join(CHUNK_DIR).join(format!("{chunk_index:08}.bin"))
+}
+
+fn write_metadata(root: &Path, metadata: &StackMetadata) -> Result<(), StackError> {
+    // Write then rename so readers never observe a half written header
+    let tmp_path = root.join(format!("{METADATA_FILE}.tmp"));
+    fs::write(&tmp_path, serde_json::to_vec_pretty(metadata)?)?;
+    fs::rename(tmp_path, root.join(METADATA_FILE))?;
+    Ok(())
+}
+
+pub struct StackWriter {
+    root: PathBuf,
+    metadata: StackMetadata,
+    chunk: Vec<u8>,
+    chunk_frame_count: u32,
+    frame_info: BufWriter<File>,
+}
+
+impl StackWriter {
+    pub fn create(root: impl AsRef<Path>, width: u32, height: u32, chunk_frames: u32, compression: StackCompression, attributes: serde_json::Value) -> Result<Self, StackError> {
+        let root = root.as_ref().to_path_buf();
+        fs::create_dir_all(root.join(CHUNK_DIR))?;
+
+        let metadata = StackMetadata {
+            format: STACK_FORMAT.into(),
+            version: STACK_FORMAT_VERSION,
+            width,
+            height,
+            chunk_frames: chunk_frames.max(1),
+            compression,
+            frame_count: 0,
+            attributes,
+        };
+        write_metadata(&root, &metadata)?;
+
+        let frame_info = BufWriter::new(OpenOptions::new().create(true).write(true).truncate(true).open(root.join(FRAME_INFO_FILE))?);
+
+        Ok(Self {
+            chunk: Vec::with_capacity(metadata.frame_len() * 2 * metadata.chunk_frames as usize),
+            root,
+            metadata,
+            chunk_frame_count: 0,
+            frame_info,
+        })
+    }
+
+    pub fn metadata(&self) -> &StackMetadata {
+        &self.metadata
+    }
+
+    pub fn write_frame(&mut self, buffer_info: &SLBufferInfo, data: &[u16]) -> Result<(), StackError> {
+        let expected = self.metadata.frame_len();
+        if data.len() != expected {
+            return Err(StackError::FrameSizeMismatch { expected, actual: data.len() });
+        }
+
+        for pixel in data {
+            self.chunk.extend_from_slice(&pixel.to_le_bytes());
+        }
+        serde_json::to_writer(&mut self.frame_info, buffer_info)?;
+        self.frame_info.write_all(b"\n")?;
+
+        self.chunk_frame_count += 1;
+        if self.chunk_frame_count == self.metadata.chunk_frames {
+            self.flush_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn flush_chunk(&mut self) -> Result<(), StackError> {
+        if self.chunk_frame_count == 0 {
+            return Ok(());
+        }
+
+        let chunk_index = self.metadata.frame_count / self.metadata.chunk_frames;
+        let bytes = match self.metadata.compression {
+            StackCompression::None => std::mem::take(&mut self.chunk),
+            StackCompression::Lz4 => lz4_flex::compress_prepend_size(&self.chunk),
+        };
+        fs::write(chunk_path(&self.root, chunk_index), bytes)?;
+        self.chunk.clear();
+
+        self.frame_info.flush()?;
+        self.metadata.frame_count += self.chunk_frame_count;
+        self.chunk_frame_count = 0;
+        write_metadata(&self.root, &self.metadata)
+    }
+
+    pub fn finish(mut self) -> Result<StackMetadata, StackError> {
+        self.flush_chunk()?;
+        Ok(self.metadata)
+    }
+}
+
+// Drains an acquisition into the writer on a blocking thread so disk I/O never stalls the detector actor.
+// A failed acquisition still flushes the frames that arrived, but the recording resolves to an error.
+pub fn spawn_stack_recorder(mut writer: StackWriter, mut rx: mpsc::Receiver<AcquisitionMessage>) -> tokio::task::JoinHandle<Result<StackMetadata, StackError>> {
+    tokio::task::spawn_blocking(move || {
+        while let Some(message) = rx.blocking_recv() {
+            match message {
+                AcquisitionMessage::Image(buffer_info, data) => writer.write_frame(&buffer_info, &data)?,
+                AcquisitionMessage::Error(error) => {
+                    let metadata = writer.finish()?;
+                    return Err(StackError::Acquisition { error, frame_count: metadata.frame_count });
+                }
+            }
+        }
+        writer.finish()
+    })
+}
+
+// Starts the sequence with its frames streamed straight to disk, the handle resolves once the last frame is written
+pub async fn record_sequence(
+    acquisition: &SequenceAcquisition,
+    detector_handle: DetectorAcquisitionHandle,
+    writer: StackWriter,
+) -> Result<tokio::task::JoinHandle<Result<StackMetadata, StackError>>, wrapper::SLError> {
+    let rx = acquisition.start(detector_handle).await?;
+    Ok(spawn_stack_recorder(writer, rx))
+}
+
+pub struct StackReader {
+    root: PathBuf,
+    metadata: StackMetadata,
+    frame_info: Vec<SLBufferInfo>,
+    cached_chunk: Option<(u32, Vec<u16>)>,
+}
+
+impl StackReader {
+    pub fn open(root: impl AsRef<Path>) -> Result<Self, StackError> {
+        let root = root.as_ref().to_path_buf();
+        let metadata: StackMetadata = serde_json::from_slice(&fs::read(root.join(METADATA_FILE))?)?;
+        if metadata.format != STACK_FORMAT || metadata.version != STACK_FORMAT_VERSION {
+            return Err(StackError::UnsupportedFormat);
+        }
+
+        // Frame info can run ahead of the last flushed chunk, only keep rows for frames that are on disk
+        let mut frame_info = Vec::with_capacity(metadata.frame_count as usize);
+        for line in BufReader::new(File::open(root.join(FRAME_INFO_FILE))?).lines().take(metadata.frame_count as usize) {
+            frame_info.push(serde_json::from_str(&line?)?);
+        }
+
+        Ok(Self {
+            root,
+            metadata,
+            frame_info,
+            cached_chunk: None,
+        })
+    }
+
+    pub fn metadata(&self) -> &StackMetadata {
+        &self.metadata
+    }
+
+    pub fn frame_count(&self) -> u32 {
+        self.metadata.frame_count
+    }
+
+    pub fn frame_info(&self, index: u32) -> Option<&SLBufferInfo> {
+        self.frame_info.get(index as usize)
+    }
+
+    pub fn read_frame(&mut self, index: u32) -> Result<Vec<u16>, StackError> {
+        if index >= self.metadata.frame_count {
+            return Err(StackError::FrameOutOfRange { index, frame_count: self.metadata.frame_count });
+        }
+
+        let chunk_index = index / self.metadata.chunk_frames;
+        if self.cached_chunk.as_ref().map(|(cached_index, _)| *cached_index) != Some(chunk_index) {
+            self.cached_chunk = Some((chunk_index, self.load_chunk(chunk_index)?));
+        }
+
+        let frame_len = self.metadata.frame_len();
+        let offset = (index % self.metadata.chunk_frames) as usize * frame_len;
+        let (_, chunk) = self.cached_chunk.as_ref().unwrap();
+        chunk.get(offset..offset + frame_len).map(<[u16]>::to_vec).ok_or(StackError::FrameSizeMismatch { expected: offset + frame_len, actual: chunk.len() })
+    }
+
+    fn load_chunk(&self, chunk_index: u32) -> Result<Vec<u16>, StackError> {
+        let bytes = fs::read(chunk_path(&self.root, chunk_index))?;
+        let bytes = match self.metadata.compression {
+            StackCompression::None => bytes,
+            StackCompression::Lz4 => lz4_flex::decompress_size_prepended(&bytes)?,
+        };
+        Ok(bytes.chunks_exact(2).map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]])).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use wrapper::{FullWellModes, ROI};
+
+    use super::*;
+    use crate::detector_controller::{AcquistionSettings, DetectorHandle};
+    use crate::virtual_detector::{ReplayDetector, ReplaySpeed};
+
+    const WIDTH: u32 = 5;
+    const HEIGHT: u32 = 4;
+
+    fn temp_stack(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("cview-stack-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    // Every pixel differs between frames so a frame read from the wrong place can't pass
+    fn frame(index: u32) -> Vec<u16> {
+        (0..WIDTH * HEIGHT).map(|pixel| (index * 1000 + pixel) as u16).collect()
+    }
+
+    fn buffer_info(index: u32) -> SLBufferInfo {
+        SLBufferInfo {
+            error: wrapper::SLError::SL_ERROR_SUCCESS,
+            width: WIDTH,
+            height: HEIGHT,
+            size: WIDTH * HEIGHT * 2,
+            missing_packets: index % 2,
+            frame_count: index,
+            block_id: 100 + index as u64,
+            timestamp: 1000 * index as u64,
+        }
+    }
+
+    fn write_stack(root: &Path, frame_count: u32, chunk_frames: u32, compression: StackCompression) -> StackMetadata {
+        let mut writer = StackWriter::create(root, WIDTH, HEIGHT, chunk_frames, compression, serde_json::json!({ "test": true })).unwrap();
+        for index in 0..frame_count {
+            writer.write_frame(&buffer_info(index), &frame(index)).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn frames_round_trip_across_partial_chunks() {
+        for compression in [StackCompression::None, StackCompression::Lz4] {
+            let root = temp_stack(&format!("round-trip-{compression:?}"));
+            // Two full chunks and a partial one
+            let metadata = write_stack(&root, 7, 3, compression);
+            assert_eq!(metadata.frame_count, 7);
+
+            let mut reader = StackReader::open(&root).unwrap();
+            assert_eq!(reader.frame_count(), 7);
+            assert_eq!(reader.metadata().attributes, serde_json::json!({ "test": true }));
+            for index in 0..7 {
+                assert_eq!(reader.read_frame(index).unwrap(), frame(index), "{compression:?} frame {index}");
+                assert_eq!(reader.frame_info(index).unwrap().block_id, buffer_info(index).block_id);
+            }
+            fs::remove_dir_all(&root).unwrap();
+        }
+    }
+
+    #[test]
+    fn frames_can_be_read_in_any_order() {
+        let root = temp_stack("random-access");
+        write_stack(&root, 10, 4, StackCompression::Lz4);
+
+        let mut reader = StackReader::open(&root).unwrap();
+        // Back and forth over chunk boundaries, and the same chunk twice in a row
+        for index in [9, 0, 4, 3, 8, 7, 1, 1, 5] {
+            assert_eq!(reader.read_frame(index).unwrap(), frame(index), "frame {index}");
+        }
+        assert!(matches!(reader.read_frame(10), Err(StackError::FrameOutOfRange { index: 10, frame_count: 10 })));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn interrupted_recording_keeps_its_flushed_chunks() {
+        let root = temp_stack("interrupted");
+        let mut writer = StackWriter::create(&root, WIDTH, HEIGHT, 4, StackCompression::Lz4, serde_json::Value::Null).unwrap();
+        for index in 0..6 {
+            writer.write_frame(&buffer_info(index), &frame(index)).unwrap();
+        }
+
+        // Frames 4 and 5 are still in the unflushed chunk
+        let mut reader = StackReader::open(&root).unwrap();
+        assert_eq!(reader.frame_count(), 4);
+        assert_eq!(reader.read_frame(3).unwrap(), frame(3));
+        assert!(reader.frame_info(4).is_none());
+        drop(writer);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn frames_of_the_wrong_size_are_rejected() {
+        let root = temp_stack("wrong-size");
+        let mut writer = StackWriter::create(&root, WIDTH, HEIGHT, 4, StackCompression::None, serde_json::Value::Null).unwrap();
+
+        assert!(matches!(writer.write_frame(&buffer_info(0), &[0; 3]), Err(StackError::FrameSizeMismatch { expected: 20, actual: 3 })));
+        assert_eq!(writer.finish().unwrap().frame_count, 0);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn sequence_is_recorded_as_it_arrives() {
+        let source = temp_stack("sequence-source");
+        write_stack(&source, 6, 4, StackCompression::None);
+        let detector_handle = DetectorHandle::from_detector(ReplayDetector::open(&source, ReplaySpeed::AsFastAsPossible, false).unwrap());
+        detector_handle.open_camera().await.unwrap();
+
+        let settings = AcquistionSettings { dds_on: false, full_well_mode: FullWellModes::High, roi: ROI::default(), test_mode: false, timeout: Duration::from_secs(1) };
+        let acquisition = SequenceAcquisition::new(settings, 5, Duration::from_millis(10));
+        let root = temp_stack("sequence");
+        let writer = StackWriter::create(&root, WIDTH, HEIGHT, 2, StackCompression::Lz4, serde_json::Value::Null).unwrap();
+
+        let recording = record_sequence(&acquisition, detector_handle.acquisition_handle(), writer).await.unwrap();
+        assert_eq!(recording.await.unwrap().unwrap().frame_count, 5);
+
+        let mut reader = StackReader::open(&root).unwrap();
+        for index in 0..5 {
+            assert_eq!(reader.read_frame(index).unwrap(), frame(index));
+        }
+        fs::remove_dir_all(&root).unwrap();
+        fs::remove_dir_all(&source).unwrap();
+    }
+
+    #[tokio::test]
+    async fn failed_acquisition_keeps_its_frames_but_is_an_error() {
+        let root = temp_stack("failed-acquisition");
+        let writer = StackWriter::create(&root, WIDTH, HEIGHT, 4, StackCompression::None, serde_json::Value::Null).unwrap();
+        let (tx, rx) = mpsc::channel(4);
+        let recording = spawn_stack_recorder(writer, rx);
+        for index in 0..2 {
+            tx.send(AcquisitionMessage::Image(buffer_info(index), frame(index))).await.unwrap();
+        }
+        tx.send(AcquisitionMessage::Error(wrapper::SLError::SL_ERROR_TIMEOUT)).await.unwrap();
+
+        let result = recording.await.unwrap();
+        assert!(matches!(result, Err(StackError::Acquisition { error: wrapper::SLError::SL_ERROR_TIMEOUT, frame_count: 2 })), "{result:?}");
+        let mut reader = StackReader::open(&root).unwrap();
+        assert_eq!(reader.frame_count(), 2);
+        assert_eq!(reader.read_frame(1).unwrap(), frame(1));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
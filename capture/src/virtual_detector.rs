@@ -0,0 +1,515 @@
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use wrapper::{ExposureModes, FullWellModes, SLBufferInfo, SLError, ROI};
+
+use crate::detector::Detector;
+use crate::stack_storage::{StackCompression, StackError, StackReader, StackWriter, DEFAULT_CHUNK_FRAMES};
+
+const TIMING_FILE: &str = "timing.jsonl";
+// Attribute of the recorded stack naming the detector the frames came from
+const DETECTOR_ID_ATTRIBUTE: &str = "detector_id";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct FrameTiming {
+    elapsed_micros: u64,
+}
+
+// Passes every call through to the wrapped detector and records the frames it returns
+pub struct RecordingDetector<D: Detector> {
+    detector: D,
+    root: PathBuf,
+    compression: StackCompression,
+    writer: Option<StackWriter>,
+    timing: Option<BufWriter<File>>,
+    started: Instant,
+}
+
+impl<D: Detector> RecordingDetector<D> {
+    pub fn new(detector: D, root: impl AsRef<Path>, compression: StackCompression) -> Self {
+        Self {
+            detector,
+            root: root.as_ref().to_path_buf(),
+            compression,
+            writer: None,
+            timing: None,
+            started: Instant::now(),
+        }
+    }
+
+    fn record(&mut self, buffer_info: &SLBufferInfo, buffer: &[u16]) -> Result<(), StackError> {
+        if self.writer.is_none() {
+            let attributes = serde_json::json!({ "source": "recording", DETECTOR_ID_ATTRIBUTE: self.detector.device_id() });
+            self.writer = Some(StackWriter::create(&self.root, buffer_info.width, buffer_info.height, DEFAULT_CHUNK_FRAMES, self.compression, attributes)?);
+            self.timing = Some(BufWriter::new(File::create(self.root.join(TIMING_FILE))?));
+            self.started = Instant::now();
+        }
+
+        let frame_len = (buffer_info.width * buffer_info.height) as usize;
+        let frame = buffer.get(..frame_len).ok_or(StackError::FrameSizeMismatch { expected: frame_len, actual: buffer.len() })?;
+        self.writer.as_mut().unwrap().write_frame(buffer_info, frame)?;
+
+        let timing = self.timing.as_mut().unwrap();
+        serde_json::to_writer(&mut *timing, &FrameTiming { elapsed_micros: self.started.elapsed().as_micros() as u64 })?;
+        timing.write_all(b"\n")?;
+        Ok(())
+    }
+
+    pub fn finish(&mut self) -> Result<(), StackError> {
+        if let Some(mut timing) = self.timing.take() {
+            timing.flush()?;
+        }
+        if let Some(writer) = self.writer.take() {
+            writer.finish()?;
+        }
+        Ok(())
+    }
+}
+
+impl<D: Detector> Detector for RecordingDetector<D> {
+    fn acquire_image(&mut self, buffer: &mut [u16], timeout: Option<Duration>) -> Result<SLBufferInfo, SLError> {
+        let buffer_info = self.detector.acquire_image(buffer, timeout)?;
+        // A failed write must not interrupt the live acquisition
+        if let Err(err) = self.record(&buffer_info, buffer) {
+            log::error!("Failed to record frame {}: {err}", buffer_info.frame_count);
+        }
+        Ok(buffer_info)
+    }
+
+    fn device_id(&mut self) -> Option<String> {
+        self.detector.device_id()
+    }
+
+    fn get_image_dims(&mut self) -> Result<(u32, u32), SLError> {
+        self.detector.get_image_dims()
+    }
+
+    fn is_connected(&mut self) -> bool {
+        self.detector.is_connected()
+    }
+
+    fn open_camera(&mut self) -> Result<(), SLError> {
+        self.detector.open_camera()
+    }
+
+    fn close_camera(&mut self) -> Result<(), SLError> {
+        if let Err(err) = self.finish() {
+            log::error!("Failed to finish recording: {err}");
+        }
+        self.detector.close_camera()
+    }
+
+    fn measure_temperature(&mut self, sensor: u32) -> Result<f32, SLError> {
+        self.detector.measure_temperature(sensor)
+    }
+
+    fn set_dds(&mut self, dds_on: bool) -> Result<(), SLError> {
+        self.detector.set_dds(dds_on)
+    }
+
+    fn set_full_well_mode(&mut self, full_well_mode: FullWellModes) -> Result<(), SLError> {
+        self.detector.set_full_well_mode(full_well_mode)
+    }
+
+    fn set_exposure_time(&mut self, exposure_time: Duration) -> Result<(), SLError> {
+        self.detector.set_exposure_time(exposure_time)
+    }
+
+    fn set_exposure_mode(&mut self, exposure_mode: ExposureModes) -> Result<(), SLError> {
+        self.detector.set_exposure_mode(exposure_mode)
+    }
+
+    fn set_roi(&mut self, roi: ROI) -> Result<(), SLError> {
+        self.detector.set_roi(roi)
+    }
+
+    fn set_number_of_frames(&mut self, frames: u32) -> Result<(), SLError> {
+        self.detector.set_number_of_frames(frames)
+    }
+
+    fn set_test_mode(&mut self, test_mode_on: bool) -> Result<(), SLError> {
+        self.detector.set_test_mode(test_mode_on)
+    }
+
+    fn software_trigger(&mut self) -> Result<(), SLError> {
+        self.detector.software_trigger()
+    }
+
+    fn start_stream(&mut self) -> Result<(), SLError> {
+        self.detector.start_stream()
+    }
+
+    fn stop_stream(&mut self) -> Result<(), SLError> {
+        self.detector.stop_stream()
+    }
+}
+
+impl<D: Detector> Drop for RecordingDetector<D> {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReplaySpeed {
+    Original,
+    AsFastAsPossible,
+}
+
+// Plays a recording back through the detector API, frames are delivered in order and wrap around when looping
+pub struct ReplayDetector {
+    reader: StackReader,
+    timing: Vec<u64>,
+    speed: ReplaySpeed,
+    looping: bool,
+    next_frame: u32,
+    started: Option<Instant>,
+    open: bool,
+}
+
+impl ReplayDetector {
+    pub fn open(root: impl AsRef<Path>, speed: ReplaySpeed, looping: bool) -> Result<Self, StackError> {
+        let root = root.as_ref();
+        let reader = StackReader::open(root)?;
+
+        // Recordings without timing can still be replayed, just not at the original speed
+        let timing = match fs::metadata(root.join(TIMING_FILE)) {
+            Ok(_) => BufReader::new(File::open(root.join(TIMING_FILE))?)
+                .lines()
+                .take(reader.frame_count() as usize)
+                .map(|line| Ok(serde_json::from_str::<FrameTiming>(&line?)?.elapsed_micros))
+                .collect::<Result<Vec<_>, StackError>>()?,
+            Err(_) => Vec::new(),
+        };
+
+        Ok(Self {
+            reader,
+            timing,
+            speed,
+            looping,
+            next_frame: 0,
+            started: None,
+            open: false,
+        })
+    }
+
+    fn wait_for_frame(&mut self, frame: u32, timeout: Option<Duration>) -> Result<(), SLError> {
+        if self.speed == ReplaySpeed::AsFastAsPossible {
+            return Ok(());
+        }
+        let (Some(&first), Some(&due)) = (self.timing.first(), self.timing.get(frame as usize)) else {
+            return Ok(());
+        };
+
+        // Restart the clock whenever playback wraps so looping keeps the original cadence
+        if frame == 0 || self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+        let due = Duration::from_micros(due - first);
+        let elapsed = self.started.unwrap().elapsed();
+        if let Some(wait) = due.checked_sub(elapsed) {
+            if timeout.is_some_and(|timeout| wait > timeout) {
+                std::thread::sleep(timeout.unwrap());
+                return Err(SLError::SL_ERROR_TIMEOUT);
+            }
+            std::thread::sleep(wait);
+        }
+        Ok(())
+    }
+}
+
+impl Detector for ReplayDetector {
+    fn acquire_image(&mut self, buffer: &mut [u16], timeout: Option<Duration>) -> Result<SLBufferInfo, SLError> {
+        if !self.open {
+            return Err(SLError::SL_ERROR_DEVICE_CLOSED);
+        }
+        if self.next_frame >= self.reader.frame_count() {
+            if !self.looping || self.reader.frame_count() == 0 {
+                return Err(SLError::SL_ERROR_TIMEOUT);
+            }
+            self.next_frame = 0;
+        }
+
+        let frame = self.next_frame;
+        self.wait_for_frame(frame, timeout)?;
+
+        let data = self.reader.read_frame(frame).map_err(|err| {
+            log::error!("Failed to read recorded frame {frame}: {err}");
+            SLError::SL_ERROR_READ_FAILED
+        })?;
+        buffer.get_mut(..data.len()).ok_or(SLError::SL_ERROR_INVALID_PARAM)?.copy_from_slice(&data);
+
+        self.next_frame += 1;
+        self.reader.frame_info(frame).cloned().ok_or(SLError::SL_ERROR_READ_FAILED)
+    }
+
+    // Replays as the detector that was recorded
+    fn device_id(&mut self) -> Option<String> {
+        self.reader.metadata().attributes.get(DETECTOR_ID_ATTRIBUTE)?.as_str().map(str::to_string)
+    }
+
+    fn get_image_dims(&mut self) -> Result<(u32, u32), SLError> {
+        Ok((self.reader.metadata().width, self.reader.metadata().height))
+    }
+
+    fn is_connected(&mut self) -> bool {
+        true
+    }
+
+    fn open_camera(&mut self) -> Result<(), SLError> {
+        self.open = true;
+        Ok(())
+    }
+
+    fn close_camera(&mut self) -> Result<(), SLError> {
+        self.open = false;
+        Ok(())
+    }
+
+    fn measure_temperature(&mut self, _sensor: u32) -> Result<f32, SLError> {
+        Err(SLError::SL_ERROR_NOT_SUPPORTED)
+    }
+
+    // Acquisition settings are baked into the recording, accept them so acquisitions can set up as usual
+    fn set_dds(&mut self, _dds_on: bool) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_full_well_mode(&mut self, _full_well_mode: FullWellModes) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_exposure_time(&mut self, _exposure_time: Duration) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_exposure_mode(&mut self, _exposure_mode: ExposureModes) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_roi(&mut self, _roi: ROI) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_number_of_frames(&mut self, _frames: u32) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn set_test_mode(&mut self, _test_mode_on: bool) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn software_trigger(&mut self) -> Result<(), SLError> {
+        Ok(())
+    }
+
+    fn start_stream(&mut self) -> Result<(), SLError> {
+        self.next_frame = 0;
+        self.started = None;
+        Ok(())
+    }
+
+    fn stop_stream(&mut self) -> Result<(), SLError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::detector_controller::DetectorHandle;
+
+    const WIDTH: u32 = 4;
+    const HEIGHT: u32 = 3;
+
+    // Stands in for the hardware, each frame is filled with its own index
+    struct CountingDetector {
+        frame_count: u32,
+    }
+
+    impl Detector for CountingDetector {
+        fn acquire_image(&mut self, buffer: &mut [u16], _timeout: Option<Duration>) -> Result<SLBufferInfo, SLError> {
+            buffer.fill(self.frame_count as u16);
+            let buffer_info = SLBufferInfo {
+                error: SLError::SL_ERROR_SUCCESS,
+                width: WIDTH,
+                height: HEIGHT,
+                size: WIDTH * HEIGHT * 2,
+                missing_packets: 0,
+                frame_count: self.frame_count,
+                block_id: 10 + self.frame_count as u64,
+                timestamp: 0,
+            };
+            self.frame_count += 1;
+            Ok(buffer_info)
+        }
+
+        fn device_id(&mut self) -> Option<String> {
+            Some("SL-1234".into())
+        }
+
+        fn get_image_dims(&mut self) -> Result<(u32, u32), SLError> {
+            Ok((WIDTH, HEIGHT))
+        }
+
+        fn is_connected(&mut self) -> bool {
+            true
+        }
+
+        fn open_camera(&mut self) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn close_camera(&mut self) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn measure_temperature(&mut self, _sensor: u32) -> Result<f32, SLError> {
+            Ok(30.)
+        }
+
+        fn set_dds(&mut self, _dds_on: bool) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_full_well_mode(&mut self, _full_well_mode: FullWellModes) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_exposure_time(&mut self, _exposure_time: Duration) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_exposure_mode(&mut self, _exposure_mode: ExposureModes) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_roi(&mut self, _roi: ROI) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_number_of_frames(&mut self, _frames: u32) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn set_test_mode(&mut self, _test_mode_on: bool) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn software_trigger(&mut self) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn start_stream(&mut self) -> Result<(), SLError> {
+            Ok(())
+        }
+
+        fn stop_stream(&mut self) -> Result<(), SLError> {
+            Ok(())
+        }
+    }
+
+    fn temp_recording(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("cview-replay-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    fn record(root: &Path, frame_count: u32) -> Vec<SLBufferInfo> {
+        let mut recorder = RecordingDetector::new(CountingDetector { frame_count: 0 }, root, StackCompression::Lz4);
+        let mut buffer = vec![0; (WIDTH * HEIGHT) as usize];
+        let infos = (0..frame_count).map(|_| recorder.acquire_image(&mut buffer, None).unwrap()).collect();
+        recorder.close_camera().unwrap();
+        infos
+    }
+
+    // Replaces the recorded timing so tests don't depend on how fast the recording ran
+    fn set_timing(root: &Path, elapsed_millis: &[u64]) {
+        let lines: String = elapsed_millis.iter().map(|millis| format!("{{\"elapsed_micros\":{}}}\n", millis * 1000)).collect();
+        fs::write(root.join(TIMING_FILE), lines).unwrap();
+    }
+
+    #[test]
+    fn replay_returns_the_recorded_frames() {
+        let root = temp_recording("frames");
+        let infos = record(&root, 3);
+
+        let mut replay = ReplayDetector::open(&root, ReplaySpeed::AsFastAsPossible, false).unwrap();
+        let mut buffer = vec![0; (WIDTH * HEIGHT) as usize];
+        assert!(matches!(replay.acquire_image(&mut buffer, None), Err(SLError::SL_ERROR_DEVICE_CLOSED)));
+
+        replay.open_camera().unwrap();
+        assert_eq!(replay.get_image_dims().unwrap(), (WIDTH, HEIGHT));
+        assert_eq!(replay.device_id().as_deref(), Some("SL-1234"));
+        for (index, info) in infos.iter().enumerate() {
+            let replayed = replay.acquire_image(&mut buffer, None).unwrap();
+            assert_eq!((replayed.frame_count, replayed.block_id), (info.frame_count, info.block_id));
+            assert!(buffer.iter().all(|&pixel| pixel == index as u16));
+        }
+        // Without looping the recording runs dry like a detector that stopped delivering
+        assert!(matches!(replay.acquire_image(&mut buffer, None), Err(SLError::SL_ERROR_TIMEOUT)));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn looping_replay_wraps_around() {
+        let root = temp_recording("looping");
+        record(&root, 2);
+
+        let mut replay = ReplayDetector::open(&root, ReplaySpeed::AsFastAsPossible, true).unwrap();
+        replay.open_camera().unwrap();
+        let mut buffer = vec![0; (WIDTH * HEIGHT) as usize];
+        let frames: Vec<u32> = (0..5).map(|_| replay.acquire_image(&mut buffer, None).unwrap().frame_count).collect();
+        assert_eq!(frames, [0, 1, 0, 1, 0]);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn original_speed_follows_the_recorded_timing() {
+        let root = temp_recording("timing");
+        record(&root, 3);
+        set_timing(&root, &[0, 50, 400]);
+
+        let mut replay = ReplayDetector::open(&root, ReplaySpeed::Original, false).unwrap();
+        replay.open_camera().unwrap();
+        let mut buffer = vec![0; (WIDTH * HEIGHT) as usize];
+        let started = Instant::now();
+        replay.acquire_image(&mut buffer, None).unwrap();
+        replay.acquire_image(&mut buffer, None).unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(50));
+
+        // The third frame is due later than the timeout allows
+        assert!(matches!(replay.acquire_image(&mut buffer, Some(Duration::from_millis(10))), Err(SLError::SL_ERROR_TIMEOUT)));
+        assert_eq!(replay.acquire_image(&mut buffer, None).unwrap().frame_count, 2);
+        assert!(started.elapsed() >= Duration::from_millis(400));
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn waiting_replay_does_not_block_the_runtime() {
+        let root = temp_recording("runtime");
+        record(&root, 2);
+        set_timing(&root, &[0, 300]);
+
+        let detector_handle = DetectorHandle::from_detector(ReplayDetector::open(&root, ReplaySpeed::Original, false).unwrap());
+        detector_handle.open_camera().await.unwrap();
+        let acquisition_handle = detector_handle.acquisition_handle();
+        let buffer = Arc::new(Mutex::new(vec![0; (WIDTH * HEIGHT) as usize]));
+        acquisition_handle.acquire_image(buffer.clone(), None).await.unwrap();
+
+        // The test runtime is single threaded, so the other task only runs early if the detector waits elsewhere
+        let started = Instant::now();
+        let (acquired, other_task) = tokio::join!(
+            async { acquisition_handle.acquire_image(buffer.clone(), None).await.map(|_| started.elapsed()) },
+            async { tokio::spawn(async move { started.elapsed() }).await.unwrap() },
+        );
+        assert!(acquired.unwrap() >= Duration::from_millis(300));
+        assert!(other_task < Duration::from_millis(100));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
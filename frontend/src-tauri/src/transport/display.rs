@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use uuid::Uuid;
+use viewer::messages::portfolio::image::utility_types::misc::ImageId;
+
+use super::{FrameTransport, TransportError};
+
+pub const DISPLAY_CHANNEL_EVENT: &str = "display-channel";
+
+// Tells the frontend which channel an image's display frames arrive on, the channel is None once the image is closed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DisplayChannel {
+    pub image_id: ImageId,
+    pub channel: Option<Uuid>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct OpenChannel {
+    id: Uuid,
+    width: u32,
+    height: u32,
+}
+
+// Every displayed image gets a channel of its own, sized to the image rather than to the largest frame a detector could send
+#[derive(Debug, Default)]
+pub struct DisplayChannels {
+    channels: HashMap<ImageId, OpenChannel>,
+}
+
+impl DisplayChannels {
+    // Opened the first time the image is shown and again whenever its size changes. A newly opened channel is returned
+    // alongside, so the frontend can be told about it before the first frame on it arrives.
+    pub fn channel(&mut self, transport: &mut dyn FrameTransport, image_id: ImageId, width: u32, height: u32) -> Result<(Uuid, Option<DisplayChannel>), TransportError> {
+        match self.channels.get(&image_id) {
+            Some(channel) if (channel.width, channel.height) == (width, height) => return Ok((channel.id, None)),
+            Some(_) => self.close(transport, image_id)?,
+            None => {}
+        }
+
+        let info = transport.open_channel(width, height)?;
+        self.channels.insert(image_id, OpenChannel { id: info.id, width, height });
+        Ok((info.id, Some(DisplayChannel { image_id, channel: Some(info.id) })))
+    }
+
+    // Closes the channels of images that are no longer open
+    pub fn retain(&mut self, transport: &mut dyn FrameTransport, is_open: impl Fn(ImageId) -> bool) -> Result<Vec<DisplayChannel>, TransportError> {
+        let closed: Vec<_> = self.channels.keys().copied().filter(|&image_id| !is_open(image_id)).collect();
+        for &image_id in &closed {
+            self.close(transport, image_id)?;
+        }
+        Ok(closed.into_iter().map(|image_id| DisplayChannel { image_id, channel: None }).collect())
+    }
+
+    fn close(&mut self, transport: &mut dyn FrameTransport, image_id: ImageId) -> Result<(), TransportError> {
+        match self.channels.remove(&image_id) {
+            Some(channel) => transport.close_channel(channel.id),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::super::tests::RecordingNotifier;
+    use super::super::{FrameHeader, FrameStore, ProtocolTransport, TransportKind};
+    use super::*;
+
+    fn transport() -> (ProtocolTransport, Arc<RecordingNotifier>) {
+        let notifier = Arc::new(RecordingNotifier::default());
+        (ProtocolTransport::new(FrameStore::new(), notifier.clone()), notifier)
+    }
+
+    fn header(width: u32, height: u32) -> FrameHeader {
+        FrameHeader { width, height, frame_count: 0, timestamp: 0 }
+    }
+
+    #[test]
+    fn channel_is_opened_once_at_the_size_of_the_image() {
+        let (mut transport, notifier) = transport();
+        let mut channels = DisplayChannels::default();
+        let image_id = ImageId(Uuid::new_v4());
+
+        let (channel, opened) = channels.channel(&mut transport, image_id, 3, 2).unwrap();
+        assert_eq!(opened, Some(DisplayChannel { image_id, channel: Some(channel) }));
+        assert_eq!(channels.channel(&mut transport, image_id, 3, 2).unwrap(), (channel, None));
+
+        transport.publish(channel, header(3, 2), &[0; 6]).unwrap();
+        assert_eq!(notifier.events(), vec![(channel, TransportKind::Protocol, header(3, 2))]);
+        assert!(matches!(transport.publish(channel, header(4, 2), &[0; 8]), Err(TransportError::FrameTooLarge { capacity: 6, actual: 8 })));
+    }
+
+    #[test]
+    fn resized_image_moves_to_a_new_channel() {
+        let (mut transport, _) = transport();
+        let mut channels = DisplayChannels::default();
+        let image_id = ImageId(Uuid::new_v4());
+
+        let (small, _) = channels.channel(&mut transport, image_id, 2, 2).unwrap();
+        let (large, opened) = channels.channel(&mut transport, image_id, 4, 4).unwrap();
+        assert_ne!(small, large);
+        assert_eq!(opened, Some(DisplayChannel { image_id, channel: Some(large) }));
+        assert!(matches!(transport.publish(small, header(2, 2), &[0; 4]), Err(TransportError::UnknownChannel(_))));
+        transport.publish(large, header(4, 4), &[0; 16]).unwrap();
+    }
+
+    #[test]
+    fn closed_images_release_their_channels() {
+        let (mut transport, _) = transport();
+        let mut channels = DisplayChannels::default();
+        let (kept, closed) = (ImageId(Uuid::new_v4()), ImageId(Uuid::new_v4()));
+        let (kept_channel, _) = channels.channel(&mut transport, kept, 1, 1).unwrap();
+        let (closed_channel, _) = channels.channel(&mut transport, closed, 1, 1).unwrap();
+
+        assert_eq!(channels.retain(&mut transport, |image_id| image_id == kept).unwrap(), [DisplayChannel { image_id: closed, channel: None }]);
+        assert!(matches!(transport.publish(closed_channel, header(1, 1), &[0]), Err(TransportError::UnknownChannel(_))));
+        transport.publish(kept_channel, header(1, 1), &[0]).unwrap();
+        assert!(channels.retain(&mut transport, |image_id| image_id == kept).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+mod display;
+mod protocol;
+// Only WebView2 can host the buffers, elsewhere the transport is built for its tests alone
+#[cfg_attr(not(windows), allow(dead_code))]
+mod shared_buffer;
+
+pub use display::{DisplayChannel, DisplayChannels, DISPLAY_CHANNEL_EVENT};
+pub use protocol::{FrameStore, ProtocolTransport, FRAME_PROTOCOL};
+#[cfg_attr(not(windows), allow(unused_imports))]
+pub use shared_buffer::{PostedBuffer, SharedBuffer, SharedBufferHost, SharedBufferManager, SharedBufferToken, SharedBufferTransport, SharedMemory};
+
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager, Runtime};
+use uuid::Uuid;
+
+pub const FRAME_HEADER_LEN: usize = 24;
+pub const FRAME_READY_EVENT: &str = "frame-ready";
+
+const FRAME_HEADER_MAGIC: [u8; 4] = *b"CVF1";
+const TRANSPORT_OVERRIDE_ENV: &str = "CVIEW_FRAME_TRANSPORT";
+
+// Every frame is sent as a fixed little-endian header followed by the raw u16 pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: u32,
+    pub timestamp: u64,
+}
+
+impl FrameHeader {
+    pub fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    pub fn to_bytes(&self) -> [u8; FRAME_HEADER_LEN] {
+        let mut bytes = [0u8; FRAME_HEADER_LEN];
+        bytes[0..4].copy_from_slice(&FRAME_HEADER_MAGIC);
+        bytes[4..8].copy_from_slice(&self.width.to_le_bytes());
+        bytes[8..12].copy_from_slice(&self.height.to_le_bytes());
+        bytes[12..16].copy_from_slice(&self.frame_count.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.timestamp.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < FRAME_HEADER_LEN || bytes[0..4] != FRAME_HEADER_MAGIC {
+            return None;
+        }
+
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Some(Self {
+            width: u32_at(4),
+            height: u32_at(8),
+            frame_count: u32_at(12),
+            timestamp: u64::from_le_bytes(bytes[16..24].try_into().unwrap()),
+        })
+    }
+}
+
+pub fn encode_frame(header: &FrameHeader, data: &[u16]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(FRAME_HEADER_LEN + data.len() * 2);
+    bytes.extend_from_slice(&header.to_bytes());
+    for pixel in data {
+        bytes.extend_from_slice(&pixel.to_le_bytes());
+    }
+    bytes
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TransportKind {
+    SharedBuffer,
+    Protocol,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameChannelInfo {
+    pub id: Uuid,
+    pub kind: TransportKind,
+    pub capacity: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameReady {
+    pub channel: Uuid,
+    pub kind: TransportKind,
+    pub header: FrameHeader,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum TransportError {
+    #[error("unknown frame channel {0}")]
+    UnknownChannel(Uuid),
+    #[error("unknown or released shared buffer {0}")]
+    UnknownBuffer(Uuid),
+    #[error("write of {len} bytes at offset {offset} exceeds buffer of {size} bytes")]
+    OutOfBounds { offset: u64, len: u64, size: u64 },
+    #[error("frame of {actual} pixels does not fit channel of {capacity} pixels")]
+    FrameTooLarge { capacity: usize, actual: usize },
+    #[error("header describes {expected} pixels but {actual} were supplied")]
+    SizeMismatch { expected: usize, actual: usize },
+    #[error("frame transport unavailable: {0}")]
+    Unavailable(String),
+}
+
+impl Serialize for TransportError {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+// Decouples the transports from the webview so they can be driven headless
+pub trait FrameNotifier: Send + Sync {
+    fn frame_ready(&self, event: FrameReady);
+}
+
+impl<R: Runtime> FrameNotifier for AppHandle<R> {
+    fn frame_ready(&self, event: FrameReady) {
+        let _ = self.emit(FRAME_READY_EVENT, event);
+    }
+}
+
+pub trait FrameTransport: Send + Sync {
+    fn kind(&self) -> TransportKind;
+
+    fn open_channel(&mut self, width: u32, height: u32) -> Result<FrameChannelInfo, TransportError>;
+
+    fn close_channel(&mut self, channel: Uuid) -> Result<(), TransportError>;
+
+    fn publish(&mut self, channel: Uuid, header: FrameHeader, data: &[u16]) -> Result<(), TransportError>;
+}
+
+fn check_frame(header: &FrameHeader, data: &[u16], capacity: usize) -> Result<(), TransportError> {
+    if header.pixel_count() != data.len() {
+        return Err(TransportError::SizeMismatch { expected: header.pixel_count(), actual: data.len() });
+    }
+    if data.len() > capacity {
+        return Err(TransportError::FrameTooLarge { capacity, actual: data.len() });
+    }
+    Ok(())
+}
+
+// Shared buffers are only available on WebView2, everything else streams frames through the custom protocol.
+// Setting CVIEW_FRAME_TRANSPORT=protocol forces the fallback, which is useful for testing it on Windows.
+pub fn select_transport(app: &AppHandle, store: FrameStore) -> Box<dyn FrameTransport> {
+    let notifier: Arc<dyn FrameNotifier> = Arc::new(app.clone());
+    let forced_protocol = std::env::var(TRANSPORT_OVERRIDE_ENV).is_ok_and(|value| value.eq_ignore_ascii_case("protocol"));
+
+    #[cfg(windows)]
+    {
+        if !forced_protocol {
+            match app.get_webview_window("main") {
+                Some(_) => return Box::new(SharedBufferTransport::new(app.clone(), notifier)),
+                None => log::warn!("Falling back to protocol frame transport: main webview window not found"),
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    let _ = forced_protocol;
+
+    Box::new(ProtocolTransport::new(store, notifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // Stands in for the webview's event listener
+    #[derive(Default)]
+    pub(super) struct RecordingNotifier {
+        events: Mutex<Vec<FrameReady>>,
+    }
+
+    impl RecordingNotifier {
+        pub(super) fn events(&self) -> Vec<(Uuid, TransportKind, FrameHeader)> {
+            self.events.lock().unwrap().iter().map(|event| (event.channel, event.kind, event.header)).collect()
+        }
+    }
+
+    impl FrameNotifier for RecordingNotifier {
+        fn frame_ready(&self, event: FrameReady) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    #[test]
+    fn frame_header_round_trips() {
+        let header = FrameHeader { width: 5000, height: 4000, frame_count: 12, timestamp: u64::MAX - 1 };
+        let bytes = encode_frame(&header, &[1, 2, 3]);
+
+        assert_eq!(FrameHeader::from_bytes(&bytes), Some(header));
+        assert_eq!(&bytes[FRAME_HEADER_LEN..], &[1, 0, 2, 0, 3, 0]);
+        assert_eq!(FrameHeader::from_bytes(&bytes[..FRAME_HEADER_LEN - 1]), None);
+        assert_eq!(FrameHeader::from_bytes(&[0; FRAME_HEADER_LEN]), None);
+    }
+}
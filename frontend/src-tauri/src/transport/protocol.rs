@@ -0,0 +1,152 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tauri::http::{Request, Response, StatusCode};
+use uuid::Uuid;
+
+use super::{check_frame, encode_frame, FrameChannelInfo, FrameHeader, FrameNotifier, FrameReady, FrameTransport, TransportError, TransportKind};
+
+// Frames are served from frames://localhost/<channel> (http://frames.localhost/<channel> on Windows)
+pub const FRAME_PROTOCOL: &str = "frames";
+
+#[derive(Debug, Default)]
+struct FrameSlot {
+    capacity: usize,
+    latest: Option<Vec<u8>>,
+}
+
+// Holds the most recent encoded frame for each channel, shared between the transport and the protocol handler
+#[derive(Debug, Clone, Default)]
+pub struct FrameStore {
+    slots: Arc<Mutex<HashMap<Uuid, FrameSlot>>>,
+}
+
+impl FrameStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Each frame is handed to the webview once, moved out of the store rather than copied. Frames are large enough
+    // that a copy per request would cost more than the transfer itself.
+    pub fn take_latest(&self, channel: Uuid) -> Result<Option<Vec<u8>>, TransportError> {
+        let mut slots = self.slots.lock().unwrap();
+        let slot = slots.get_mut(&channel).ok_or(TransportError::UnknownChannel(channel))?;
+        Ok(slot.latest.take())
+    }
+
+    pub fn handle_request(&self, request: &Request<Vec<u8>>) -> Response<Cow<'static, [u8]>> {
+        let channel = request.uri().path().trim_start_matches('/').parse::<Uuid>();
+
+        let (status, body) = match channel.map(|channel| self.take_latest(channel)) {
+            Err(_) => (StatusCode::BAD_REQUEST, Vec::new()),
+            Ok(Err(_)) => (StatusCode::NOT_FOUND, Vec::new()),
+            // Already fetched, nothing newer has been published since
+            Ok(Ok(None)) => (StatusCode::NO_CONTENT, Vec::new()),
+            Ok(Ok(Some(frame))) => (StatusCode::OK, frame),
+        };
+
+        Response::builder()
+            .status(status)
+            .header("Content-Type", "application/octet-stream")
+            .header("Access-Control-Allow-Origin", "*")
+            .body(Cow::Owned(body))
+            .unwrap()
+    }
+}
+
+pub struct ProtocolTransport {
+    store: FrameStore,
+    notifier: Arc<dyn FrameNotifier>,
+}
+
+impl ProtocolTransport {
+    pub fn new(store: FrameStore, notifier: Arc<dyn FrameNotifier>) -> Self {
+        Self { store, notifier }
+    }
+}
+
+impl FrameTransport for ProtocolTransport {
+    fn kind(&self) -> TransportKind {
+        TransportKind::Protocol
+    }
+
+    fn open_channel(&mut self, width: u32, height: u32) -> Result<FrameChannelInfo, TransportError> {
+        let id = Uuid::new_v4();
+        let capacity = width as usize * height as usize;
+        self.store.slots.lock().unwrap().insert(id, FrameSlot { capacity, latest: None });
+
+        Ok(FrameChannelInfo { id, kind: self.kind(), capacity })
+    }
+
+    fn close_channel(&mut self, channel: Uuid) -> Result<(), TransportError> {
+        self.store.slots.lock().unwrap().remove(&channel).map(|_| ()).ok_or(TransportError::UnknownChannel(channel))
+    }
+
+    fn publish(&mut self, channel: Uuid, header: FrameHeader, data: &[u16]) -> Result<(), TransportError> {
+        {
+            let mut slots = self.store.slots.lock().unwrap();
+            let slot = slots.get_mut(&channel).ok_or(TransportError::UnknownChannel(channel))?;
+            check_frame(&header, data, slot.capacity)?;
+            slot.latest = Some(encode_frame(&header, data));
+        }
+
+        self.notifier.frame_ready(FrameReady { channel, kind: self.kind(), header });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::RecordingNotifier;
+    use super::*;
+
+    fn request(path: &str) -> Request<Vec<u8>> {
+        Request::builder().uri(format!("frames://localhost/{path}")).body(Vec::new()).unwrap()
+    }
+
+    #[test]
+    fn published_frame_is_served_once_over_the_protocol() {
+        let store = FrameStore::new();
+        let notifier = Arc::new(RecordingNotifier::default());
+        let mut transport = ProtocolTransport::new(store.clone(), notifier.clone());
+        let info = transport.open_channel(3, 2).unwrap();
+
+        let header = FrameHeader { width: 3, height: 2, frame_count: 1, timestamp: 42 };
+        let pixels = [0, 1, 2, 65533, 65534, 65535];
+        transport.publish(info.id, header, &pixels).unwrap();
+        assert_eq!(notifier.events(), vec![(info.id, TransportKind::Protocol, header)]);
+
+        let response = store.handle_request(&request(&info.id.to_string()));
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.body().as_ref(), encode_frame(&header, &pixels));
+
+        // Nothing newer has been published
+        assert_eq!(store.handle_request(&request(&info.id.to_string())).status(), StatusCode::NO_CONTENT);
+    }
+
+    #[test]
+    fn unknown_and_malformed_channels_are_not_served() {
+        let store = FrameStore::new();
+        let mut transport = ProtocolTransport::new(store.clone(), Arc::new(RecordingNotifier::default()));
+        let info = transport.open_channel(1, 1).unwrap();
+        transport.close_channel(info.id).unwrap();
+
+        assert_eq!(store.handle_request(&request(&info.id.to_string())).status(), StatusCode::NOT_FOUND);
+        assert_eq!(store.handle_request(&request("not-a-channel")).status(), StatusCode::BAD_REQUEST);
+        assert!(matches!(transport.publish(info.id, FrameHeader { width: 1, height: 1, frame_count: 0, timestamp: 0 }, &[0]), Err(TransportError::UnknownChannel(_))));
+    }
+
+    #[test]
+    fn frames_that_do_not_match_their_header_or_channel_are_rejected() {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let mut transport = ProtocolTransport::new(FrameStore::new(), notifier.clone());
+        let info = transport.open_channel(2, 2).unwrap();
+
+        let header = FrameHeader { width: 2, height: 2, frame_count: 0, timestamp: 0 };
+        assert!(matches!(transport.publish(info.id, header, &[0; 3]), Err(TransportError::SizeMismatch { expected: 4, actual: 3 })));
+        let header = FrameHeader { width: 4, height: 2, frame_count: 0, timestamp: 0 };
+        assert!(matches!(transport.publish(info.id, header, &[0; 8]), Err(TransportError::FrameTooLarge { capacity: 4, actual: 8 })));
+        assert!(notifier.events().is_empty());
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use super::{check_frame, encode_frame, FrameChannelInfo, FrameHeader, FrameNotifier, FrameReady, FrameTransport, TransportError, TransportKind, FRAME_HEADER_LEN};
+
+// All shared buffers are backed by Rust, due to not being able to pass SharedArrayBuffer from TS -> Rust.
+// The frontend only ever sees an opaque token, the pointer never leaves the manager.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SharedBufferToken(Uuid);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SharedBuffer {
+    pub token: SharedBufferToken,
+    pub size: u64,
+}
+
+// Sent along with the buffer so the frontend knows which channel it belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct PostedBuffer {
+    pub channel: Uuid,
+    pub token: SharedBufferToken,
+    pub size: u64,
+}
+
+// The native memory behind a shared buffer
+pub trait SharedMemory {
+    // Detaches the ArrayBuffer on the script side so it can't outlive the native memory
+    fn close(&self);
+}
+
+// The webview side of shared buffers, kept behind a trait so the transport can be driven headless
+pub trait SharedBufferHost: Send + Sync {
+    type Memory: SharedMemory + Send + Sync;
+
+    // Returns the memory along with a pointer to its first byte, valid until the memory is closed
+    fn create_buffer(&self, size: u64) -> Result<(Self::Memory, *mut u8), TransportError>;
+
+    fn post_buffer(&self, memory: &Self::Memory, posted: PostedBuffer) -> Result<(), TransportError>;
+}
+
+#[derive(Debug)]
+struct SharedBufferEntry<M: SharedMemory> {
+    memory: M,
+    data: *mut u8,
+    size: u64,
+}
+
+impl<M: SharedMemory> Drop for SharedBufferEntry<M> {
+    fn drop(&mut self) {
+        self.memory.close();
+    }
+}
+
+#[derive(Debug)]
+pub struct SharedBufferManager<M: SharedMemory> {
+    buffers: HashMap<SharedBufferToken, SharedBufferEntry<M>>,
+}
+
+impl<M: SharedMemory> Default for SharedBufferManager<M> {
+    fn default() -> Self {
+        Self { buffers: HashMap::new() }
+    }
+}
+
+impl<M: SharedMemory> SharedBufferManager<M> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_buffer<H: SharedBufferHost<Memory = M>>(&mut self, size: u64, host: &H) -> Result<(SharedBuffer, &M), TransportError> {
+        let (memory, data) = host.create_buffer(size)?;
+        if data.is_null() {
+            return Err(TransportError::Unavailable("shared buffer has no backing memory".into()));
+        }
+
+        let token = SharedBufferToken(Uuid::new_v4());
+        let entry = self.buffers.entry(token).or_insert(SharedBufferEntry { memory, data, size });
+        Ok((SharedBuffer { token, size }, &entry.memory))
+    }
+
+    pub fn release_buffer(&mut self, token: SharedBufferToken) -> Result<(), TransportError> {
+        self.buffers.remove(&token).map(|_| ()).ok_or(TransportError::UnknownBuffer(token.0))
+    }
+
+    pub fn size(&self, token: SharedBufferToken) -> Result<u64, TransportError> {
+        self.buffers.get(&token).map(|entry| entry.size).ok_or(TransportError::UnknownBuffer(token.0))
+    }
+
+    // Every access is resolved against a live entry and bounds checked, so a stale or forged token can never write
+    pub fn write(&mut self, token: SharedBufferToken, offset: u64, bytes: &[u8]) -> Result<(), TransportError> {
+        let entry = self.buffers.get_mut(&token).ok_or(TransportError::UnknownBuffer(token.0))?;
+        let end = offset.checked_add(bytes.len() as u64);
+        if end.map_or(true, |end| end > entry.size) {
+            return Err(TransportError::OutOfBounds { offset, len: bytes.len() as u64, size: entry.size });
+        }
+
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), entry.data.add(offset as usize), bytes.len());
+        }
+        Ok(())
+    }
+
+    #[cfg(test)]
+    fn read(&self, token: SharedBufferToken) -> Result<&[u8], TransportError> {
+        let entry = self.buffers.get(&token).ok_or(TransportError::UnknownBuffer(token.0))?;
+        Ok(unsafe { std::slice::from_raw_parts(entry.data, entry.size as usize) })
+    }
+}
+
+// SAFETY: the pointers only keep the manager from being Send and Sync. Each points into memory its entry owns and is
+// only written through &mut self, so the manager can cross threads whenever that memory can.
+unsafe impl<M: SharedMemory + Send> Send for SharedBufferManager<M> {}
+unsafe impl<M: SharedMemory + Sync> Sync for SharedBufferManager<M> {}
+
+#[cfg(windows)]
+mod webview2 {
+    use tauri::{AppHandle, ICoreWebView2SharedBuffer, Manager, WebviewWindow};
+    use webview2_com::Microsoft::Web::WebView2::Win32::{ICoreWebView2_17, COREWEBVIEW2_SHARED_BUFFER_ACCESS_READ_ONLY};
+    use windows::core::{ComInterface, HSTRING};
+
+    use super::{PostedBuffer, SharedBufferHost, SharedMemory, TransportError};
+
+    // COM interfaces aren't Send, the buffer is only touched again on the webview's own thread
+    struct PostableBuffer(ICoreWebView2SharedBuffer);
+
+    unsafe impl Send for PostableBuffer {}
+
+    impl PostableBuffer {
+        // Closures capture the fields they use, going through a method moves the whole Send wrapper in instead
+        fn get(&self) -> &ICoreWebView2SharedBuffer {
+            &self.0
+        }
+    }
+
+    // Keeps the window the buffer was created for, so it can be closed on the webview's thread
+    pub struct WebviewSharedBuffer {
+        buffer: ICoreWebView2SharedBuffer,
+        window: WebviewWindow,
+    }
+
+    // SAFETY: once created, the buffer's methods are only called on the webview's thread, close and post_buffer hand it
+    // over through with_webview. Other threads only move it and clone it, and WebView2 counts references atomically.
+    // Its bytes are reached through the manager's pointer alone.
+    unsafe impl Send for WebviewSharedBuffer {}
+    unsafe impl Sync for WebviewSharedBuffer {}
+
+    impl SharedMemory for WebviewSharedBuffer {
+        fn close(&self) {
+            let buffer = PostableBuffer(self.buffer.clone());
+            let result = self.window.with_webview(move |_| unsafe {
+                let _ = buffer.get().Close();
+            });
+            if let Err(err) = result {
+                log::error!("Failed to close shared buffer: {err}");
+            }
+        }
+    }
+
+    impl SharedBufferHost for AppHandle {
+        type Memory = WebviewSharedBuffer;
+
+        fn create_buffer(&self, size: u64) -> Result<(Self::Memory, *mut u8), TransportError> {
+            let window = self.get_webview_window("main").ok_or_else(|| TransportError::Unavailable("main webview window not found".into()))?;
+            let buffer = window.create_shared_buffer(size);
+            let mut data = std::ptr::null_mut();
+            unsafe {
+                buffer.Buffer(&mut data as *mut *mut u8).map_err(|err| TransportError::Unavailable(err.to_string()))?;
+            }
+            Ok((WebviewSharedBuffer { buffer, window }, data))
+        }
+
+        // Arrives in script as a sharedbufferreceived event, with the posted details as its additional data
+        fn post_buffer(&self, memory: &Self::Memory, posted: PostedBuffer) -> Result<(), TransportError> {
+            let window = self.get_webview_window("main").ok_or_else(|| TransportError::Unavailable("main webview window not found".into()))?;
+            let additional_data = HSTRING::from(serde_json::to_string(&posted).unwrap());
+            let buffer = PostableBuffer(memory.buffer.clone());
+
+            window
+                .with_webview(move |webview| {
+                    let result = unsafe {
+                        webview
+                            .controller()
+                            .CoreWebView2()
+                            .and_then(|core| core.cast::<ICoreWebView2_17>())
+                            .and_then(|core| core.PostSharedBufferToScript(buffer.get(), COREWEBVIEW2_SHARED_BUFFER_ACCESS_READ_ONLY, &additional_data))
+                    };
+                    if let Err(err) = result {
+                        log::error!("Failed to post shared buffer {:?} to script: {err}", posted.token);
+                    }
+                })
+                .map_err(|err| TransportError::Unavailable(err.to_string()))
+        }
+    }
+}
+
+pub struct SharedBufferTransport<H: SharedBufferHost> {
+    host: H,
+    manager: SharedBufferManager<H::Memory>,
+    channels: HashMap<Uuid, SharedBuffer>,
+    notifier: Arc<dyn FrameNotifier>,
+}
+
+impl<H: SharedBufferHost> SharedBufferTransport<H> {
+    pub fn new(host: H, notifier: Arc<dyn FrameNotifier>) -> Self {
+        Self {
+            host,
+            manager: SharedBufferManager::new(),
+            channels: HashMap::new(),
+            notifier,
+        }
+    }
+}
+
+impl<H: SharedBufferHost> FrameTransport for SharedBufferTransport<H> {
+    fn kind(&self) -> TransportKind {
+        TransportKind::SharedBuffer
+    }
+
+    // The buffer is posted to script straight away, frames are then only announced and read in place
+    fn open_channel(&mut self, width: u32, height: u32) -> Result<FrameChannelInfo, TransportError> {
+        let capacity = width as usize * height as usize;
+        let (buffer, memory) = self.manager.new_buffer((FRAME_HEADER_LEN + capacity * 2) as u64, &self.host)?;
+        let id = Uuid::new_v4();
+        if let Err(err) = self.host.post_buffer(memory, PostedBuffer { channel: id, token: buffer.token, size: buffer.size }) {
+            self.manager.release_buffer(buffer.token)?;
+            return Err(err);
+        }
+        self.channels.insert(id, buffer);
+
+        Ok(FrameChannelInfo { id, kind: self.kind(), capacity })
+    }
+
+    fn close_channel(&mut self, channel: Uuid) -> Result<(), TransportError> {
+        let buffer = self.channels.remove(&channel).ok_or(TransportError::UnknownChannel(channel))?;
+        self.manager.release_buffer(buffer.token)
+    }
+
+    // Each channel has a single buffer, so a frame script is still reading can be overwritten by the next one and show
+    // parts of both. Script copies a frame out as soon as it is announced, which leaves only the copy open to tearing.
+    fn publish(&mut self, channel: Uuid, header: FrameHeader, data: &[u16]) -> Result<(), TransportError> {
+        let buffer = self.channels.get(&channel).ok_or(TransportError::UnknownChannel(channel))?;
+        let capacity = (self.manager.size(buffer.token)? as usize).saturating_sub(FRAME_HEADER_LEN) / 2;
+        check_frame(&header, data, capacity)?;
+        self.manager.write(buffer.token, 0, &encode_frame(&header, data))?;
+
+        self.notifier.frame_ready(FrameReady { channel, kind: self.kind(), header });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use super::super::tests::RecordingNotifier;
+    use super::*;
+
+    struct MockMemory {
+        bytes: Box<[u8]>,
+        closed: Arc<AtomicUsize>,
+    }
+
+    impl SharedMemory for MockMemory {
+        fn close(&self) {
+            self.closed.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    // Stands in for WebView2, keeping what would have been posted to script
+    #[derive(Default)]
+    struct MockWebview {
+        posted: Mutex<Vec<PostedBuffer>>,
+        closed: Arc<AtomicUsize>,
+        refuse_posts: bool,
+    }
+
+    impl SharedBufferHost for MockWebview {
+        type Memory = MockMemory;
+
+        fn create_buffer(&self, size: u64) -> Result<(Self::Memory, *mut u8), TransportError> {
+            let mut bytes = vec![0; size as usize].into_boxed_slice();
+            let data = bytes.as_mut_ptr();
+            Ok((MockMemory { bytes, closed: self.closed.clone() }, data))
+        }
+
+        fn post_buffer(&self, memory: &Self::Memory, posted: PostedBuffer) -> Result<(), TransportError> {
+            if self.refuse_posts {
+                return Err(TransportError::Unavailable("script is not listening".into()));
+            }
+            assert_eq!(memory.bytes.len() as u64, posted.size);
+            self.posted.lock().unwrap().push(posted);
+            Ok(())
+        }
+    }
+
+    fn manager_with_buffer(size: u64) -> (SharedBufferManager<MockMemory>, SharedBuffer) {
+        let mut manager = SharedBufferManager::new();
+        let (buffer, _) = manager.new_buffer(size, &MockWebview::default()).unwrap();
+        (manager, buffer)
+    }
+
+    #[test]
+    fn writes_inside_the_buffer_land_at_the_offset() {
+        let (mut manager, buffer) = manager_with_buffer(8);
+        manager.write(buffer.token, 2, &[1, 2, 3]).unwrap();
+        manager.write(buffer.token, 5, &[4, 5, 6]).unwrap();
+
+        assert_eq!(manager.read(buffer.token).unwrap(), &[0, 0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn writes_past_the_end_are_rejected() {
+        let (mut manager, buffer) = manager_with_buffer(8);
+
+        assert!(matches!(manager.write(buffer.token, 6, &[0; 3]), Err(TransportError::OutOfBounds { offset: 6, len: 3, size: 8 })));
+        assert!(matches!(manager.write(buffer.token, 9, &[]), Err(TransportError::OutOfBounds { .. })));
+        assert!(matches!(manager.write(buffer.token, u64::MAX, &[0]), Err(TransportError::OutOfBounds { .. })));
+        assert_eq!(manager.read(buffer.token).unwrap(), &[0; 8]);
+    }
+
+    #[test]
+    fn released_and_forged_tokens_are_unknown() {
+        let (mut manager, buffer) = manager_with_buffer(8);
+        let forged = SharedBufferToken(Uuid::new_v4());
+
+        assert!(matches!(manager.write(forged, 0, &[0]), Err(TransportError::UnknownBuffer(id)) if id == forged.0));
+        manager.release_buffer(buffer.token).unwrap();
+        assert!(matches!(manager.write(buffer.token, 0, &[0]), Err(TransportError::UnknownBuffer(_))));
+        assert!(matches!(manager.size(buffer.token), Err(TransportError::UnknownBuffer(_))));
+        assert!(matches!(manager.release_buffer(buffer.token), Err(TransportError::UnknownBuffer(_))));
+    }
+
+    #[test]
+    fn opened_channel_posts_its_buffer_and_publishes_into_it() {
+        let webview = MockWebview::default();
+        let closed = webview.closed.clone();
+        let notifier = Arc::new(RecordingNotifier::default());
+        let mut transport = SharedBufferTransport::new(webview, notifier.clone());
+
+        let info = transport.open_channel(4, 2).unwrap();
+        let posted = transport.host.posted.lock().unwrap().clone();
+        assert_eq!(posted, vec![PostedBuffer { channel: info.id, token: transport.channels[&info.id].token, size: (FRAME_HEADER_LEN + 16) as u64 }]);
+
+        let header = FrameHeader { width: 4, height: 2, frame_count: 7, timestamp: 1234 };
+        let pixels: Vec<u16> = (0..8).map(|pixel| pixel * 1000).collect();
+        transport.publish(info.id, header, &pixels).unwrap();
+
+        let bytes = transport.manager.read(posted[0].token).unwrap();
+        assert_eq!(bytes, encode_frame(&header, &pixels));
+        assert_eq!(notifier.events(), vec![(info.id, TransportKind::SharedBuffer, header)]);
+
+        transport.close_channel(info.id).unwrap();
+        assert_eq!(closed.load(Ordering::SeqCst), 1);
+        assert!(matches!(transport.publish(info.id, header, &pixels), Err(TransportError::UnknownChannel(_))));
+    }
+
+    #[test]
+    fn frames_larger_than_the_channel_are_not_written() {
+        let notifier = Arc::new(RecordingNotifier::default());
+        let mut transport = SharedBufferTransport::new(MockWebview::default(), notifier.clone());
+        let info = transport.open_channel(2, 2).unwrap();
+
+        let header = FrameHeader { width: 3, height: 2, frame_count: 0, timestamp: 0 };
+        assert!(matches!(transport.publish(info.id, header, &[0; 6]), Err(TransportError::FrameTooLarge { capacity: 4, actual: 6 })));
+        assert!(notifier.events().is_empty());
+    }
+
+    #[test]
+    fn buffer_is_released_when_it_cannot_be_posted() {
+        let webview = MockWebview { refuse_posts: true, ..Default::default() };
+        let closed = webview.closed.clone();
+        let mut transport = SharedBufferTransport::new(webview, Arc::new(RecordingNotifier::default()));
+
+        assert!(matches!(transport.open_channel(2, 2), Err(TransportError::Unavailable(_))));
+        assert_eq!(closed.load(Ordering::SeqCst), 1);
+        assert!(transport.manager.buffers.is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::thread;
+
+use tauri::{AppHandle, Manager};
+use viewer::application::Viewer;
+use viewer::event_bridge::MessageSender;
+use viewer::messages::frontend::FrontendMessage;
+use viewer::messages::portfolio::image::utility_types::misc::ImageId;
+
+use crate::transport::{DisplayChannels, FrameHeader, TransportError, DISPLAY_CHANNEL_EVENT};
+use crate::FrameTransportState;
+
+pub const FRONTEND_MESSAGE_EVENT: &str = "frontend-message";
+
+pub struct ViewerState(pub MessageSender);
+
+// The viewer isn't Send, so it lives on a thread of its own and commands reach it through its message sender
+pub fn spawn_viewer(app: AppHandle) -> MessageSender {
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("viewer".into())
+        .spawn(move || {
+            let (mut viewer, mut frontend_messages) = Viewer::new();
+            sender.send(viewer.message_sender()).unwrap();
+            let mut display_channels = DisplayChannels::default();
+            loop {
+                tauri::async_runtime::block_on(viewer.next_tick());
+                while let Ok(message) = frontend_messages.try_recv() {
+                    forward_message(&app, &viewer, &mut display_channels, message);
+                }
+            }
+        })
+        .expect("failed to spawn the viewer thread");
+    receiver.recv().unwrap()
+}
+
+// Display images go through the frame transport, everything else is emitted to the webview as is
+fn forward_message(app: &AppHandle, viewer: &Viewer, display_channels: &mut DisplayChannels, message: FrontendMessage) {
+    let result = match &message {
+        FrontendMessage::UpdateDisplayImage { image_id, .. } => publish_display_image(app, viewer, display_channels, *image_id),
+        FrontendMessage::UpdateOpenImages { images, .. } => {
+            let open: HashSet<_> = images.iter().map(|image| image.id).collect();
+            let frame_transport = app.state::<FrameTransportState>();
+            let mut frame_transport = frame_transport.0.lock().unwrap();
+            display_channels.retain(frame_transport.as_mut(), |image_id| open.contains(&image_id)).map(|closed| {
+                for display_channel in closed {
+                    let _ = app.emit(DISPLAY_CHANNEL_EVENT, display_channel);
+                }
+            })
+        }
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        log::error!("Failed to update the display frames: {err}");
+    }
+    let _ = app.emit(FRONTEND_MESSAGE_EVENT, message);
+}
+
+// Live frames land in the live image, so they are published here along with every other displayed image. The display
+// image is 8-bit and is widened to the transport's u16 pixels, the header's frame count is the index of the frame shown.
+fn publish_display_image(app: &AppHandle, viewer: &Viewer, display_channels: &mut DisplayChannels, image_id: ImageId) -> Result<(), TransportError> {
+    // Closed before its update was forwarded
+    let Some(image) = viewer.dispatcher.portfolio().image(image_id) else { return Ok(()) };
+    let display_image = image.display_image();
+    let (width, height) = display_image.dimensions();
+    let header = FrameHeader { width, height, frame_count: image.current_frame() as u32, timestamp: 0 };
+    let pixels: Vec<u16> = display_image.into_raw().into_iter().map(u16::from).collect();
+
+    let frame_transport = app.state::<FrameTransportState>();
+    let mut frame_transport = frame_transport.0.lock().unwrap();
+    let (channel, opened) = display_channels.channel(frame_transport.as_mut(), image_id, width, height)?;
+    if let Some(display_channel) = opened {
+        let _ = app.emit(DISPLAY_CHANNEL_EVENT, display_channel);
+    }
+    frame_transport.publish(channel, header, &pixels)
+}
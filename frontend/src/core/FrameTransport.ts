@@ -0,0 +1,134 @@
+import { invoke } from "@tauri-apps/api/core";
+import { listen, type UnlistenFn } from "@tauri-apps/api/event";
+
+export const FRAME_HEADER_LEN = 24;
+const FRAME_HEADER_MAGIC = "CVF1";
+
+export type TransportKind = "sharedBuffer" | "protocol";
+
+export interface FrameHeader {
+  width: number;
+  height: number;
+  frame_count: number;
+  timestamp: number;
+}
+
+// Sent by the backend when it opens an image's channel, the channel is null once the image is closed
+export interface DisplayChannel {
+  imageId: string;
+  channel: string | null;
+}
+
+export interface FrameReady {
+  channel: string;
+  kind: TransportKind;
+  header: FrameHeader;
+}
+
+export interface Frame {
+  header: FrameHeader;
+  pixels: Uint16Array;
+}
+
+// Additional data WebView2 delivers along with each posted buffer
+interface PostedBuffer {
+  channel: string;
+  token: string;
+  size: number;
+}
+
+interface SharedBufferReceivedEvent extends Event {
+  additionalData: PostedBuffer;
+  getBuffer(): ArrayBuffer;
+}
+
+// Each shared buffer channel's buffer, posted once right after the channel is opened
+const sharedBuffers = new Map<string, ArrayBuffer>();
+
+// Registered before any channel can be opened, so no posted buffer is missed
+(window as any).chrome?.webview?.addEventListener("sharedbufferreceived", (event: SharedBufferReceivedEvent) => {
+  sharedBuffers.set(event.additionalData.channel, event.getBuffer());
+});
+
+// The image whose display frames arrive on each channel, channels are opened at the image's size when it is first shown
+const displayChannels = new Map<string, string>();
+
+listen<DisplayChannel>("display-channel", (event) => {
+  const { imageId, channel } = event.payload;
+  for (const [previous, previousImageId] of displayChannels) {
+    if (previousImageId === imageId) {
+      displayChannels.delete(previous);
+      sharedBuffers.delete(previous);
+    }
+  }
+  if (channel) {
+    displayChannels.set(channel, imageId);
+  }
+});
+
+export function decodeFrame(buffer: ArrayBuffer): Frame {
+  const view = new DataView(buffer);
+  const magic = String.fromCharCode(...new Uint8Array(buffer, 0, 4));
+  if (magic !== FRAME_HEADER_MAGIC) {
+    throw new Error("Invalid frame header");
+  }
+
+  const header = {
+    width: view.getUint32(4, true),
+    height: view.getUint32(8, true),
+    frame_count: view.getUint32(12, true),
+    timestamp: Number(view.getBigUint64(16, true)),
+  };
+
+  return { header, pixels: new Uint16Array(buffer, FRAME_HEADER_LEN, header.width * header.height) };
+}
+
+function frameUrl(channel: string): string {
+  // WebView2 maps custom protocols onto http://<scheme>.localhost
+  return navigator.userAgent.includes("Windows") ? `http://frames.localhost/${channel}` : `frames://localhost/${channel}`;
+}
+
+// Resolves to null when the frame was already fetched and nothing newer has been published
+export async function fetchFrame(channel: string): Promise<Frame | null> {
+  const response = await fetch(frameUrl(channel));
+  if (response.status === 204) {
+    return null;
+  }
+  if (!response.ok) {
+    throw new Error(`Failed to fetch frame for channel ${channel}: ${response.status}`);
+  }
+  return decodeFrame(await response.arrayBuffer());
+}
+
+export async function frameTransportKind(): Promise<TransportKind> {
+  return invoke<TransportKind>("frame_transport_kind");
+}
+
+// Shared buffer frames are read directly from the buffer posted by WebView2, only protocol frames need fetching
+export async function onFrame(callback: (channel: string, frame: Frame) => void): Promise<UnlistenFn> {
+  return listen<FrameReady>("frame-ready", async (event) => {
+    const { channel, kind } = event.payload;
+    if (kind === "sharedBuffer") {
+      const buffer = sharedBuffers.get(channel);
+      if (buffer) {
+        // Copied straight away, the next frame is written into the same buffer
+        callback(channel, decodeFrame(buffer.slice(0)));
+      }
+    } else {
+      const frame = await fetchFrame(channel);
+      if (frame) {
+        callback(channel, frame);
+      }
+    }
+  });
+}
+
+// Display images hold 8-bit values widened to u16, the header's frame count is the index of the frame shown
+export async function onDisplayImage(callback: (imageId: string, frame: Frame) => void): Promise<UnlistenFn> {
+  return onFrame((channel, frame) => {
+    const imageId = displayChannels.get(channel);
+    if (imageId) {
+      callback(imageId, frame);
+    }
+  });
+}
@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::portfolio::image::utility_types::misc::ImageFrame;
+
+use super::AnalysisError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum FrameAggregate {
+    Average,
+    Sum,
+    Median,
+    Maximum,
+    Minimum,
+    StandardDeviation,
+}
+
+pub struct AggregatedFrame {
+    pub frame: ImageFrame,
+    // Images hold 16-bit frames, so a sum that doesn't fit is divided by this, it is 1 for everything else
+    pub divisor: u64,
+}
+
+pub fn aggregate_frames(frames: &[ImageFrame], aggregate: FrameAggregate) -> Result<AggregatedFrame, AnalysisError> {
+    let first = frames.first().ok_or(AnalysisError::NoFrames)?;
+    if frames.iter().any(|frame| frame.dimensions() != first.dimensions()) {
+        return Err(AnalysisError::FrameSizeMismatch);
+    }
+    let (width, height) = first.dimensions();
+    let count = frames.len() as f64;
+
+    let per_pixel = |reduce: &dyn Fn(&mut dyn Iterator<Item = u16>) -> u16| -> ImageFrame {
+        let data = (0..first.as_raw().len()).map(|index| reduce(&mut frames.iter().map(|frame| frame.as_raw()[index]))).collect();
+        ImageFrame::from_raw(width, height, data).unwrap()
+    };
+
+    let mut divisor = 1;
+    let image = match aggregate {
+        // The smallest divisor that brings the largest sum into range, so the sum keeps as much precision as it can
+        FrameAggregate::Sum => {
+            let sum = sum_frames(frames);
+            divisor = sum.iter().copied().max().unwrap_or(0).div_ceil(u16::MAX as u64).max(1);
+            ImageFrame::from_raw(width, height, sum.iter().map(|&value| ((value + divisor / 2) / divisor) as u16).collect()).unwrap()
+        }
+        FrameAggregate::Average => {
+            let sum = sum_frames(frames);
+            ImageFrame::from_raw(width, height, sum.iter().map(|&value| (value as f64 / count).round() as u16).collect()).unwrap()
+        }
+        FrameAggregate::Maximum => per_pixel(&|values| values.max().unwrap_or(0)),
+        FrameAggregate::Minimum => per_pixel(&|values| values.min().unwrap_or(0)),
+        FrameAggregate::Median => {
+            let mut scratch = Vec::with_capacity(frames.len());
+            let data = (0..first.as_raw().len())
+                .map(|index| {
+                    scratch.clear();
+                    scratch.extend(frames.iter().map(|frame| frame.as_raw()[index]));
+                    let (middle, even) = (scratch.len() / 2, scratch.len() % 2 == 0);
+                    let (lower, &mut upper_middle, _) = scratch.select_nth_unstable(middle);
+                    match lower.iter().max() {
+                        Some(&lower_middle) if even => ((lower_middle as u32 + upper_middle as u32 + 1) / 2) as u16,
+                        _ => upper_middle,
+                    }
+                })
+                .collect();
+            ImageFrame::from_raw(width, height, data).unwrap()
+        }
+        FrameAggregate::StandardDeviation => {
+            let sum = sum_frames(frames);
+            let data = sum
+                .iter()
+                .enumerate()
+                .map(|(index, &total)| {
+                    let mean = total as f64 / count;
+                    let squared_deviations: f64 = frames.iter().map(|frame| (frame.as_raw()[index] as f64 - mean).powi(2)).sum();
+                    if frames.len() > 1 { (squared_deviations / (count - 1.)).sqrt().round() as u16 } else { 0 }
+                })
+                .collect();
+            ImageFrame::from_raw(width, height, data).unwrap()
+        }
+    };
+    Ok(AggregatedFrame { frame: image, divisor })
+}
+
+// Accumulated at 64 bits, 32 would overflow past 65537 frames
+fn sum_frames(frames: &[ImageFrame]) -> Vec<u64> {
+    let mut sum = vec![0u64; frames[0].as_raw().len()];
+    for frame in frames {
+        for (total, &value) in sum.iter_mut().zip(frame.as_raw()) {
+            *total += value as u64;
+        }
+    }
+    sum
+}
+
+// Average of the last `length` frames, updated in constant time per pixel as frames arrive
+pub struct RollingAverage {
+    length: usize,
+    frames: VecDeque<ImageFrame>,
+    sum: Vec<u32>,
+}
+
+impl RollingAverage {
+    pub fn new(length: usize) -> Self {
+        Self {
+            length: length.max(1),
+            frames: VecDeque::new(),
+            sum: Vec::new(),
+        }
+    }
+
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn push(&mut self, frame: ImageFrame) -> ImageFrame {
+        // A change of ROI or binning restarts the average
+        if self.frames.front().is_some_and(|first| first.dimensions() != frame.dimensions()) {
+            self.reset();
+        }
+        if self.sum.is_empty() {
+            self.sum = vec![0; frame.as_raw().len()];
+        }
+
+        for (total, &value) in self.sum.iter_mut().zip(frame.as_raw()) {
+            *total += value as u32;
+        }
+        let (width, height) = frame.dimensions();
+        self.frames.push_back(frame);
+        if self.frames.len() > self.length {
+            let oldest = self.frames.pop_front().unwrap();
+            for (total, &value) in self.sum.iter_mut().zip(oldest.as_raw()) {
+                *total -= value as u32;
+            }
+        }
+
+        let count = self.frames.len() as u32;
+        let data = self.sum.iter().map(|&total| ((total + count / 2) / count) as u16).collect();
+        ImageFrame::from_raw(width, height, data).unwrap()
+    }
+
+    pub fn reset(&mut self) {
+        self.frames.clear();
+        self.sum.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(values: [u16; 2]) -> ImageFrame {
+        ImageFrame::from_raw(2, 1, values.to_vec()).unwrap()
+    }
+
+    fn aggregate(frames: &[ImageFrame], aggregate: FrameAggregate) -> Vec<u16> {
+        aggregate_frames(frames, aggregate).unwrap().frame.into_raw()
+    }
+
+    #[test]
+    fn per_pixel_aggregates() {
+        let frames = [frame([1, 10]), frame([4, 30]), frame([2, 20]), frame([9, 20])];
+        assert_eq!(aggregate(&frames, FrameAggregate::Average), [4, 20]);
+        assert_eq!(aggregate(&frames, FrameAggregate::Sum), [16, 80]);
+        assert_eq!(aggregate(&frames, FrameAggregate::Maximum), [9, 30]);
+        assert_eq!(aggregate(&frames, FrameAggregate::Minimum), [1, 10]);
+        // An even count takes the mean of the middle two, rounded up
+        assert_eq!(aggregate(&frames, FrameAggregate::Median), [3, 20]);
+        assert_eq!(aggregate(&frames[..3], FrameAggregate::Median), [2, 20]);
+        assert_eq!(aggregate(&frames[1..3], FrameAggregate::StandardDeviation), [1, 7]);
+    }
+
+    #[test]
+    fn single_frame_has_no_deviation() {
+        let frames = [frame([5, 6])];
+        assert_eq!(aggregate(&frames, FrameAggregate::StandardDeviation), [0, 0]);
+        assert_eq!(aggregate(&frames, FrameAggregate::Median), [5, 6]);
+    }
+
+    #[test]
+    fn sums_beyond_16_bits_are_divided_into_range() {
+        let frames = [frame([u16::MAX, 1]), frame([1, 1])];
+        let sum = aggregate_frames(&frames, FrameAggregate::Sum).unwrap();
+        assert_eq!((sum.frame.into_raw(), sum.divisor), (vec![32768, 1], 2));
+        assert_eq!(aggregate_frames(&frames, FrameAggregate::Average).unwrap().divisor, 1);
+        assert_eq!(aggregate(&frames, FrameAggregate::Average), [u16::MAX / 2 + 1, 1]);
+
+        // Past the 65537 frames that would overflow a 32-bit sum
+        let frames = vec![frame([u16::MAX, 0]); 70_000];
+        let sum = aggregate_frames(&frames, FrameAggregate::Sum).unwrap();
+        assert_eq!((sum.frame.into_raw(), sum.divisor), (vec![u16::MAX, 0], 70_000));
+    }
+
+    #[test]
+    fn unusable_frames_are_rejected() {
+        assert!(matches!(aggregate_frames(&[], FrameAggregate::Average), Err(AnalysisError::NoFrames)));
+        let mismatched = [frame([1, 2]), ImageFrame::new(1, 2)];
+        assert!(matches!(aggregate_frames(&mismatched, FrameAggregate::Average), Err(AnalysisError::FrameSizeMismatch)));
+    }
+
+    #[test]
+    fn rolling_average_covers_the_latest_frames() {
+        let mut average = RollingAverage::new(3);
+        assert_eq!(average.push(frame([3, 0])).into_raw(), [3, 0]);
+        assert_eq!(average.push(frame([6, 1])).into_raw(), [5, 1]);
+        assert_eq!(average.push(frame([9, 2])).into_raw(), [6, 1]);
+        // The first frame drops out
+        assert_eq!(average.push(frame([12, 3])).into_raw(), [9, 2]);
+    }
+
+    #[test]
+    fn rolling_average_restarts_when_the_frame_size_changes() {
+        let mut average = RollingAverage::new(3);
+        average.push(frame([100, 100]));
+        let resized = ImageFrame::from_raw(1, 1, vec![7]).unwrap();
+        assert_eq!(average.push(resized).into_raw(), [7]);
+
+        average.reset();
+        assert_eq!(average.push(frame([1, 2])).into_raw(), [1, 2]);
+        assert_eq!(RollingAverage::new(0).length(), 1);
+    }
+}
@@ -0,0 +1,60 @@
+pub mod aggregate;
+pub mod mtf;
+pub mod nps;
+
+// Pixel values of a rectangular region in row-major order
+#[derive(Debug, Clone)]
+pub struct Region {
+    pub data: Vec<f64>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Region {
+    pub fn get(&self, x: usize, y: usize) -> f64 {
+        self.data[y * self.width + x]
+    }
+
+    pub fn transposed(&self) -> Self {
+        let data = (0..self.width).flat_map(|x| (0..self.height).map(move |y| self.get(x, y))).collect();
+        Self { data, width: self.height, height: self.width }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum AnalysisError {
+    #[error("region of {width}x{height} pixels is too small, at least {minimum}x{minimum} is needed")]
+    RegionTooSmall { width: usize, height: usize, minimum: usize },
+    #[error("no edge found in the region")]
+    NoEdge,
+    #[error("edge angle of {0:.2} degrees is outside the usable range of 1 to 15 degrees")]
+    EdgeAngle(f64),
+    #[error("no frames to analyse")]
+    NoFrames,
+    #[error("frames have different dimensions")]
+    FrameSizeMismatch,
+    #[error("pixel pitch is unknown")]
+    MissingPixelPitch,
+    #[error("incident fluence must be positive, got {0}")]
+    InvalidFluence(f64),
+}
+
+// Plain DFT magnitudes for the first `count` frequencies, the signals analysed here are only a few hundred samples long
+pub(crate) fn dft_magnitudes(signal: &[f64], count: usize) -> Vec<f64> {
+    let n = signal.len() as f64;
+    (0..count)
+        .map(|k| {
+            let (re, im) = signal.iter().enumerate().fold((0., 0.), |(re, im), (index, &value)| {
+                let phase = -2. * std::f64::consts::PI * k as f64 * index as f64 / n;
+                (re + value * phase.cos(), im + value * phase.sin())
+            });
+            (re * re + im * im).sqrt()
+        })
+        .collect()
+}
+
+pub(crate) fn hamming_window(length: usize, centre: f64) -> impl Iterator<Item = f64> {
+    // Wide enough to cover the whole signal whichever side the centre is closer to
+    let half_width = centre.max(length as f64 - 1. - centre).max(1.);
+    (0..length).map(move |index| 0.54 + 0.46 * (std::f64::consts::PI * (index as f64 - centre) / half_width).cos())
+}
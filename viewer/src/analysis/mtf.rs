@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+
+use super::{dft_magnitudes, hamming_window, AnalysisError, Region};
+
+pub const OVERSAMPLING: usize = 4;
+const MINIMUM_REGION_SIZE: usize = 16;
+const MINIMUM_EDGE_ANGLE: f64 = 1.;
+const MAXIMUM_EDGE_ANGLE: f64 = 15.;
+const NYQUIST: f64 = 0.5;
+
+// Frequencies are in cycles per pixel, divide by the pixel pitch for line pairs per millimetre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct MtfResult {
+    pub edge_angle_degrees: f64,
+    pub oversampling: u32,
+    pub esf: Vec<f64>,
+    pub lsf: Vec<f64>,
+    pub frequencies: Vec<f64>,
+    pub mtf: Vec<f64>,
+    pub mtf_at_nyquist: f64,
+    pub mtf50: Option<f64>,
+    pub pixel_pitch_mm: Option<f64>,
+}
+
+impl MtfResult {
+    // Linearly interpolated MTF at the given frequency in cycles per pixel
+    pub fn mtf_at(&self, frequency: f64) -> f64 {
+        interpolate(&self.frequencies, &self.mtf, frequency)
+    }
+}
+
+// Slanted-edge method as described in ISO 12233 and IEC 62220-1
+pub fn slanted_edge_mtf(region: &Region, pixel_pitch_mm: Option<f64>) -> Result<MtfResult, AnalysisError> {
+    if region.width < MINIMUM_REGION_SIZE || region.height < MINIMUM_REGION_SIZE {
+        return Err(AnalysisError::RegionTooSmall { width: region.width, height: region.height, minimum: MINIMUM_REGION_SIZE });
+    }
+
+    // Work on a near-vertical edge, so each row crosses it once
+    let horizontal_gradient: f64 = (0..region.height).flat_map(|y| (1..region.width).map(move |x| (y, x))).map(|(y, x)| (region.get(x, y) - region.get(x - 1, y)).abs()).sum();
+    let vertical_gradient: f64 = (1..region.height).flat_map(|y| (0..region.width).map(move |x| (y, x))).map(|(y, x)| (region.get(x, y) - region.get(x, y - 1)).abs()).sum();
+    let region = if vertical_gradient > horizontal_gradient { region.transposed() } else { region.clone() };
+
+    let (intercept, slope) = fit_edge(&region)?;
+    let edge_angle_degrees = slope.atan().to_degrees();
+    if !(MINIMUM_EDGE_ANGLE..=MAXIMUM_EDGE_ANGLE).contains(&edge_angle_degrees.abs()) {
+        return Err(AnalysisError::EdgeAngle(edge_angle_degrees));
+    }
+
+    let esf = oversampled_esf(&region, intercept, slope);
+    let bin_width = 1. / OVERSAMPLING as f64;
+
+    let mut lsf: Vec<f64> = (0..esf.len())
+        .map(|index| {
+            let (before, after) = (esf[index.saturating_sub(1)], esf[(index + 1).min(esf.len() - 1)]);
+            (after - before) / 2.
+        })
+        .collect();
+    let peak = lsf.iter().enumerate().max_by(|a, b| a.1.abs().total_cmp(&b.1.abs())).map(|(index, _)| index).unwrap_or(0);
+    for (value, weight) in lsf.iter_mut().zip(hamming_window(esf.len(), peak as f64)) {
+        *value *= weight;
+    }
+
+    // Up to the sampling frequency, twice Nyquist, which is what detector reports usually show
+    let frequency_step = 1. / (lsf.len() as f64 * bin_width);
+    let frequency_count = ((2. * NYQUIST / frequency_step).floor() as usize + 1).min(lsf.len() / 2 + 1);
+    let magnitudes = dft_magnitudes(&lsf, frequency_count);
+    let dc = magnitudes.first().copied().filter(|&dc| dc > 0.).ok_or(AnalysisError::NoEdge)?;
+
+    let frequencies: Vec<f64> = (0..frequency_count).map(|k| k as f64 * frequency_step).collect();
+    let mtf: Vec<f64> = magnitudes
+        .iter()
+        .zip(&frequencies)
+        .map(|(magnitude, &frequency)| magnitude / dc / derivative_correction(frequency, bin_width))
+        .collect();
+
+    let mtf50 = frequencies.windows(2).zip(mtf.windows(2)).find(|(_, m)| m[0] >= 0.5 && m[1] < 0.5).map(|(f, m)| f[0] + (f[1] - f[0]) * (m[0] - 0.5) / (m[0] - m[1]));
+    let mtf_at_nyquist = interpolate(&frequencies, &mtf, NYQUIST);
+
+    Ok(MtfResult {
+        edge_angle_degrees,
+        oversampling: OVERSAMPLING as u32,
+        esf,
+        lsf,
+        frequencies,
+        mtf,
+        mtf_at_nyquist,
+        mtf50,
+        pixel_pitch_mm,
+    })
+}
+
+// Fits column = intercept + slope * row to the centroids of each row's derivative
+fn fit_edge(region: &Region) -> Result<(f64, f64), AnalysisError> {
+    let centroids = |window: Option<(f64, f64)>| -> Vec<(f64, f64)> {
+        (0..region.height)
+            .filter_map(|y| {
+                let derivative: Vec<f64> = (1..region.width - 1).map(|x| (region.get(x + 1, y) - region.get(x - 1, y)).abs() / 2.).collect();
+                let weights: Vec<f64> = match window {
+                    Some((intercept, slope)) => hamming_window(derivative.len(), intercept + slope * y as f64 - 1.).collect(),
+                    None => vec![1.; derivative.len()],
+                };
+                let total: f64 = derivative.iter().zip(&weights).map(|(d, w)| d * w).sum();
+                (total > 0.).then(|| (y as f64, derivative.iter().zip(&weights).enumerate().map(|(x, (d, w))| (x + 1) as f64 * d * w).sum::<f64>() / total))
+            })
+            .collect()
+    };
+
+    let first_fit = linear_fit(&centroids(None)).ok_or(AnalysisError::NoEdge)?;
+    linear_fit(&centroids(Some(first_fit))).ok_or(AnalysisError::NoEdge)
+}
+
+fn linear_fit(points: &[(f64, f64)]) -> Option<(f64, f64)> {
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let (mean_x, mean_y) = (points.iter().map(|p| p.0).sum::<f64>() / n, points.iter().map(|p| p.1).sum::<f64>() / n);
+    let covariance: f64 = points.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+    if variance == 0. {
+        return None;
+    }
+    let slope = covariance / variance;
+    Some((mean_y - slope * mean_x, slope))
+}
+
+// Projects every pixel onto the edge normal and averages them in quarter-pixel bins
+fn oversampled_esf(region: &Region, intercept: f64, slope: f64) -> Vec<f64> {
+    let cos_angle = slope.atan().cos();
+    let distance = |x: usize, y: usize| (x as f64 - (intercept + slope * y as f64)) * cos_angle;
+
+    let (mut min_distance, mut max_distance) = (f64::MAX, f64::MIN);
+    for y in 0..region.height {
+        for x in 0..region.width {
+            min_distance = min_distance.min(distance(x, y));
+            max_distance = max_distance.max(distance(x, y));
+        }
+    }
+
+    let bin_count = ((max_distance - min_distance) * OVERSAMPLING as f64).floor() as usize + 1;
+    let mut sums = vec![0.; bin_count];
+    let mut counts = vec![0u32; bin_count];
+    for y in 0..region.height {
+        for x in 0..region.width {
+            let bin = ((distance(x, y) - min_distance) * OVERSAMPLING as f64).floor() as usize;
+            sums[bin.min(bin_count - 1)] += region.get(x, y);
+            counts[bin.min(bin_count - 1)] += 1;
+        }
+    }
+
+    // Bins near the corners of the region can be empty, fill them from their neighbours
+    let mut esf: Vec<Option<f64>> = sums.iter().zip(&counts).map(|(&sum, &count)| (count > 0).then(|| sum / count as f64)).collect();
+    for index in 0..esf.len() {
+        if esf[index].is_none() {
+            let before = esf[..index].iter().rev().find_map(|value| *value);
+            let after = esf[index + 1..].iter().find_map(|value| *value);
+            esf[index] = match (before, after) {
+                (Some(before), Some(after)) => Some((before + after) / 2.),
+                (value, None) | (None, value) => value,
+            };
+        }
+    }
+    esf.into_iter().map(|value| value.unwrap_or(0.)).collect()
+}
+
+// The central difference used for the LSF acts as a two-bin moving average
+fn derivative_correction(frequency: f64, bin_width: f64) -> f64 {
+    let argument = std::f64::consts::PI * frequency * 2. * bin_width;
+    if argument == 0. {
+        1.
+    } else {
+        (argument.sin() / argument).max(0.1)
+    }
+}
+
+fn interpolate(xs: &[f64], ys: &[f64], x: f64) -> f64 {
+    match xs.iter().position(|&value| value >= x) {
+        Some(0) => ys.first().copied().unwrap_or(0.),
+        Some(index) => {
+            let t = (x - xs[index - 1]) / (xs[index] - xs[index - 1]);
+            ys[index - 1] + t * (ys[index] - ys[index - 1])
+        }
+        None => ys.last().copied().unwrap_or(0.),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: usize = 64;
+    // Scale of the logistic edge profile in pixels
+    const EDGE_SCALE: f64 = 0.5;
+
+    // A logistic edge crossing the region at the given angle from vertical, sampled at pixel centres
+    fn slanted_edge(angle_degrees: f64) -> Region {
+        let (tan, cos) = (angle_degrees.to_radians().tan(), angle_degrees.to_radians().cos());
+        let data = (0..SIZE)
+            .flat_map(|y| (0..SIZE).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let distance = (x as f64 - (SIZE as f64 / 2. + tan * (y as f64 - SIZE as f64 / 2.))) * cos;
+                100. + 1000. / (1. + (-distance / EDGE_SCALE).exp())
+            })
+            .collect();
+        Region { data, width: SIZE, height: SIZE }
+    }
+
+    // The logistic line spread function transforms to x / sinh(x)
+    fn expected_mtf(frequency: f64) -> f64 {
+        let x = 2. * std::f64::consts::PI.powi(2) * EDGE_SCALE * frequency;
+        if x == 0. { 1. } else { x / x.sinh() }
+    }
+
+    #[test]
+    fn slanted_edge_matches_the_known_mtf() {
+        let result = slanted_edge_mtf(&slanted_edge(5.), Some(0.1)).unwrap();
+        assert!((result.edge_angle_degrees.abs() - 5.).abs() < 0.1, "angle {}", result.edge_angle_degrees);
+        assert_eq!(result.mtf[0], 1.);
+
+        for frequency in [0.05, 0.1, 0.2, 0.3, 0.4] {
+            let (measured, expected) = (result.mtf_at(frequency), expected_mtf(frequency));
+            assert!((measured - expected).abs() < 0.03, "MTF at {frequency} is {measured}, expected {expected}");
+        }
+        assert!(result.mtf_at_nyquist < 0.1);
+
+        // x / sinh(x) falls to a half at x = 2.1773
+        let expected_mtf50 = 2.1773 / (2. * std::f64::consts::PI.powi(2) * EDGE_SCALE);
+        assert!((result.mtf50.unwrap() - expected_mtf50).abs() < 0.01, "MTF50 {:?}, expected {expected_mtf50}", result.mtf50);
+    }
+
+    #[test]
+    fn horizontal_edges_are_measured_the_same() {
+        let vertical = slanted_edge_mtf(&slanted_edge(5.), None).unwrap();
+        let horizontal = slanted_edge_mtf(&slanted_edge(5.).transposed(), None).unwrap();
+        assert!((vertical.mtf_at(0.2) - horizontal.mtf_at(0.2)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unusable_regions_are_rejected() {
+        assert!(matches!(slanted_edge_mtf(&slanted_edge(0.), None), Err(AnalysisError::EdgeAngle(_))));
+        assert!(matches!(slanted_edge_mtf(&slanted_edge(30.), None), Err(AnalysisError::EdgeAngle(_))));
+
+        let flat = Region { data: vec![100.; SIZE * SIZE], width: SIZE, height: SIZE };
+        assert!(matches!(slanted_edge_mtf(&flat, None), Err(AnalysisError::NoEdge)));
+
+        let small = Region { data: vec![0.; 8 * SIZE], width: 8, height: SIZE };
+        assert!(matches!(slanted_edge_mtf(&small, None), Err(AnalysisError::RegionTooSmall { width: 8, .. })));
+    }
+}
@@ -0,0 +1,344 @@
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+use serde::{Deserialize, Serialize};
+
+use crate::messages::portfolio::image::utility_types::misc::ImageFrame;
+
+use super::mtf::MtfResult;
+use super::AnalysisError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum Detrend {
+    Mean,
+    Plane,
+    Quadratic,
+}
+
+impl Detrend {
+    fn term_count(&self) -> usize {
+        match self {
+            Detrend::Mean => 1,
+            Detrend::Plane => 3,
+            Detrend::Quadratic => 6,
+        }
+    }
+
+    fn terms(&self, x: f64, y: f64) -> [f64; 6] {
+        [1., x, y, x * x, x * y, y * y]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct NpsOptions {
+    pub roi_size: u32,
+    pub detrend: Detrend,
+    // Falls back to the pitch recorded with the acquisition
+    pub pixel_pitch_mm: Option<f64>,
+}
+
+impl Default for NpsOptions {
+    fn default() -> Self {
+        Self {
+            roi_size: 256,
+            detrend: Detrend::Quadratic,
+            pixel_pitch_mm: None,
+        }
+    }
+}
+
+// Spatial frequencies are in line pairs per millimetre, NPS in signal² mm² and NNPS in mm²
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct NpsResult {
+    pub roi_size: u32,
+    pub roi_count: u32,
+    pub mean_signal: f64,
+    pub pixel_pitch_mm: f64,
+    // Row-major with zero frequency at the centre
+    pub nps_2d: Vec<f64>,
+    pub frequencies: Vec<f64>,
+    pub nps: Vec<f64>,
+    pub nnps: Vec<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct DqeResult {
+    pub fluence_per_mm2: f64,
+    pub frequencies: Vec<f64>,
+    pub mtf: Vec<f64>,
+    pub nnps: Vec<f64>,
+    pub dqe: Vec<f64>,
+}
+
+// Follows IEC 62220-1: half-overlapping ROIs over detrended flat frames, radially averaged excluding the axes
+pub fn noise_power_spectrum(frames: &[ImageFrame], options: &NpsOptions, pixel_pitch_mm: Option<f64>) -> Result<NpsResult, AnalysisError> {
+    let pixel_pitch_mm = options.pixel_pitch_mm.or(pixel_pitch_mm).filter(|&pitch| pitch > 0.).ok_or(AnalysisError::MissingPixelPitch)?;
+    let size = options.roi_size as usize;
+    let first = frames.first().ok_or(AnalysisError::NoFrames)?;
+    let (width, height) = (first.width() as usize, first.height() as usize);
+    if size < 2 || width < size || height < size {
+        return Err(AnalysisError::RegionTooSmall { width, height, minimum: size.max(2) });
+    }
+
+    let fft = FftPlanner::<f64>::new().plan_fft_forward(size);
+    let step = size / 2;
+    let mut power = vec![0.; size * size];
+    let mut roi_count = 0;
+    let mut signal_sum = 0.;
+
+    for frame in frames {
+        if frame.dimensions() != first.dimensions() {
+            return Err(AnalysisError::FrameSizeMismatch);
+        }
+        signal_sum += frame.as_raw().iter().map(|&value| value as f64).sum::<f64>() / frame.as_raw().len() as f64;
+        let residual = detrend(frame, options.detrend);
+
+        for top in (0..=height - size).step_by(step) {
+            for left in (0..=width - size).step_by(step) {
+                let mut roi: Vec<Complex<f64>> = (0..size).flat_map(|y| (0..size).map(move |x| (x, y))).map(|(x, y)| Complex::new(residual[(top + y) * width + left + x], 0.)).collect();
+                fft_2d(&mut roi, size, fft.as_ref());
+                for (total, value) in power.iter_mut().zip(&roi) {
+                    *total += value.norm_sqr();
+                }
+                roi_count += 1;
+            }
+        }
+    }
+
+    let scale = pixel_pitch_mm * pixel_pitch_mm / (size * size) as f64 / roi_count as f64;
+    let nps_2d: Vec<f64> = (0..size * size)
+        .map(|index| {
+            // Shift so zero frequency lands in the centre
+            let (x, y) = ((index % size + size / 2) % size, (index / size + size / 2) % size);
+            power[y * size + x] * scale
+        })
+        .collect();
+
+    let frequency_step = 1. / (size as f64 * pixel_pitch_mm);
+    let bin_count = size / 2 + 1;
+    let mut sums = vec![0.; bin_count];
+    let mut counts = vec![0u32; bin_count];
+    for y in 0..size {
+        for x in 0..size {
+            let (u, v) = (signed_index(x, size), signed_index(y, size));
+            if u == 0 || v == 0 {
+                continue;
+            }
+            let bin = ((u * u + v * v) as f64).sqrt().round() as usize;
+            if bin < bin_count {
+                sums[bin] += power[y * size + x] * scale;
+                counts[bin] += 1;
+            }
+        }
+    }
+
+    let mean_signal = signal_sum / frames.len() as f64;
+    let (frequencies, nps): (Vec<f64>, Vec<f64>) = (1..bin_count).filter(|&bin| counts[bin] > 0).map(|bin| (bin as f64 * frequency_step, sums[bin] / counts[bin] as f64)).unzip();
+    let nnps = nps.iter().map(|value| if mean_signal > 0. { value / (mean_signal * mean_signal) } else { 0. }).collect();
+
+    Ok(NpsResult {
+        roi_size: options.roi_size,
+        roi_count,
+        mean_signal,
+        pixel_pitch_mm,
+        nps_2d,
+        frequencies,
+        nps,
+        nnps,
+    })
+}
+
+// DQE(f) = MTF(f)² / (q NNPS(f)) with q the incident photon fluence per mm²
+pub fn detective_quantum_efficiency(nps: &NpsResult, mtf: &MtfResult, fluence_per_mm2: f64) -> Result<DqeResult, AnalysisError> {
+    if fluence_per_mm2.is_nan() || fluence_per_mm2 <= 0. {
+        return Err(AnalysisError::InvalidFluence(fluence_per_mm2));
+    }
+
+    // The MTF is measured in cycles per pixel, the NPS in line pairs per millimetre
+    let mtf: Vec<f64> = nps.frequencies.iter().map(|frequency| mtf.mtf_at(frequency * nps.pixel_pitch_mm)).collect();
+    let dqe = mtf.iter().zip(&nps.nnps).map(|(mtf, nnps)| if *nnps > 0. { mtf * mtf / (fluence_per_mm2 * nnps) } else { 0. }).collect();
+
+    Ok(DqeResult {
+        fluence_per_mm2,
+        frequencies: nps.frequencies.clone(),
+        mtf,
+        nnps: nps.nnps.clone(),
+        dqe,
+    })
+}
+
+fn signed_index(index: usize, size: usize) -> i64 {
+    if index <= size / 2 { index as i64 } else { index as i64 - size as i64 }
+}
+
+fn fft_2d(data: &mut [Complex<f64>], size: usize, fft: &dyn rustfft::Fft<f64>) {
+    fft.process(data);
+    let mut column = vec![Complex::default(); size];
+    for x in 0..size {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = data[y * size + x];
+        }
+        fft.process(&mut column);
+        for (y, value) in column.iter().enumerate() {
+            data[y * size + x] = *value;
+        }
+    }
+}
+
+// Least-squares fit of a low order polynomial surface, returns the frame with the fit subtracted
+fn detrend(frame: &ImageFrame, detrend: Detrend) -> Vec<f64> {
+    let (width, height) = (frame.width() as usize, frame.height() as usize);
+    let terms = detrend.term_count();
+    // Normalised coordinates keep the normal equations well conditioned
+    let coordinate = |x: usize, y: usize| (x as f64 / width as f64 - 0.5, y as f64 / height as f64 - 0.5);
+
+    let mut normal = [[0.; 7]; 6];
+    for (index, &value) in frame.as_raw().iter().enumerate() {
+        let (x, y) = coordinate(index % width, index / width);
+        let basis = detrend.terms(x, y);
+        for row in 0..terms {
+            for column in 0..terms {
+                normal[row][column] += basis[row] * basis[column];
+            }
+            normal[row][6] += basis[row] * value as f64;
+        }
+    }
+    let coefficients = solve(&mut normal, terms);
+
+    frame
+        .as_raw()
+        .iter()
+        .enumerate()
+        .map(|(index, &value)| {
+            let (x, y) = coordinate(index % width, index / width);
+            let basis = detrend.terms(x, y);
+            value as f64 - (0..terms).map(|term| coefficients[term] * basis[term]).sum::<f64>()
+        })
+        .collect()
+}
+
+// Gaussian elimination with partial pivoting on an augmented matrix
+fn solve(matrix: &mut [[f64; 7]; 6], size: usize) -> [f64; 6] {
+    for pivot in 0..size {
+        let best = (pivot..size).max_by(|&a, &b| matrix[a][pivot].abs().total_cmp(&matrix[b][pivot].abs())).unwrap();
+        matrix.swap(pivot, best);
+        if matrix[pivot][pivot] == 0. {
+            continue;
+        }
+        for row in pivot + 1..size {
+            let factor = matrix[row][pivot] / matrix[pivot][pivot];
+            for column in pivot..size {
+                matrix[row][column] -= factor * matrix[pivot][column];
+            }
+            matrix[row][6] -= factor * matrix[pivot][6];
+        }
+    }
+
+    let mut solution = [0.; 6];
+    for row in (0..size).rev() {
+        if matrix[row][row] == 0. {
+            continue;
+        }
+        let known: f64 = (row + 1..size).map(|column| matrix[row][column] * solution[column]).sum();
+        solution[row] = (matrix[row][6] - known) / matrix[row][row];
+    }
+    solution
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PITCH: f64 = 0.1;
+    const NOISE_RANGE: u64 = 1001;
+
+    // Uniform integer noise around a mean of 1000, from a fixed seed so the test is repeatable
+    fn white_noise_frames(count: usize, size: u32) -> Vec<ImageFrame> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..count)
+            .map(|_| {
+                ImageFrame::from_fn(size, size, |_, _| {
+                    state ^= state << 13;
+                    state ^= state >> 7;
+                    state ^= state << 17;
+                    image::Luma([(500 + state % NOISE_RANGE) as u16])
+                })
+            })
+            .collect()
+    }
+
+    fn options(detrend: Detrend) -> NpsOptions {
+        NpsOptions { roi_size: 64, detrend, pixel_pitch_mm: None }
+    }
+
+    #[test]
+    fn white_noise_has_a_flat_spectrum() {
+        let frames = white_noise_frames(2, 256);
+        let result = noise_power_spectrum(&frames, &options(Detrend::Quadratic), Some(PITCH)).unwrap();
+        assert_eq!(result.roi_count, 2 * 7 * 7);
+        assert!((result.mean_signal - 1000.).abs() < 5.);
+        assert_eq!(result.frequencies.len(), 32);
+        assert!((result.frequencies.last().unwrap() - 0.5 / PITCH).abs() < 1e-9);
+
+        // The NPS of white noise is its variance times the pixel area at every frequency
+        let expected = (NOISE_RANGE * NOISE_RANGE - 1) as f64 / 12. * PITCH * PITCH;
+        for (frequency, nps) in result.frequencies.iter().zip(&result.nps).skip(1) {
+            assert!((nps / expected - 1.).abs() < 0.1, "NPS at {frequency} lp/mm is {nps}, expected {expected}");
+        }
+        for (nps, nnps) in result.nps.iter().zip(&result.nnps) {
+            assert!((nnps - nps / result.mean_signal.powi(2)).abs() < 1e-15);
+        }
+    }
+
+    #[test]
+    fn detrending_removes_a_gradient() {
+        let flat = white_noise_frames(1, 128);
+        let sloped: Vec<ImageFrame> = flat.iter().map(|frame| ImageFrame::from_fn(128, 128, |x, y| image::Luma([frame.get_pixel(x, y).0[0] + (x * 20 + y * 10) as u16]))).collect();
+
+        let flat = noise_power_spectrum(&flat, &options(Detrend::Plane), Some(PITCH)).unwrap();
+        let sloped = noise_power_spectrum(&sloped, &options(Detrend::Plane), Some(PITCH)).unwrap();
+        for (flat, sloped) in flat.nps.iter().zip(&sloped.nps) {
+            assert!((sloped / flat - 1.).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn options_pitch_overrides_the_acquisition() {
+        let frames = white_noise_frames(1, 64);
+        let result = noise_power_spectrum(&frames, &NpsOptions { pixel_pitch_mm: Some(0.2), ..options(Detrend::Mean) }, Some(PITCH)).unwrap();
+        assert_eq!(result.pixel_pitch_mm, 0.2);
+    }
+
+    #[test]
+    fn unusable_input_is_rejected() {
+        let frames = white_noise_frames(1, 64);
+        assert!(matches!(noise_power_spectrum(&frames, &options(Detrend::Mean), None), Err(AnalysisError::MissingPixelPitch)));
+        assert!(matches!(noise_power_spectrum(&[], &options(Detrend::Mean), Some(PITCH)), Err(AnalysisError::NoFrames)));
+        assert!(matches!(noise_power_spectrum(&white_noise_frames(1, 32), &options(Detrend::Mean), Some(PITCH)), Err(AnalysisError::RegionTooSmall { .. })));
+
+        let mismatched = [frames[0].clone(), white_noise_frames(1, 128).remove(0)];
+        assert!(matches!(noise_power_spectrum(&mismatched, &options(Detrend::Mean), Some(PITCH)), Err(AnalysisError::FrameSizeMismatch)));
+    }
+
+    #[test]
+    fn dqe_of_a_perfect_mtf_is_the_inverse_of_fluence_times_nnps() {
+        let nps = noise_power_spectrum(&white_noise_frames(1, 64), &options(Detrend::Mean), Some(PITCH)).unwrap();
+        let mtf = MtfResult {
+            edge_angle_degrees: 5.,
+            oversampling: 4,
+            esf: Vec::new(),
+            lsf: Vec::new(),
+            frequencies: vec![0., 1.],
+            mtf: vec![1., 1.],
+            mtf_at_nyquist: 1.,
+            mtf50: None,
+            pixel_pitch_mm: Some(PITCH),
+        };
+
+        let result = detective_quantum_efficiency(&nps, &mtf, 1e4).unwrap();
+        for (dqe, nnps) in result.dqe.iter().zip(&nps.nnps) {
+            assert!((dqe * 1e4 * nnps - 1.).abs() < 1e-9);
+        }
+        assert!(matches!(detective_quantum_efficiency(&nps, &mtf, 0.), Err(AnalysisError::InvalidFluence(_))));
+        assert!(matches!(detective_quantum_efficiency(&nps, &mtf, f64::NAN), Err(AnalysisError::InvalidFluence(_))));
+    }
+}
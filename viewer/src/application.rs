@@ -1,22 +1,202 @@
-use crate::{dispatcher::Dispatcher, messages::{prelude::Message, frontend::FrontendMessage}};
-use std::sync::mpsc::{Sender, Receiver, self};
+use crate::{dispatcher::Dispatcher, event_bridge::{EventBridge, MessageSender}, messages::{prelude::Message, frontend::FrontendMessage}};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
 pub struct Viewer {
     pub dispatcher: Dispatcher,
-    tx: Sender<FrontendMessage>,
+    event_bridge: EventBridge,
+    tx: UnboundedSender<FrontendMessage>,
 }
 
 impl Viewer {
-    pub fn new() -> (Viewer, Receiver<FrontendMessage>) {
-        let (tx, rx) = mpsc::channel();
-        return ( Self { dispatcher: Dispatcher::new(), tx }, rx );
+    // The host polls the receiver with try_recv or awaits it with recv
+    pub fn new() -> (Viewer, UnboundedReceiver<FrontendMessage>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let event_bridge = EventBridge::new();
+        let dispatcher = Dispatcher::new(event_bridge.runtime(), event_bridge.message_sender());
+        return ( Self { dispatcher, event_bridge, tx }, rx );
+    }
+
+    // For host threads that need to send messages without access to the viewer
+    pub fn message_sender(&self) -> MessageSender {
+        self.event_bridge.message_sender()
     }
 
     pub fn handle_message<T: Into<Message>>(&mut self, message: T) {
         self.dispatcher.handle_message(message);
+        self.send_responses();
+    }
+
+    // Holds the responses back until the next tick, so high-rate input such as pointer moves during a drag updates the frontend once per tick
+    pub fn queue_message<T: Into<Message>>(&mut self, message: T) {
+        self.dispatcher.handle_message(message);
+    }
+
+    // Processes the messages sent by background work since the last tick, called regularly by the host
+    pub fn tick(&mut self) {
+        for message in self.event_bridge.pending_messages() {
+            self.dispatcher.handle_message(message);
+        }
+        self.send_responses();
+    }
+
+    // Waits until background work sends a message, then ticks, for hosts driving the viewer from an async loop
+    pub async fn next_tick(&mut self) {
+        if let Some(message) = self.event_bridge.next_message().await {
+            self.dispatcher.handle_message(message);
+        }
+        self.tick();
+    }
 
-        while let Some(response) = self.dispatcher.responses.pop() {
+    // Sent in the order they were produced, with updates superseded by later ones dropped
+    fn send_responses(&mut self) {
+        for response in FrontendMessage::coalesce(std::mem::take(&mut self.dispatcher.responses)) {
             self.tx.send(response).unwrap();
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    use capture::{CaptureMode, StackCompression, StackWriter};
+    use wrapper::{SLBufferInfo, SLError};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::messages::prelude::*;
+
+    // A recording of frames filled with their own index, replayed without timing so captures run as fast as they can
+    fn write_recording(frame_count: u32) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("bridge-{}", Uuid::new_v4()));
+        let attributes = serde_json::json!({ "detector_id": "SL-1234" });
+        let mut writer = StackWriter::create(&root, 4, 3, 2, StackCompression::None, attributes).unwrap();
+        for frame in 0..frame_count {
+            let buffer_info = SLBufferInfo {
+                error: SLError::SL_ERROR_SUCCESS,
+                width: 4,
+                height: 3,
+                size: 4 * 3 * 2,
+                missing_packets: 0,
+                frame_count: frame,
+                block_id: frame as u64,
+                timestamp: 0,
+            };
+            writer.write_frame(&buffer_info, &[frame as u16; 12]).unwrap();
+        }
+        writer.finish().unwrap();
+        root
+    }
+
+    // Ticks the viewer until it reports the capture has finished, the status sent when it started may be coalesced away
+    fn tick_until_finished(viewer: &mut Viewer, frontend_messages: &mut UnboundedReceiver<FrontendMessage>) -> Vec<FrontendMessage> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut received = Vec::new();
+        loop {
+            viewer.tick();
+            received.extend(std::iter::from_fn(|| frontend_messages.try_recv().ok()));
+            if received.iter().any(|message| matches!(message, FrontendMessage::UpdateCaptureStatus { capturing: false, .. })) {
+                return received;
+            }
+            assert!(Instant::now() < deadline, "the capture didn't finish");
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn open_images(received: &[FrontendMessage]) -> Vec<(String, u32)> {
+        let images = received.iter().rev().find_map(|message| match message {
+            FrontendMessage::UpdateOpenImages { images, .. } => Some(images),
+            _ => None,
+        });
+        images.into_iter().flatten().map(|image| (image.name.clone(), image.frame_count)).collect()
+    }
+
+    #[test]
+    fn replayed_sequence_opens_as_an_image_of_its_own() {
+        let root = write_recording(3);
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        viewer.handle_message(DetectorMessage::OpenReplay { path: root.clone(), looping: false });
+        viewer.handle_message(DetectorMessage::StartCapture(CaptureMode::Sequence { exposure_time_ms: 1, num_frames: 3 }));
+        std::iter::from_fn(|| frontend_messages.try_recv().ok()).for_each(drop);
+        let received = tick_until_finished(&mut viewer, &mut frontend_messages);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(open_images(&received), vec![("Sequence of 3 frames".to_string(), 3)]);
+        let progress: Vec<_> = received.iter().filter_map(|message| match message {
+            FrontendMessage::UpdateCaptureProgress { frames, total } => Some((*frames, *total)),
+            _ => None,
+        }).collect();
+        assert_eq!(progress.last(), Some(&(3, Some(3))));
+    }
+
+    #[test]
+    fn stopped_stream_keeps_the_latest_frame_in_the_live_image() {
+        let root = write_recording(2);
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        viewer.handle_message(DetectorMessage::OpenReplay { path: root.clone(), looping: true });
+        viewer.handle_message(DetectorMessage::StartCapture(CaptureMode::Stream { exposure_time_ms: 1 }));
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while !open_images(&std::iter::from_fn(|| frontend_messages.try_recv().ok()).collect::<Vec<_>>()).iter().any(|(name, _)| name == "Live") {
+            assert!(Instant::now() < deadline, "no live frame arrived");
+            std::thread::sleep(Duration::from_millis(5));
+            viewer.tick();
+        }
+        viewer.handle_message(DetectorMessage::StopCapture);
+        let received = tick_until_finished(&mut viewer, &mut frontend_messages);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(received.iter().all(|message| !matches!(message, FrontendMessage::UpdateFrameInfo { frame_count, .. } if *frame_count != 1)));
+        assert!(received.iter().any(|message| matches!(message, FrontendMessage::UpdateCaptureStatus { connected: true, capturing: false })));
+    }
+
+    #[test]
+    fn events_of_a_capture_stopped_by_disconnecting_are_dropped() {
+        let root = write_recording(2);
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        viewer.handle_message(DetectorMessage::OpenReplay { path: root.clone(), looping: true });
+        viewer.handle_message(DetectorMessage::StartCapture(CaptureMode::Stream { exposure_time_ms: 1 }));
+        viewer.handle_message(DetectorMessage::Disconnect);
+        std::iter::from_fn(|| frontend_messages.try_recv().ok()).for_each(drop);
+
+        std::thread::sleep(Duration::from_millis(100));
+        viewer.tick();
+        let received: Vec<_> = std::iter::from_fn(|| frontend_messages.try_recv().ok()).collect();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(received.is_empty(), "{received:?}");
+    }
+
+    #[test]
+    fn sequence_cut_short_by_disconnecting_keeps_its_frames() {
+        let root = write_recording(10_000);
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        viewer.handle_message(DetectorMessage::OpenReplay { path: root.clone(), looping: false });
+        viewer.handle_message(DetectorMessage::StartCapture(CaptureMode::Sequence { exposure_time_ms: 1, num_frames: 10_000 }));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while viewer.dispatcher.portfolio().active_image().is_none() {
+            assert!(Instant::now() < deadline, "no sequence frame arrived");
+            std::thread::sleep(Duration::from_millis(5));
+            viewer.tick();
+        }
+        let cut_short_id = viewer.dispatcher.portfolio().active_image_id().unwrap();
+        viewer.handle_message(DetectorMessage::Disconnect);
+
+        viewer.handle_message(DetectorMessage::OpenReplay { path: root.clone(), looping: false });
+        viewer.handle_message(DetectorMessage::StartCapture(CaptureMode::Sequence { exposure_time_ms: 1, num_frames: 2 }));
+        std::iter::from_fn(|| frontend_messages.try_recv().ok()).for_each(drop);
+        tick_until_finished(&mut viewer, &mut frontend_messages);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let portfolio = viewer.dispatcher.portfolio();
+        let cut_short = portfolio.image(cut_short_id).unwrap();
+        assert!(cut_short.frame_count() < 10_000);
+        assert_eq!(cut_short.name(), format!("Sequence of {} frames", cut_short.frame_count()));
+        let sequence = portfolio.active_image().unwrap();
+        assert_eq!((sequence.name(), sequence.frame_count()), ("Sequence of 2 frames", 2));
+        // Identified when the replay opened, before either capture started
+        assert_eq!(sequence.acquisition_metadata().detector_id.as_deref(), Some("SL-1234"));
+    }
+}
@@ -1 +1,7 @@
-pub const DRAG_THRESHOLD: f64 = 1.;
\ No newline at end of file
+pub const DRAG_THRESHOLD: f64 = 1.;
+pub const MAX_UNDO_HISTORY: usize = 100;
+// Viewport
+pub const VIEWPORT_ZOOM_MIN: f64 = 0.01;
+pub const VIEWPORT_ZOOM_MAX: f64 = 64.;
+// Zoom factor per unit of wheel scroll
+pub const VIEWPORT_ZOOM_WHEEL_RATE: f64 = 1. / 600.;
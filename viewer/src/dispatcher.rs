@@ -1,88 +1,157 @@
+use std::time::Instant;
+
+use tokio::runtime::Handle;
+
+use crate::event_bridge::MessageSender;
+use crate::messages::debug::utility_types::MessageLoggingVerbosity;
 use crate::messages::prelude::*;
 
+// Sent many times a second, logging them would drown out everything else
+const MESSAGE_LOG_BLOCK_LIST: &[&[&str]] = &[
+    &["Detector", "CaptureEvent"],
+    &["InputMapper", "PointerMove"],
+    &["Portfolio", "Image", "PlaybackTick"],
+    &["Portfolio", "LiveFrame"],
+    &["Tool", "PointerMove"],
+];
+
 pub struct Dispatcher {
     message_queues: Vec<VecDeque<Message>>,
     pub responses: Vec<FrontendMessage>,
     pub message_handlers: DispatcherMessageHandlers,
 }
 
-impl Default for Dispatcher {
-    fn default() -> Self {
-        let mut responses = Vec::new();
-        let detector_message_handler = DetectorMessageHandler::new(&mut responses);
+pub struct DispatcherMessageHandlers  {
+    debug_message_handler: DebugMessageHandler,
+    detector_message_handler: DetectorMessageHandler,
+    dialog_message_handler: DialogMessageHandler,
+    input_mapper_message_handler: InputMapperMessageHandler,
+    portfolio_message_handler: PortfolioMessageHandler,
+    tool_message_handler: ToolMessageHandler,
+}
+
 
+impl Dispatcher {
+    // Background work is spawned on the runtime and reports back through the message sender
+    pub fn new(runtime: Handle, messages: MessageSender) -> Self {
         let message_handlers = DispatcherMessageHandlers {
-            detector_message_handler,
+            debug_message_handler: DebugMessageHandler::default(),
+            detector_message_handler: DetectorMessageHandler::new(runtime, messages),
             dialog_message_handler: DialogMessageHandler::default(),
+            input_mapper_message_handler: InputMapperMessageHandler::default(),
+            portfolio_message_handler: PortfolioMessageHandler::default(),
             // menu_bar_messsage_handler: MenuBarMessageHandler::default(),
             tool_message_handler: ToolMessageHandler::default()
         };
 
         Self {
-            responses,
+            responses: Vec::new(),
             message_handlers,
             message_queues: Vec::new()
         }
     }
-}
 
-pub struct DispatcherMessageHandlers  {
-    detector_message_handler: DetectorMessageHandler,
-    dialog_message_handler: DialogMessageHandler,
-    tool_message_handler: ToolMessageHandler,
-}
-
-
-impl Dispatcher {
-    pub fn new() -> Self {
-        Self::default()
+    pub fn portfolio(&self) -> &PortfolioMessageHandler {
+        &self.message_handlers.portfolio_message_handler
     }
 
     pub fn handle_message<T: Into<Message>>(&mut self, message: T) {
         self.message_queues.push(VecDeque::from_iter([message.into()]));
 
-        while let Some(message) = self.message_queues.last_mut().and_then(VecDeque::pop_front) {
+        while let Some(mut message) = self.message_queues.last_mut().and_then(VecDeque::pop_front) {
+            // 0 for the message passed in, one more for each handler it passed through
+            let depth = self.message_queues.len() - 1;
+            self.message_handlers.debug_message_handler.record(depth, &mut message);
+            let log_entry = self.log_entry(&message, depth);
+            let start = Instant::now();
 
             let mut queue = VecDeque::new();
 
             match message {
                 Message::NoOp => {},
                 Message::Init => {
-                    queue.add(Message::Tool(ToolMessage::InitTools))
+                    queue.add(Message::Tool(ToolMessage::InitTools));
+                    queue.add(InputMapperMessage::SendKeyBindings)
+                },
+                Message::Debug(message) => {
+                    self.message_handlers.debug_message_handler.process_message(message, &mut queue, ())
+                },
+                Message::Detector(message) => {
+                    self.message_handlers.detector_message_handler.process_message(message, &mut queue, ())
                 },
-                // Message::Detector(message) => {
-                //     self.message_handlers.detector_message_handler.process_message(message, &mut queue, ())
-                // },
                 Message::Dialog(message) => {
                     self.message_handlers.dialog_message_handler.process_message(message, &mut queue, ())
                 },
                 Message::Frontend(message) => {
                     self.responses.push(message);
 				}
-                // Message::Debug(message) => {
-                // }
+                Message::InputMapper(message) => {
+                    let active_viewport = self.message_handlers.portfolio_message_handler.active_viewport();
+                    self.message_handlers.input_mapper_message_handler.process_message(message, &mut queue, active_viewport)
+                }
+                Message::Portfolio(message) => {
+                    self.message_handlers.portfolio_message_handler.process_message(message, &mut queue, &self.message_handlers.input_mapper_message_handler)
+                }
                 Message::Tool(message) => {
-                    self.message_handlers.tool_message_handler.process_message(message, &mut queue, ())
+                    let image = self.message_handlers.portfolio_message_handler.active_image();
+                    self.message_handlers.tool_message_handler.process_message(message, &mut queue, image)
                 }
             }
 
+            if let Some(log_entry) = log_entry {
+                log::info!("{log_entry} ({:?})", start.elapsed());
+            }
+
             if !queue.is_empty() {
                 self.message_queues.push(queue);
             }
+            // Finished queues are dropped so processing carries on with the parent queue's remaining messages
+            while self.message_queues.last().is_some_and(VecDeque::is_empty) {
+                self.message_queues.pop();
+            }
         }
     }
 
-    /*
-    pub fn log_message(&self, message: &Message, queries: &[VecDeque<Message>], message_logging_verbosity: MessageLoggingVerbosity) {
-        match message_logging_verbosity {
-            MessageLoggingVerbosity::Off => {},
-            MessageLoggingVerbosity::Names => {
-                info!("Logging names");
-            },
-            MessageLoggingVerbosity::Contents => {
-                info!("Logging contents");
-            }
+    // Formatted before the message is handed to its handler, the time the handler took is added once it returns
+    fn log_entry(&self, message: &Message, depth: usize) -> Option<String> {
+        let verbosity = self.message_handlers.debug_message_handler.message_logging_verbosity;
+        if verbosity == MessageLoggingVerbosity::Off {
+            return None;
+        }
+        let discriminant = message.to_discriminant();
+        if MESSAGE_LOG_BLOCK_LIST.iter().any(|blocked| discriminant.starts_with(blocked)) {
+            return None;
+        }
+
+        let indentation = "  ".repeat(depth);
+        match verbosity {
+            MessageLoggingVerbosity::Off => None,
+            MessageLoggingVerbosity::Names => Some(format!("{indentation}{discriminant}")),
+            MessageLoggingVerbosity::Contents => Some(format!("{indentation}{message:?}")),
         }
     }
-    */
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::event_bridge::EventBridge;
+
+    use super::*;
+
+    #[test]
+    fn nested_responses_are_handled_before_the_rest_of_their_parent_queue() {
+        let event_bridge = EventBridge::new();
+        let mut dispatcher = Dispatcher::new(event_bridge.runtime(), event_bridge.message_sender());
+
+        // Init queues InitTools then SendKeyBindings, each answering with a frontend message of its own
+        dispatcher.handle_message(Message::Init);
+        let responses = std::mem::take(&mut dispatcher.responses);
+        assert!(matches!(responses.as_slice(), [FrontendMessage::SetActiveTool(_), FrontendMessage::UpdateKeyBindings { .. }]), "{responses:?}");
+        assert!(dispatcher.message_queues.is_empty());
+
+        // Three levels deep, with nothing left in the parent queues once it returns
+        dispatcher.handle_message(InputMapperMessage::ResetKeymap);
+        assert!(matches!(dispatcher.responses.as_slice(), [FrontendMessage::UpdateKeyBindings { .. }]), "{:?}", dispatcher.responses);
+        assert!(dispatcher.message_queues.is_empty());
+    }
+}
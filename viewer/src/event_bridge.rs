@@ -0,0 +1,75 @@
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::mpsc;
+
+use crate::messages::prelude::Message;
+
+// Sends messages into the dispatcher from other threads and tasks, they are processed on the viewer's next tick
+#[derive(Clone, Debug)]
+pub struct MessageSender {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl MessageSender {
+    // Returns false once the viewer has been dropped
+    pub fn send(&self, message: impl Into<Message>) -> bool {
+        self.sender.send(message.into()).is_ok()
+    }
+}
+
+// Owns the runtime background work such as captures runs on, and the channel that work reports back through
+pub struct EventBridge {
+    runtime: Option<Runtime>,
+    sender: mpsc::UnboundedSender<Message>,
+    receiver: mpsc::UnboundedReceiver<Message>,
+}
+
+impl EventBridge {
+    pub fn new() -> Self {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("viewer-background")
+            .enable_time()
+            .build()
+            .expect("Failed to start the background runtime");
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        Self {
+            runtime: Some(runtime),
+            sender,
+            receiver,
+        }
+    }
+
+    pub fn runtime(&self) -> Handle {
+        self.runtime.as_ref().unwrap().handle().clone()
+    }
+
+    pub fn message_sender(&self) -> MessageSender {
+        MessageSender { sender: self.sender.clone() }
+    }
+
+    // The messages that arrived since the last call, without waiting for more
+    pub fn pending_messages(&mut self) -> Vec<Message> {
+        std::iter::from_fn(|| self.receiver.try_recv().ok()).collect()
+    }
+
+    // Waits for the next message, never returns None since the bridge holds a sender itself
+    pub async fn next_message(&mut self) -> Option<Message> {
+        self.receiver.recv().await
+    }
+}
+
+impl Default for EventBridge {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Dropping a runtime blocks until its tasks finish, which panics when the viewer is dropped inside the host's own runtime
+impl Drop for EventBridge {
+    fn drop(&mut self) {
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use crate::analysis::nps::{DqeResult, NpsResult};
+use crate::messages::portfolio::image::utility_types::profile::LineProfile;
+
+use super::ImageIoError;
+
+pub fn write_line_profile<W: Write>(mut writer: W, profile: &LineProfile) -> Result<(), ImageIoError> {
+    match profile.pixel_pitch_mm {
+        Some(_) => writeln!(writer, "distance_px,distance_mm,value")?,
+        None => writeln!(writer, "distance_px,value")?,
+    }
+    for (distance, value) in profile.distances.iter().zip(&profile.values) {
+        match profile.pixel_pitch_mm {
+            Some(pitch) => writeln!(writer, "{distance},{},{value}", distance * pitch)?,
+            None => writeln!(writer, "{distance},{value}")?,
+        }
+    }
+    Ok(())
+}
+
+pub fn save_line_profile(path: impl AsRef<Path>, profile: &LineProfile) -> Result<(), ImageIoError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_line_profile(&mut writer, profile)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn write_noise_analysis<W: Write>(mut writer: W, nps: &NpsResult, dqe: Option<&DqeResult>) -> Result<(), ImageIoError> {
+    match dqe {
+        Some(_) => writeln!(writer, "frequency_lp_mm,nps,nnps,mtf,dqe")?,
+        None => writeln!(writer, "frequency_lp_mm,nps,nnps")?,
+    }
+    for (index, frequency) in nps.frequencies.iter().enumerate() {
+        match dqe {
+            Some(dqe) => writeln!(writer, "{frequency},{},{},{},{}", nps.nps[index], nps.nnps[index], dqe.mtf[index], dqe.dqe[index])?,
+            None => writeln!(writer, "{frequency},{},{}", nps.nps[index], nps.nnps[index])?,
+        }
+    }
+    Ok(())
+}
+
+pub fn save_noise_analysis(path: impl AsRef<Path>, nps: &NpsResult, dqe: Option<&DqeResult>) -> Result<(), ImageIoError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_noise_analysis(&mut writer, nps, dqe)?;
+    writer.flush()?;
+    Ok(())
+}
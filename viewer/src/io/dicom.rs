@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::messages::portfolio::image::utility_types::metadata::AcquisitionMetadata;
+use crate::messages::portfolio::image::utility_types::misc::{AdjustmentLevels, ImageFrame};
+
+use super::ImageIoError;
+
+const EXPLICIT_VR_LITTLE_ENDIAN: &str = "1.2.840.10008.1.2.1";
+const DX_FOR_PRESENTATION_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.1.1";
+const CR_SOP_CLASS: &str = "1.2.840.10008.5.1.4.1.1.1";
+// Root for UUID derived UIDs, see PS3.5 B.2
+const UUID_UID_ROOT: &str = "2.25";
+const IMPLEMENTATION_CLASS_UID: &str = "2.25.229358713508962476385296416234811520311";
+const IMPLEMENTATION_VERSION_NAME: &str = "CVIEW_010";
+const MANUFACTURER: &str = "Spectrum Logic";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum DicomModality {
+    DigitalRadiography,
+    ComputedRadiography,
+}
+
+impl DicomModality {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::DigitalRadiography => "DX",
+            Self::ComputedRadiography => "CR",
+        }
+    }
+
+    fn sop_class_uid(&self) -> &'static str {
+        match self {
+            Self::DigitalRadiography => DX_FOR_PRESENTATION_SOP_CLASS,
+            Self::ComputedRadiography => CR_SOP_CLASS,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct DicomExportOptions {
+    pub modality: DicomModality,
+    pub patient_name: String,
+    pub patient_id: String,
+    pub study_description: String,
+    pub pixel_pitch_mm: Option<f64>,
+}
+
+impl Default for DicomExportOptions {
+    fn default() -> Self {
+        Self {
+            modality: DicomModality::DigitalRadiography,
+            patient_name: "Anonymous".into(),
+            patient_id: "UNKNOWN".into(),
+            study_description: String::new(),
+            pixel_pitch_mm: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Vr {
+    CS, DA, DS, IS, LO, OB, OW, PN, SH, SS, TM, UI, UL, US,
+}
+
+impl Vr {
+    fn code(&self) -> &'static [u8; 2] {
+        match self {
+            Vr::CS => b"CS",
+            Vr::DA => b"DA",
+            Vr::DS => b"DS",
+            Vr::IS => b"IS",
+            Vr::LO => b"LO",
+            Vr::OB => b"OB",
+            Vr::OW => b"OW",
+            Vr::PN => b"PN",
+            Vr::SH => b"SH",
+            Vr::SS => b"SS",
+            Vr::TM => b"TM",
+            Vr::UI => b"UI",
+            Vr::UL => b"UL",
+            Vr::US => b"US",
+        }
+    }
+
+    // These VRs use a reserved field and a 32-bit length in explicit VR encoding
+    fn has_long_length(&self) -> bool {
+        matches!(self, Vr::OB | Vr::OW)
+    }
+
+    fn padding(&self) -> u8 {
+        match self {
+            Vr::UI | Vr::OB => 0,
+            _ => b' ',
+        }
+    }
+}
+
+// Explicit VR little endian data set, elements must be pushed in ascending tag order
+#[derive(Default)]
+struct DataSet {
+    bytes: Vec<u8>,
+}
+
+impl DataSet {
+    fn element(&mut self, group: u16, element: u16, vr: Vr, value: &[u8]) {
+        let padded_len = value.len() + value.len() % 2;
+
+        self.bytes.extend_from_slice(&group.to_le_bytes());
+        self.bytes.extend_from_slice(&element.to_le_bytes());
+        self.bytes.extend_from_slice(vr.code());
+        if vr.has_long_length() {
+            self.bytes.extend_from_slice(&[0, 0]);
+            self.bytes.extend_from_slice(&(padded_len as u32).to_le_bytes());
+        } else {
+            self.bytes.extend_from_slice(&(padded_len as u16).to_le_bytes());
+        }
+        self.bytes.extend_from_slice(value);
+        if padded_len != value.len() {
+            self.bytes.push(vr.padding());
+        }
+    }
+
+    fn string(&mut self, group: u16, element: u16, vr: Vr, value: &str) {
+        self.element(group, element, vr, value.as_bytes());
+    }
+
+    fn us(&mut self, group: u16, element: u16, value: u16) {
+        self.element(group, element, Vr::US, &value.to_le_bytes());
+    }
+}
+
+fn generate_uid() -> String {
+    format!("{UUID_UID_ROOT}.{}", Uuid::new_v4().as_u128())
+}
+
+// DS is limited to 16 characters
+fn decimal_string(value: f64) -> String {
+    let formatted = format!("{value:.6}");
+    let trimmed = formatted.trim_end_matches('0').trim_end_matches('.');
+    trimmed.chars().take(16).collect()
+}
+
+pub fn write_dicom<W: Write>(mut writer: W, frame: &ImageFrame, adjustment_levels: &AdjustmentLevels, acquisition: &AcquisitionMetadata, options: &DicomExportOptions) -> Result<(), ImageIoError> {
+    let (columns, rows) = frame.dimensions();
+    let (Ok(columns), Ok(rows)) = (u16::try_from(columns), u16::try_from(rows)) else {
+        return Err(ImageIoError::Unsupported(format!("{columns}x{rows} exceeds the DICOM image size limit")));
+    };
+
+    let sop_instance_uid = generate_uid();
+    let now = chrono::Local::now();
+    let date = now.format("%Y%m%d").to_string();
+    let time = now.format("%H%M%S").to_string();
+
+    let mut meta = DataSet::default();
+    meta.element(0x0002, 0x0001, Vr::OB, &[0, 1]);
+    meta.string(0x0002, 0x0002, Vr::UI, options.modality.sop_class_uid());
+    meta.string(0x0002, 0x0003, Vr::UI, &sop_instance_uid);
+    meta.string(0x0002, 0x0010, Vr::UI, EXPLICIT_VR_LITTLE_ENDIAN);
+    meta.string(0x0002, 0x0012, Vr::UI, IMPLEMENTATION_CLASS_UID);
+    meta.string(0x0002, 0x0013, Vr::SH, IMPLEMENTATION_VERSION_NAME);
+
+    let window_width = (adjustment_levels.max as f64 - adjustment_levels.min as f64).max(1.);
+    let window_center = adjustment_levels.min as f64 + window_width / 2.;
+    let pixel_pitch = options.pixel_pitch_mm.or(acquisition.pixel_pitch_mm);
+    let is_dx = options.modality == DicomModality::DigitalRadiography;
+    // An inverted image shows low values bright, DX pairs MONOCHROME1 with an INVERSE presentation LUT
+    let (photometric_interpretation, presentation_lut_shape) = if adjustment_levels.invert { ("MONOCHROME1", "INVERSE") } else { ("MONOCHROME2", "IDENTITY") };
+
+    let mut data = DataSet::default();
+    data.string(0x0008, 0x0008, Vr::CS, "ORIGINAL\\PRIMARY");
+    data.string(0x0008, 0x0016, Vr::UI, options.modality.sop_class_uid());
+    data.string(0x0008, 0x0018, Vr::UI, &sop_instance_uid);
+    data.string(0x0008, 0x0020, Vr::DA, &date);
+    data.string(0x0008, 0x0023, Vr::DA, &date);
+    data.string(0x0008, 0x0030, Vr::TM, &time);
+    data.string(0x0008, 0x0033, Vr::TM, &time);
+    data.string(0x0008, 0x0050, Vr::SH, "");
+    data.string(0x0008, 0x0060, Vr::CS, options.modality.code());
+    if is_dx {
+        data.string(0x0008, 0x0068, Vr::CS, "FOR PRESENTATION");
+    }
+    data.string(0x0008, 0x0070, Vr::LO, MANUFACTURER);
+    data.string(0x0008, 0x0090, Vr::PN, "");
+    data.string(0x0008, 0x1030, Vr::LO, &options.study_description);
+    data.string(0x0010, 0x0010, Vr::PN, &options.patient_name);
+    data.string(0x0010, 0x0020, Vr::LO, &options.patient_id);
+    data.string(0x0010, 0x0030, Vr::DA, "");
+    data.string(0x0010, 0x0040, Vr::CS, "");
+    if let Some(detector_id) = &acquisition.detector_id {
+        data.string(0x0018, 0x1000, Vr::LO, detector_id);
+    }
+    if let Some(exposure_time_ms) = acquisition.exposure_time_ms {
+        data.string(0x0018, 0x1150, Vr::IS, &exposure_time_ms.to_string());
+    }
+    if let Some(pixel_pitch) = pixel_pitch {
+        let spacing = format!("{0}\\{0}", decimal_string(pixel_pitch));
+        data.string(0x0018, 0x1164, Vr::DS, &spacing);
+    }
+    data.string(0x0020, 0x000D, Vr::UI, &generate_uid());
+    data.string(0x0020, 0x000E, Vr::UI, &generate_uid());
+    data.string(0x0020, 0x0010, Vr::SH, "1");
+    data.string(0x0020, 0x0011, Vr::IS, "1");
+    data.string(0x0020, 0x0013, Vr::IS, "1");
+    data.string(0x0020, 0x0020, Vr::CS, "");
+    data.us(0x0028, 0x0002, 1);
+    data.string(0x0028, 0x0004, Vr::CS, photometric_interpretation);
+    data.us(0x0028, 0x0010, rows);
+    data.us(0x0028, 0x0011, columns);
+    if let Some(pixel_pitch) = pixel_pitch {
+        let spacing = format!("{0}\\{0}", decimal_string(pixel_pitch));
+        data.string(0x0028, 0x0030, Vr::DS, &spacing);
+    }
+    data.us(0x0028, 0x0100, 16);
+    data.us(0x0028, 0x0101, 16);
+    data.us(0x0028, 0x0102, 15);
+    data.us(0x0028, 0x0103, 0);
+    if is_dx {
+        data.string(0x0028, 0x1040, Vr::CS, "LIN");
+        data.element(0x0028, 0x1041, Vr::SS, &1i16.to_le_bytes());
+    }
+    data.string(0x0028, 0x1050, Vr::DS, &decimal_string(window_center));
+    data.string(0x0028, 0x1051, Vr::DS, &decimal_string(window_width));
+    data.string(0x0028, 0x1052, Vr::DS, "0");
+    data.string(0x0028, 0x1053, Vr::DS, "1");
+    data.string(0x0028, 0x1054, Vr::LO, "US");
+    data.string(0x0028, 0x2110, Vr::CS, "00");
+    if is_dx {
+        data.string(0x2050, 0x0020, Vr::CS, presentation_lut_shape);
+    }
+    let pixel_data: Vec<u8> = frame.as_raw().iter().flat_map(|pixel| pixel.to_le_bytes()).collect();
+    data.element(0x7FE0, 0x0010, Vr::OW, &pixel_data);
+
+    let mut group_length = DataSet::default();
+    group_length.element(0x0002, 0x0000, Vr::UL, &(meta.bytes.len() as u32).to_le_bytes());
+
+    writer.write_all(&[0u8; 128])?;
+    writer.write_all(b"DICM")?;
+    writer.write_all(&group_length.bytes)?;
+    writer.write_all(&meta.bytes)?;
+    writer.write_all(&data.bytes)?;
+    Ok(())
+}
+
+pub fn save_dicom(path: impl AsRef<Path>, frame: &ImageFrame, adjustment_levels: &AdjustmentLevels, acquisition: &AcquisitionMetadata, options: &DicomExportOptions) -> Result<(), ImageIoError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_dicom(&mut writer, frame, adjustment_levels, acquisition, options)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use dicom_dictionary_std::tags;
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn dicom_export_round_trips_through_parser() {
+        let frame = ImageFrame::from_fn(64, 32, |x, y| Luma([(x * 1000 + y) as u16]));
+        let adjustment_levels = AdjustmentLevels { min: 1000, max: 3000, ..Default::default() };
+        let acquisition = AcquisitionMetadata { detector_id: Some("SL-1510".into()), exposure_time_ms: Some(250), ..Default::default() };
+        let options = DicomExportOptions { pixel_pitch_mm: Some(0.1), ..Default::default() };
+
+        let path = std::env::temp_dir().join(format!("{}.dcm", Uuid::new_v4()));
+        save_dicom(&path, &frame, &adjustment_levels, &acquisition, &options).unwrap();
+        let object = dicom_object::open_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(object.meta().transfer_syntax(), EXPLICIT_VR_LITTLE_ENDIAN);
+        assert_eq!(object.element(tags::MODALITY).unwrap().to_str().unwrap(), "DX");
+        assert_eq!(object.element(tags::ROWS).unwrap().to_int::<u16>().unwrap(), 32);
+        assert_eq!(object.element(tags::COLUMNS).unwrap().to_int::<u16>().unwrap(), 64);
+        assert_eq!(object.element(tags::WINDOW_CENTER).unwrap().to_float64().unwrap(), 2000.);
+        assert_eq!(object.element(tags::WINDOW_WIDTH).unwrap().to_float64().unwrap(), 2000.);
+        assert_eq!(object.element(tags::IMAGER_PIXEL_SPACING).unwrap().to_multi_float64().unwrap(), vec![0.1, 0.1]);
+        assert_eq!(object.element(tags::DEVICE_SERIAL_NUMBER).unwrap().to_str().unwrap(), "SL-1510");
+        assert_eq!(object.element(tags::PHOTOMETRIC_INTERPRETATION).unwrap().to_str().unwrap(), "MONOCHROME2");
+        assert_eq!(object.element(tags::PRESENTATION_LUT_SHAPE).unwrap().to_str().unwrap(), "IDENTITY");
+
+        let pixels = object.element(tags::PIXEL_DATA).unwrap().to_bytes().unwrap();
+        let pixels: Vec<u16> = pixels.chunks_exact(2).map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]])).collect();
+        assert_eq!(pixels, frame.into_raw());
+    }
+
+    #[test]
+    fn inverted_image_is_exported_as_monochrome1() {
+        let frame = ImageFrame::from_pixel(4, 4, Luma([100]));
+        let adjustment_levels = AdjustmentLevels { invert: true, ..Default::default() };
+        let mut bytes = Vec::new();
+        write_dicom(&mut bytes, &frame, &adjustment_levels, &AcquisitionMetadata::default(), &DicomExportOptions::default()).unwrap();
+        let object = dicom_object::from_reader(&bytes[128..]).unwrap();
+
+        assert_eq!(object.element(tags::PHOTOMETRIC_INTERPRETATION).unwrap().to_str().unwrap(), "MONOCHROME1");
+        assert_eq!(object.element(tags::PRESENTATION_LUT_SHAPE).unwrap().to_str().unwrap(), "INVERSE");
+    }
+
+    #[test]
+    fn images_beyond_the_dicom_size_limit_are_rejected() {
+        let frame = ImageFrame::new(u16::MAX as u32 + 1, 1);
+        let result = write_dicom(Vec::new(), &frame, &AdjustmentLevels::default(), &AcquisitionMetadata::default(), &DicomExportOptions::default());
+        assert!(matches!(result, Err(ImageIoError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,66 @@
+pub mod csv;
+pub mod dicom;
+pub mod stack;
+pub mod tiff;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+use crate::messages::portfolio::image::utility_types::misc::ImageFrame;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ImageIoError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Tiff(#[from] ::tiff::TiffError),
+    #[error(transparent)]
+    Stack(#[from] capture::StackError),
+    #[error("invalid metadata: {0}")]
+    Metadata(#[from] serde_json::Error),
+    #[error("unsupported image: {0}")]
+    Unsupported(String),
+}
+
+// A frame stack together with its acquisition metadata, as read from or written to disk
+#[derive(Debug, Clone, Default)]
+pub struct ImageStack {
+    pub frames: Vec<ImageFrame>,
+    pub acquisition: AcquisitionMetadata,
+    pub frame_metadata: Vec<FrameMetadata>,
+}
+
+impl ImageStack {
+    pub fn from_frame(frame: ImageFrame, acquisition: AcquisitionMetadata) -> Self {
+        Self {
+            frames: vec![frame],
+            acquisition,
+            frame_metadata: vec![FrameMetadata::default()],
+        }
+    }
+
+    pub fn dimensions(&self) -> Option<(u32, u32)> {
+        self.frames.first().map(|frame| frame.dimensions())
+    }
+}
+
+// Directories are stacks recorded by the capture crate, anything else is read as a TIFF
+pub fn load_image_stack(path: impl AsRef<Path>) -> Result<ImageStack, ImageIoError> {
+    let path = path.as_ref();
+    if path.is_dir() {
+        stack::load_stack(path)
+    } else {
+        tiff::load_tiff(path)
+    }
+}
+
+pub fn save_json(path: impl AsRef<Path>, value: &impl Serialize) -> Result<(), ImageIoError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use std::path::Path;
+
+use capture::StackReader;
+
+use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+use crate::messages::portfolio::image::utility_types::misc::ImageFrame;
+
+use super::{ImageIoError, ImageStack};
+
+// Reads a stack recorded by the capture crate, acquisition metadata is taken from its attributes when present
+pub fn load_stack(path: impl AsRef<Path>) -> Result<ImageStack, ImageIoError> {
+    let mut reader = StackReader::open(path)?;
+    let (width, height) = (reader.metadata().width, reader.metadata().height);
+    let acquisition = reader
+        .metadata()
+        .attributes
+        .get("acquisition")
+        .and_then(|acquisition| serde_json::from_value::<AcquisitionMetadata>(acquisition.clone()).ok())
+        .unwrap_or_default();
+
+    let mut stack = ImageStack { acquisition, ..Default::default() };
+    for index in 0..reader.frame_count() {
+        let data = reader.read_frame(index)?;
+        let frame = ImageFrame::from_raw(width, height, data).ok_or_else(|| ImageIoError::Unsupported("stack frame does not match its dimensions".into()))?;
+        let frame_metadata = reader
+            .frame_info(index)
+            .map(|info| FrameMetadata {
+                frame_count: info.frame_count,
+                block_id: info.block_id,
+                timestamp: info.timestamp,
+                missing_packets: info.missing_packets,
+            })
+            .unwrap_or_default();
+        stack.frames.push(frame);
+        stack.frame_metadata.push(frame_metadata);
+    }
+    Ok(stack)
+}
@@ -0,0 +1,136 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tiff::decoder::{Decoder, DecodingResult};
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::Tag;
+use tiff::ColorType;
+
+use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+use crate::messages::portfolio::image::utility_types::misc::ImageFrame;
+
+use super::{ImageIoError, ImageStack};
+
+const SOFTWARE: &str = "cview";
+const PAGE_NUMBER_TAG: Tag = Tag::Unknown(297);
+
+// Stored as JSON in each page's ImageDescription so other readers still see a plain 16-bit TIFF
+#[derive(Serialize, Deserialize)]
+struct PageDescription {
+    acquisition: AcquisitionMetadata,
+    frame: FrameMetadata,
+}
+
+pub fn write_tiff<W: Write + Seek>(writer: W, stack: &ImageStack) -> Result<(), ImageIoError> {
+    if stack.frames.is_empty() {
+        return Err(ImageIoError::Unsupported("image has no frames".into()));
+    }
+    // PageNumber holds SHORTs, longer sequences belong in a recorded stack instead
+    let page_count = u16::try_from(stack.frames.len()).map_err(|_| ImageIoError::Unsupported(format!("{} frames don't fit in a TIFF, at most {} do", stack.frames.len(), u16::MAX)))?;
+
+    let mut encoder = TiffEncoder::new(writer)?;
+    for (index, frame) in (0..page_count).zip(&stack.frames) {
+        let description = serde_json::to_string(&PageDescription {
+            acquisition: stack.acquisition.clone(),
+            frame: stack.frame_metadata.get(index as usize).copied().unwrap_or_default(),
+        })?;
+
+        let mut image = encoder.new_image::<colortype::Gray16>(frame.width(), frame.height())?;
+        image.encoder().write_tag(Tag::ImageDescription, description.as_str())?;
+        image.encoder().write_tag(Tag::Software, SOFTWARE)?;
+        if let Some(detector_id) = &stack.acquisition.detector_id {
+            image.encoder().write_tag(Tag::Model, detector_id.as_str())?;
+        }
+        image.encoder().write_tag(PAGE_NUMBER_TAG, &[index, page_count][..])?;
+        image.write_data(frame.as_raw())?;
+    }
+
+    Ok(())
+}
+
+pub fn read_tiff<R: Read + Seek>(reader: R) -> Result<ImageStack, ImageIoError> {
+    let mut decoder = Decoder::new(reader)?;
+    let mut stack = ImageStack::default();
+
+    loop {
+        let (width, height) = decoder.dimensions()?;
+        let data = match (decoder.colortype()?, decoder.read_image()?) {
+            (ColorType::Gray(16), DecodingResult::U16(data)) => data,
+            (ColorType::Gray(8), DecodingResult::U8(data)) => data.into_iter().map(|value| u16::from(value) << 8).collect(),
+            (colortype, _) => return Err(ImageIoError::Unsupported(format!("{colortype:?} TIFF pages are not supported"))),
+        };
+        let frame = ImageFrame::from_raw(width, height, data).ok_or_else(|| ImageIoError::Unsupported("TIFF page data does not match its dimensions".into()))?;
+
+        // Files written by other software won't carry our description, fall back to empty metadata for those
+        let description = decoder.get_tag_ascii_string(Tag::ImageDescription).ok().and_then(|description| serde_json::from_str::<PageDescription>(&description).ok());
+        match description {
+            Some(PageDescription { acquisition, frame }) => {
+                if stack.frames.is_empty() {
+                    stack.acquisition = acquisition;
+                }
+                stack.frame_metadata.push(frame);
+            }
+            None => stack.frame_metadata.push(FrameMetadata::default()),
+        }
+        stack.frames.push(frame);
+
+        if !decoder.more_images() {
+            break;
+        }
+        decoder.next_image()?;
+    }
+
+    Ok(stack)
+}
+
+pub fn save_tiff(path: impl AsRef<Path>, stack: &ImageStack) -> Result<(), ImageIoError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    write_tiff(&mut writer, stack)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn load_tiff(path: impl AsRef<Path>) -> Result<ImageStack, ImageIoError> {
+    read_tiff(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::Luma;
+
+    use super::*;
+
+    #[test]
+    fn tiff_round_trips_frames_and_metadata() {
+        let frames: Vec<_> = (0..3).map(|page| ImageFrame::from_fn(40, 30, |x, y| Luma([(page * 20000 + x * 100 + y) as u16]))).collect();
+        let acquisition = AcquisitionMetadata {
+            detector_id: Some("SL-1510".into()),
+            exposure_time_ms: Some(250),
+            pixel_pitch_mm: Some(0.1),
+            corrections: vec!["Dark subtracted".into()],
+            ..Default::default()
+        };
+        let frame_metadata: Vec<_> = (0..3).map(|page| FrameMetadata { frame_count: page, block_id: 100 + page as u64, timestamp: 5000 * page as u64, missing_packets: page % 2 }).collect();
+        let stack = ImageStack { frames, acquisition, frame_metadata };
+
+        let mut bytes = Cursor::new(Vec::new());
+        write_tiff(&mut bytes, &stack).unwrap();
+        bytes.set_position(0);
+        let read = read_tiff(bytes).unwrap();
+
+        assert_eq!(read.frames, stack.frames);
+        assert_eq!(read.acquisition, stack.acquisition);
+        assert_eq!(read.frame_metadata, stack.frame_metadata);
+    }
+
+    #[test]
+    fn more_pages_than_page_numbers_can_hold_are_rejected() {
+        let stack = ImageStack { frames: vec![ImageFrame::new(1, 1); u16::MAX as usize + 1], ..Default::default() };
+
+        assert!(matches!(write_tiff(Cursor::new(Vec::new()), &stack), Err(ImageIoError::Unsupported(_))));
+    }
+}
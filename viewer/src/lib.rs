@@ -1,6 +1,11 @@
+pub mod analysis;
 pub mod application;
 pub mod consts;
 pub mod dispatcher;
+pub mod event_bridge;
+pub mod io;
 pub mod messages;
 pub mod renderer;
+pub mod replay;
+pub mod utility_functions;
 pub mod utility_traits;
\ No newline at end of file
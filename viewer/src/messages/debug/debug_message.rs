@@ -0,0 +1,16 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
+pub enum DebugMessage {
+    MessageOff,
+    MessageNames,
+    MessageContents,
+
+    // Writes every dispatched message to the file as JSON lines, for attaching to bug reports
+    StartRecording {
+        path: PathBuf
+    },
+    StopRecording,
+}
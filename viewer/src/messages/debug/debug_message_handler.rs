@@ -0,0 +1,51 @@
+use uuid::Uuid;
+
+use crate::utility_functions::{clear_uuid_seed, set_uuid_seed};
+use crate::messages::prelude::*;
+
+use super::utility_types::{MessageLoggingVerbosity, MessageRecorder, RecordingHeader};
+
+#[derive(Default)]
+pub struct DebugMessageHandler {
+    pub message_logging_verbosity: MessageLoggingVerbosity,
+    recorder: Option<MessageRecorder>,
+}
+
+impl MessageHandler<DebugMessage, ()> for DebugMessageHandler {
+    fn process_message(&mut self, message: DebugMessage, responses: &mut VecDeque<Message>, data: ()) {
+        match message {
+            DebugMessage::MessageOff => self.message_logging_verbosity = MessageLoggingVerbosity::Off,
+            DebugMessage::MessageNames => self.message_logging_verbosity = MessageLoggingVerbosity::Names,
+            DebugMessage::MessageContents => self.message_logging_verbosity = MessageLoggingVerbosity::Contents,
+
+            // Ids are generated from a recorded seed from here on, so a replay of the session refers to the same images and annotations
+            DebugMessage::StartRecording { path } => {
+                let header = RecordingHeader { uuid_seed: Uuid::new_v4().as_u128() as u64 };
+                match MessageRecorder::create(&path, header) {
+                    Ok(recorder) => {
+                        set_uuid_seed(header.uuid_seed);
+                        self.recorder = Some(recorder);
+                    }
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to record to {}: {err}", path.display()) }),
+                }
+            }
+            DebugMessage::StopRecording => {
+                clear_uuid_seed();
+                if let Some(Err(err)) = self.recorder.take().map(MessageRecorder::finish) {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to finish the recording: {err}") });
+                }
+            }
+        }
+    }
+}
+
+impl DebugMessageHandler {
+    // A failed write ends the recording rather than failing every message after it
+    pub fn record(&mut self, depth: usize, message: &mut Message) {
+        let Some(recorder) = &mut self.recorder else { return };
+        if let Err(err) = recorder.record(depth, message) {
+            log::error!("Stopped recording messages: {err}");
+            self.recorder = None;
+        }
+    }
+}
@@ -0,0 +1,6 @@
+mod debug_message;
+mod debug_message_handler;
+pub mod utility_types;
+
+pub use debug_message::DebugMessage;
+pub use debug_message_handler::DebugMessageHandler;
@@ -0,0 +1,127 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::messages::prelude::Message;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum MessageLoggingVerbosity {
+    #[default]
+    Off,
+    Names,
+    Contents,
+}
+
+// The first line of a recording, replaying with the same seed generates the same ids
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct RecordingHeader {
+    pub uuid_seed: u64,
+}
+
+// Every following line, depth 0 is a message passed to the dispatcher and deeper ones were sent by handlers
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordedMessage<M> {
+    pub depth: usize,
+    pub message: M,
+}
+
+// Pixel data of the recorded frames, kept next to the recording instead of inside its JSON
+pub fn frames_path(path: impl AsRef<Path>) -> PathBuf {
+    path.as_ref().with_extension("frames")
+}
+
+// Frames are stored one after another as a little-endian u64 length followed by that many u16 pixels
+pub fn read_frame_data(reader: &mut impl Read) -> io::Result<Vec<u16>> {
+    let mut len = [0; 8];
+    reader.read_exact(&mut len)?;
+    let mut bytes = vec![0; u64::from_le_bytes(len) as usize * 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(bytes.chunks_exact(2).map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]])).collect())
+}
+
+pub struct MessageRecorder {
+    writer: BufWriter<File>,
+    frames: BufWriter<File>,
+}
+
+impl MessageRecorder {
+    pub fn create(path: impl AsRef<Path>, header: RecordingHeader) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(&path)?);
+        serde_json::to_writer(&mut writer, &header)?;
+        writer.write_all(b"\n")?;
+        let frames = BufWriter::new(File::create(frames_path(path))?);
+        Ok(Self { writer, frames })
+    }
+
+    // The message's pixel data is moved out while it is written and put back afterwards. Replays only send messages
+    // of depth 0, so frames of deeper ones are left out of the recording altogether.
+    pub fn record(&mut self, depth: usize, message: &mut Message) -> io::Result<()> {
+        let frame_data: Vec<_> = message.frames_mut().into_iter().map(|frame| std::mem::take(&mut frame.data)).collect();
+        let result = self.write(depth, message, &frame_data);
+        for (frame, data) in message.frames_mut().into_iter().zip(frame_data) {
+            frame.data = data;
+        }
+        result
+    }
+
+    fn write(&mut self, depth: usize, message: &Message, frame_data: &[Vec<u16>]) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, &RecordedMessage { depth, message })?;
+        self.writer.write_all(b"\n")?;
+        if depth == 0 {
+            for data in frame_data {
+                self.frames.write_all(&(data.len() as u64).to_le_bytes())?;
+                for pixel in data {
+                    self.frames.write_all(&pixel.to_le_bytes())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.frames.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+    use crate::messages::portfolio::image::utility_types::misc::RawFrame;
+    use crate::messages::prelude::*;
+
+    fn raw_frame(value: u16) -> RawFrame {
+        RawFrame { width: 2, height: 2, data: vec![value; 4], metadata: FrameMetadata::default() }
+    }
+
+    #[test]
+    fn frame_data_is_written_by_reference_for_top_level_messages_only() {
+        let path = std::env::temp_dir().join(format!("recording-{}.jsonl", Uuid::new_v4()));
+        let mut recorder = MessageRecorder::create(&path, RecordingHeader { uuid_seed: 1 }).unwrap();
+        let mut acquisition: Message = PortfolioMessage::NewImageFromAcquisition { name: None, acquisition: AcquisitionMetadata::default(), frames: vec![raw_frame(1), raw_frame(2)] }.into();
+        let mut live_frame: Message = PortfolioMessage::LiveFrame { frame: raw_frame(3) }.into();
+        recorder.record(0, &mut acquisition).unwrap();
+        recorder.record(1, &mut live_frame).unwrap();
+        recorder.finish().unwrap();
+
+        let recording = fs::read_to_string(&path).unwrap();
+        let mut frames = File::open(frames_path(&path)).unwrap();
+        let frame_data = [read_frame_data(&mut frames).unwrap(), read_frame_data(&mut frames).unwrap()];
+        let remaining = frames.read(&mut [0]).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(frames_path(&path)).unwrap();
+
+        assert_eq!(recording.lines().count(), 3);
+        assert!(recording.lines().skip(1).all(|line| line.contains(r#""data":[]"#)), "{recording}");
+        assert_eq!(frame_data, [vec![1; 4], vec![2; 4]]);
+        assert_eq!(remaining, 0);
+        // The messages get their frames back for the handlers
+        assert_eq!(live_frame.frames_mut()[0].data, [3; 4]);
+        assert_eq!(acquisition.frames_mut().iter().map(|frame| frame.data[0]).collect::<Vec<_>>(), [1, 2]);
+    }
+}
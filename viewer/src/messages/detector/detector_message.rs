@@ -1,9 +1,39 @@
-use capture::CaptureMode;
+use std::path::PathBuf;
+
+use capture::{CaptureMode, DetectorInterface};
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize, specta::Type)]
+use super::utility_types::{CaptureEvent, CaptureId};
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum DetectorMessage {
     StartCapture(CaptureMode),
     StopCapture,
-    SendSoftwareTrigger
-}
\ No newline at end of file
+    SendSoftwareTrigger,
+
+    // Sent by the capture through the event bridge, events of an earlier capture are ignored
+    CaptureEvent {
+        capture_id: CaptureId,
+        event: CaptureEvent
+    },
+    Connect {
+        interface: DetectorInterface
+    },
+    ConnectionFailed {
+        description: String
+    },
+    // Sent once the detector has opened, virtual detectors may have no id
+    DetectorIdentified {
+        detector_id: Option<String>
+    },
+    Disconnect,
+    // Plays a recording back as if it came from a connected detector
+    OpenReplay {
+        path: PathBuf,
+        looping: bool
+    },
+    // Read periodically for as long as the detector is connected
+    TemperatureMeasured {
+        celsius: f32
+    },
+}
@@ -1,57 +1,235 @@
-use std::{sync::{mpsc::Receiver, Mutex, Arc}, thread};
-use futures_util::StreamExt;
+use capture::{CaptureHandle, CaptureMode, DetectorEvent, DetectorHandle, ReplayDetector, ReplaySpeed};
+use std::time::Duration;
 
-use capture::{DetectorController, DetectorEvent, CaptureHandle};
+use tokio::runtime::Handle;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
 
+use crate::event_bridge::MessageSender;
 use crate::messages::prelude::*;
+use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+use crate::messages::portfolio::image::utility_types::misc::RawFrame;
 
+use super::utility_types::{CaptureEvent, CaptureId};
+
+const TEMPERATURE_PERIOD_MILLIS: u64 = 5000;
+const TEMPERATURE_SENSOR: u32 = 0;
+
+// Detector calls block, so the detector and its captures run on the background runtime and report back through messages
 pub struct DetectorMessageHandler {
-    detector_controller: DetectorController,
-    handle: Arc<Mutex<Option<Box<dyn CaptureHandle + Send>>>>,
+    runtime: Handle,
+    messages: MessageSender,
+    detector_handle: Option<DetectorHandle>,
+    // Opens the detector and then reads its temperature for as long as it stays connected
+    connection_task: Option<JoinHandle<()>>,
+    capture_handle: Option<CaptureHandle>,
+    capture_id: CaptureId,
+    // What is known about the connected detector, captures add their own settings to it
+    acquisition: AcquisitionMetadata,
 }
 
-impl DetectorMessageHandler {
-    pub fn new(responses: &mut Vec<FrontendMessage>) -> Self {
-        let (detector_controller, rx) = DetectorController::new();
+impl MessageHandler<DetectorMessage, ()> for DetectorMessageHandler {
+    fn process_message(&mut self, message: DetectorMessage, responses: &mut VecDeque<Message>, data: ()) {
+        match message {
+            DetectorMessage::StartCapture(mode) => {
+                let Some(detector_handle) = &self.detector_handle else {
+                    responses.add(FrontendMessage::DisplayDialog { title: "Connect a detector before starting a capture".into() });
+                    return;
+                };
+                if self.capture_handle.is_some() {
+                    responses.add(FrontendMessage::DisplayDialog { title: "A capture is already running".into() });
+                    return;
+                }
+
+                self.capture_id = CaptureId(self.capture_id.0.wrapping_add(1));
+                let (event_sender, events) = mpsc::unbounded_channel();
+                let _guard = self.runtime.enter();
+                self.capture_handle = Some(CaptureHandle::start(detector_handle.acquisition_handle(), mode, event_sender));
+                self.runtime.spawn(forward_capture_events(events, self.capture_id, mode, self.messages.clone()));
 
+                self.acquisition.exposure_time_ms = Some(mode.exposure_time_ms());
+                self.acquisition.exposure_mode = Some(format!("{:?}", mode.exposure_mode()));
+                responses.add(PortfolioMessage::BeginLiveCapture {
+                    acquisition: self.acquisition.clone(),
+                    keep_frames: mode.frame_limit().is_some(),
+                });
+            }
+            DetectorMessage::StopCapture => {
+                if let Some(capture_handle) = &self.capture_handle {
+                    capture_handle.stop();
+                }
+            }
+            DetectorMessage::SendSoftwareTrigger => match &self.capture_handle {
+                Some(capture_handle) if matches!(capture_handle.mode(), CaptureMode::SoftwareTrigger { .. }) => capture_handle.software_trigger(),
+                _ => responses.add(FrontendMessage::DisplayDialog { title: "Start a software triggered capture before sending a trigger".into() }),
+            },
+
+            DetectorMessage::CaptureEvent { capture_id, event } => {
+                if capture_id != self.capture_id {
+                    return;
+                }
+                self.process_capture_event(event, responses);
+            }
+            DetectorMessage::Connect { interface } => match DetectorHandle::new(interface.into()) {
+                Ok(detector_handle) => self.connect(detector_handle, responses),
+                Err(description) => responses.add(DetectorMessage::ConnectionFailed { description }),
+            },
+            DetectorMessage::ConnectionFailed { description } => {
+                self.disconnect(responses);
+                responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to connect to the detector: {description}") });
+                self.send_capture_status(responses);
+            }
+            DetectorMessage::DetectorIdentified { detector_id } => {
+                if self.detector_handle.is_none() {
+                    return;
+                }
+                self.acquisition.detector_id = detector_id;
+                self.update_live_acquisition(responses);
+            }
+            // The detector closes once the capture has let go of it
+            DetectorMessage::Disconnect => {
+                self.disconnect(responses);
+                self.send_capture_status(responses);
+            }
+            DetectorMessage::OpenReplay { path, looping } => {
+                let detector = match ReplayDetector::open(&path, ReplaySpeed::Original, looping) {
+                    Ok(detector) => detector,
+                    Err(err) => {
+                        responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to open {}: {err}", path.display()) });
+                        return;
+                    }
+                };
+                self.connect(DetectorHandle::from_detector(detector), responses);
+            }
+            DetectorMessage::TemperatureMeasured { celsius } => {
+                if self.detector_handle.is_none() {
+                    return;
+                }
+                self.acquisition.temperature = Some(celsius);
+                self.update_live_acquisition(responses);
+                responses.add(FrontendMessage::UpdateDetectorTemperature { celsius });
+            }
+        }
+    }
+}
+
+impl DetectorMessageHandler {
+    pub fn new(runtime: Handle, messages: MessageSender) -> Self {
         Self {
-            detector_controller,
-            handle: Arc::new(Mutex::new(None)),
+            runtime,
+            messages,
+            detector_handle: None,
+            connection_task: None,
+            capture_handle: None,
+            capture_id: CaptureId(0),
+            acquisition: AcquisitionMetadata::default(),
         }
     }
 
-    pub fn set_handle(&self, new_handle: Box<dyn CaptureHandle + Send>) {
-        {
-            let mut handle_lock = self.handle.lock().unwrap();
-            *handle_lock = Some(new_handle);
+    fn process_capture_event(&mut self, event: CaptureEvent, responses: &mut VecDeque<Message>) {
+        match event {
+            CaptureEvent::Started => self.send_capture_status(responses),
+            CaptureEvent::Frame(frame) => responses.add(PortfolioMessage::LiveFrame { frame }),
+            CaptureEvent::Progress { frames, total } => responses.add(FrontendMessage::UpdateCaptureProgress { frames, total }),
+            CaptureEvent::Error { description } => responses.add(FrontendMessage::DisplayDialog { title: format!("Detector error: {description}") }),
+            CaptureEvent::Finished { .. } => {
+                self.capture_handle = None;
+                responses.add(PortfolioMessage::EndLiveCapture);
+                self.send_capture_status(responses);
+            }
         }
-        
-        let handle_clone = Arc::clone(&self.handle);
+    }
 
-        let thread_handle = thread::spawn(move || {
-            Self::process_stream(handle_clone);
-        });
+    // Opening the camera blocks, so it runs on the background runtime and a failure comes back as ConnectionFailed
+    fn connect(&mut self, detector_handle: DetectorHandle, responses: &mut VecDeque<Message>) {
+        self.disconnect(responses);
+        self.connection_task = Some(self.runtime.spawn(run_connection(detector_handle.clone(), self.messages.clone())));
+        self.detector_handle = Some(detector_handle);
+        self.send_capture_status(responses);
     }
 
-    fn process_stream(handle: Arc<Mutex<Option<Box<dyn CaptureHandle + Send>>>>) {
-        let handle_lock: std::sync::MutexGuard<'_, Option<Box<dyn CaptureHandle + Send>>> = handle.lock().unwrap();
-        let mut stream = handle_lock.as_ref().unwrap().get_stream();
+    // The detector closes once the capture and the connection task have let go of it
+    fn disconnect(&mut self, responses: &mut VecDeque<Message>) {
+        self.stop_capture(responses);
+        if let Some(connection_task) = self.connection_task.take() {
+            connection_task.abort();
+        }
+        self.detector_handle = None;
+        self.acquisition = AcquisitionMetadata::default();
+    }
 
-        while let Some(item) = stream.next() {
+    // Moving on to the next capture id drops any events the old capture still has in flight, including the one that
+    // would have ended it, so it is ended here with the frames that made it
+    fn stop_capture(&mut self, responses: &mut VecDeque<Message>) {
+        if self.capture_handle.take().is_some() {
+            responses.add(PortfolioMessage::EndLiveCapture);
+        }
+        self.capture_id = CaptureId(self.capture_id.0.wrapping_add(1));
+    }
 
+    // A capture started before the detector finished identifying itself still gets what it learns
+    fn update_live_acquisition(&self, responses: &mut VecDeque<Message>) {
+        if self.capture_handle.is_some() {
+            responses.add(PortfolioMessage::UpdateLiveAcquisition { acquisition: self.acquisition.clone() });
         }
     }
 
+    fn send_capture_status(&self, responses: &mut VecDeque<Message>) {
+        responses.add(FrontendMessage::UpdateCaptureStatus {
+            connected: self.detector_handle.is_some(),
+            capturing: self.capture_handle.is_some(),
+        });
+    }
 }
 
-impl MessageHandler<DetectorMessage, ()> for DetectorMessageHandler {
-    fn process_message(&mut self, message: DetectorMessage, responses: &mut VecDeque<Message>, data: ()) {
-
+// Runs on the background runtime, the temperature is read whether or not a capture is running
+async fn run_connection(detector_handle: DetectorHandle, messages: MessageSender) {
+    if let Err(err) = detector_handle.open_camera().await {
+        messages.send(DetectorMessage::ConnectionFailed { description: format!("{err:?}") });
+        return;
+    }
+    messages.send(DetectorMessage::DetectorIdentified { detector_id: detector_handle.device_id().await });
+    let acquisition_handle = detector_handle.acquisition_handle();
+    let mut interval = tokio::time::interval(Duration::from_millis(TEMPERATURE_PERIOD_MILLIS));
+    loop {
+        interval.tick().await;
+        // Virtual detectors have no sensor to read
+        let Ok(celsius) = acquisition_handle.measure_temperature(TEMPERATURE_SENSOR).await else { break };
+        if !messages.send(DetectorMessage::TemperatureMeasured { celsius }) {
+            break;
+        }
     }
 }
 
-pub fn detector_thread() {
-    loop {
-        
+// Runs on the background runtime, converting the capture's events into messages for the dispatcher
+async fn forward_capture_events(mut events: mpsc::UnboundedReceiver<DetectorEvent>, capture_id: CaptureId, mode: CaptureMode, messages: MessageSender) {
+    let send = |event| messages.send(DetectorMessage::CaptureEvent { capture_id, event });
+    let mut frames = 0;
+    while let Some(event) = events.recv().await {
+        let delivered = match event {
+            DetectorEvent::Started(_) => send(CaptureEvent::Started),
+            DetectorEvent::Frame(buffer_info, data) => {
+                let frame = RawFrame {
+                    width: buffer_info.width,
+                    height: buffer_info.height,
+                    data,
+                    metadata: FrameMetadata {
+                        frame_count: buffer_info.frame_count,
+                        block_id: buffer_info.block_id,
+                        timestamp: buffer_info.timestamp,
+                        missing_packets: buffer_info.missing_packets,
+                    },
+                };
+                frames += 1;
+                send(CaptureEvent::Frame(frame));
+                send(CaptureEvent::Progress { frames, total: mode.frame_limit() })
+            }
+            DetectorEvent::Error(err) => send(CaptureEvent::Error { description: format!("{err:?}") }),
+            DetectorEvent::Finished { frames } => send(CaptureEvent::Finished { frames }),
+        };
+        // Stopping early drops the capture's event channel, which stops the capture itself
+        if !delivered {
+            break;
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 mod detector_message;
 mod detector_message_handler;
+pub mod utility_types;
 
 pub use detector_message::DetectorMessage;
-pub use detector_message_handler::DetectorMessageHandler;
\ No newline at end of file
+pub use detector_message_handler::DetectorMessageHandler;
@@ -0,0 +1,24 @@
+use serde::{Deserialize, Serialize};
+
+use crate::messages::portfolio::image::utility_types::misc::RawFrame;
+
+// Tells the events of one capture apart from those of the captures before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, specta::Type)]
+pub struct CaptureId(pub u32);
+
+// What a running capture reports, converted from the capture crate's events so it can travel in messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub enum CaptureEvent {
+    Started,
+    Frame(RawFrame),
+    Progress {
+        frames: u32,
+        total: Option<u32>
+    },
+    Error {
+        description: String
+    },
+    Finished {
+        frames: u32
+    },
+}
@@ -1,12 +1,191 @@
+use std::collections::HashSet;
+use std::mem::Discriminant;
+
 use serde::{Serialize, Deserialize};
 
+use crate::analysis::{mtf::MtfResult, nps::{DqeResult, NpsResult}};
+use crate::messages::portfolio::image::utility_types::{histogram::HistogramData, metadata::FrameMetadata, misc::{AdjustmentLevels, AnnotationId, ImageId}, navigation::ViewportTransform, profile::LineProfile, statistics::RoiStatistics};
+use crate::messages::portfolio::ViewportSync;
 use crate::messages::tool::utility_types::{ToolType};
 
+use super::utility_types::{FrontendImageDetails, FrontendKeyBinding};
+
 #[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum FrontendMessage {
+    // The annotation is gone, its statistics, histogram, line profile and MTF should no longer be shown
+    ClearAnnotationResults {
+        image_id: ImageId,
+        annotation_id: AnnotationId
+    },
     DisplayDialog {
         title: String
     },
     SetActiveTool(ToolType),
     TriggerViewportResize,
-}
\ No newline at end of file
+    UpdateCaptureProgress {
+        frames: u32,
+        total: Option<u32>
+    },
+    UpdateCaptureStatus {
+        connected: bool,
+        capturing: bool
+    },
+    UpdateDetectorTemperature {
+        celsius: f32
+    },
+    // The image's displayed pixels changed, the host renders them with display_image and publishes them to the frontend
+    UpdateDisplayImage {
+        image_id: ImageId,
+        width: u32,
+        height: u32
+    },
+    UpdateAdjustmentLevels {
+        image_id: ImageId,
+        adjustment_levels: AdjustmentLevels
+    },
+    UpdateDqe {
+        result: DqeResult
+    },
+    UpdateFrameInfo {
+        image_id: ImageId,
+        frame_index: u32,
+        frame_count: u32,
+        metadata: FrameMetadata,
+        playing: bool
+    },
+    UpdateKeyBindings {
+        bindings: Vec<FrontendKeyBinding>
+    },
+    UpdateHistogram {
+        image_id: ImageId,
+        annotation_id: Option<AnnotationId>,
+        histogram: HistogramData
+    },
+    UpdateLineProfile {
+        image_id: ImageId,
+        annotation_id: AnnotationId,
+        profile: LineProfile
+    },
+    UpdateMtf {
+        image_id: ImageId,
+        annotation_id: AnnotationId,
+        result: MtfResult
+    },
+    UpdateOpenImages {
+        images: Vec<FrontendImageDetails>,
+        active_image_id: Option<ImageId>
+    },
+    UpdateNoisePowerSpectrum {
+        result: NpsResult
+    },
+    UpdateRoiStatistics {
+        image_id: ImageId,
+        annotation_id: AnnotationId,
+        statistics: RoiStatistics
+    },
+    // The matrix maps image pixels to viewport pixels, in the order taken by a canvas's setTransform
+    UpdateViewportTransform {
+        image_id: ImageId,
+        transform: ViewportTransform,
+        matrix: [f64; 6]
+    },
+    // The image shown in each viewport, in the order of their bounds
+    UpdateViewports {
+        viewports: Vec<Option<ImageId>>,
+        active_viewport: u32,
+        sync: ViewportSync
+    },
+}
+
+// Identifies updates that carry the full state of something, a later one with the same key makes the earlier one redundant
+#[derive(PartialEq, Eq, Hash)]
+struct CoalescingKey(Discriminant<FrontendMessage>, Option<ImageId>, Option<AnnotationId>);
+
+impl FrontendMessage {
+    fn coalescing_key(&self) -> Option<CoalescingKey> {
+        let (image_id, annotation_id) = match self {
+            // Every dialog has to be shown
+            FrontendMessage::DisplayDialog { .. } => return None,
+            FrontendMessage::UpdateAdjustmentLevels { image_id, .. }
+            | FrontendMessage::UpdateDisplayImage { image_id, .. }
+            | FrontendMessage::UpdateFrameInfo { image_id, .. }
+            | FrontendMessage::UpdateViewportTransform { image_id, .. } => (Some(*image_id), None),
+            FrontendMessage::UpdateHistogram { image_id, annotation_id, .. } => (Some(*image_id), *annotation_id),
+            FrontendMessage::ClearAnnotationResults { image_id, annotation_id }
+            | FrontendMessage::UpdateLineProfile { image_id, annotation_id, .. }
+            | FrontendMessage::UpdateMtf { image_id, annotation_id, .. }
+            | FrontendMessage::UpdateRoiStatistics { image_id, annotation_id, .. } => (Some(*image_id), Some(*annotation_id)),
+            _ => (None, None),
+        };
+        Some(CoalescingKey(std::mem::discriminant(self), image_id, annotation_id))
+    }
+
+    // Drops updates superseded later in the same batch, the rest keep their order with each update at its last position
+    pub fn coalesce(messages: Vec<FrontendMessage>) -> Vec<FrontendMessage> {
+        let mut seen = HashSet::new();
+        let mut coalesced: Vec<_> = messages
+            .into_iter()
+            .rev()
+            .filter(|message| message.coalescing_key().map_or(true, |key| seen.insert(key)))
+            .collect();
+        coalesced.reverse();
+        coalesced
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn frame_info(image_id: ImageId, frame_index: u32) -> FrontendMessage {
+        FrontendMessage::UpdateFrameInfo { image_id, frame_index, frame_count: 10, metadata: FrameMetadata::default(), playing: false }
+    }
+
+    fn clear(image_id: ImageId, annotation_id: AnnotationId) -> FrontendMessage {
+        FrontendMessage::ClearAnnotationResults { image_id, annotation_id }
+    }
+
+    fn dialog(title: &str) -> FrontendMessage {
+        FrontendMessage::DisplayDialog { title: title.into() }
+    }
+
+    #[test]
+    fn later_update_replaces_an_earlier_one_at_its_own_position() {
+        let image_id = ImageId(Uuid::new_v4());
+        let messages = vec![frame_info(image_id, 0), FrontendMessage::TriggerViewportResize, frame_info(image_id, 1), FrontendMessage::SetActiveTool(ToolType::default())];
+        let expected = vec![FrontendMessage::TriggerViewportResize, frame_info(image_id, 1), FrontendMessage::SetActiveTool(ToolType::default())];
+        assert_eq!(FrontendMessage::coalesce(messages), expected);
+    }
+
+    #[test]
+    fn updates_are_kept_per_image_and_annotation() {
+        let (first_image, second_image) = (ImageId(Uuid::new_v4()), ImageId(Uuid::new_v4()));
+        let (first_annotation, second_annotation) = (AnnotationId(Uuid::new_v4()), AnnotationId(Uuid::new_v4()));
+        let messages = vec![
+            frame_info(first_image, 0),
+            frame_info(second_image, 0),
+            clear(first_image, first_annotation),
+            clear(first_image, second_annotation),
+            clear(second_image, first_annotation),
+            clear(first_image, first_annotation),
+            frame_info(first_image, 3),
+        ];
+        let expected = vec![
+            frame_info(second_image, 0),
+            clear(first_image, second_annotation),
+            clear(second_image, first_annotation),
+            clear(first_image, first_annotation),
+            frame_info(first_image, 3),
+        ];
+        assert_eq!(FrontendMessage::coalesce(messages), expected);
+    }
+
+    #[test]
+    fn dialogs_are_never_dropped() {
+        let messages = vec![dialog("first"), FrontendMessage::TriggerViewportResize, dialog("first"), dialog("second"), FrontendMessage::TriggerViewportResize];
+        let expected = vec![dialog("first"), dialog("first"), dialog("second"), FrontendMessage::TriggerViewportResize];
+        assert_eq!(FrontendMessage::coalesce(messages), expected);
+    }
+}
@@ -1,3 +1,5 @@
 mod frontend_message;
 
+pub mod utility_types;
+
 pub use frontend_message::FrontendMessage;
\ No newline at end of file
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+
+use crate::messages::input_mapper::utility_types::misc::{InputAction, KeyBinding};
+use crate::messages::portfolio::image::utility_types::misc::ImageId;
+use crate::messages::portfolio::image::utility_types::navigation::ViewportTransform;
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
+pub struct FrontendImageDetails {
+    pub id: ImageId,
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+    pub frame_count: u32,
+    pub viewport_transform: ViewportTransform,
+}
+
+// The shortcuts of an action, as shown in tooltips and the shortcut list
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
+pub struct FrontendKeyBinding {
+    pub action: InputAction,
+    pub bindings: Vec<KeyBinding>,
+    pub labels: Vec<String>,
+}
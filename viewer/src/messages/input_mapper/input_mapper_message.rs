@@ -1,10 +1,50 @@
-use crate::messages::input_mapper::utility_types::input_mouse::MouseButton;
+use std::path::PathBuf;
 
-use super::utility_types::input_mouse::ViewportBounds;
+use serde::{Deserialize, Serialize};
 
+use super::utility_types::input_keyboard::{Key, ModifierKeys};
+use super::utility_types::input_mouse::{EditorPosition, MouseButton, ScrollDelta, ViewportBounds};
+
+// Raw input from the frontend, positions are relative to the editor window
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum InputMapperMessage {
-    DoubleClick(MouseButton),
-    PointerMove,
+    DoubleClick {
+        editor_position: EditorPosition,
+        button: MouseButton,
+        modifiers: ModifierKeys
+    },
+    KeyDown {
+        key: Key,
+        modifiers: ModifierKeys
+    },
+    KeyUp {
+        key: Key,
+        modifiers: ModifierKeys
+    },
+    // Applies the shortcuts in a keymap file on top of the defaults
+    LoadKeymap {
+        path: PathBuf
+    },
+    PointerDown {
+        editor_position: EditorPosition,
+        button: MouseButton,
+        modifiers: ModifierKeys
+    },
+    PointerMove {
+        editor_position: EditorPosition,
+        modifiers: ModifierKeys
+    },
+    PointerUp {
+        editor_position: EditorPosition,
+        button: MouseButton,
+        modifiers: ModifierKeys
+    },
+    ResetKeymap,
+    SendKeyBindings,
     ViewportBounds(Vec<ViewportBounds>),
-    WheelScroll,
-}
\ No newline at end of file
+    WheelScroll {
+        editor_position: EditorPosition,
+        delta: ScrollDelta,
+        modifiers: ModifierKeys
+    },
+}
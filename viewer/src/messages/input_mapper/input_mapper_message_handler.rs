@@ -1,26 +1,276 @@
-use super::{utility_types::input_mouse::{ViewportBounds, MouseState}, input_mapper_message::InputMapperMessage};
-use crate::messages::prelude::*;
+use std::collections::HashSet;
 
+use super::input_mapper_message::InputMapperMessage;
+use super::keymap::Keymap;
+use super::utility_types::input_keyboard::{Key, ModifierKeys};
+use super::utility_types::input_mouse::{EditorMouseState, EditorPosition, MouseButton, MouseState, ScrollDelta, ViewportBounds};
+use super::utility_types::misc::KeyBinding;
+use crate::consts::VIEWPORT_ZOOM_WHEEL_RATE;
+use crate::messages::prelude::*;
 
+// Keeps track of the keys and buttons held, turning key presses into the messages bound to them and pointer input
+// over the viewports into tool events and navigation of the image under the cursor
 #[derive(Debug, Default)]
 pub struct InputMapperMessageHandler {
+    pub keyboard: HashSet<Key>,
+    pub modifiers: ModifierKeys,
+    // The position is relative to the viewport the pointer input goes to
     pub mouse: MouseState,
-    pub viewport_bounds: ViewportBounds
+    pub viewport_bounds: Vec<ViewportBounds>,
+    keymap: Keymap,
+    // The viewport the left button was pressed in, while it is held
+    dragging: Option<u32>,
+    // The viewport the middle button was pressed in, while it is held
+    panning: Option<u32>,
 }
 
-impl MessageHandler<InputMapperMessage, ()> for InputMapperMessageHandler {
-    fn process_message(&mut self, message: InputMapperMessage, responses: &mut VecDeque<Message>, data: ()) {
+// The active viewport is kept by the portfolio, its image is the one tools work on
+impl MessageHandler<InputMapperMessage, u32> for InputMapperMessageHandler {
+    fn process_message(&mut self, message: InputMapperMessage, responses: &mut VecDeque<Message>, active_viewport: u32) {
         match message {
             InputMapperMessage::ViewportBounds(viewport_bounds) => {
-                assert_eq!(viewport_bounds.len(), 1, "Only one viewport is currently supported");
-                
-                for bounds in viewport_bounds {
-                    self.viewport_bounds = bounds;
+                let count = viewport_bounds.len() as u32;
+                if count < self.viewport_bounds.len() as u32 {
+                    // A drag in a viewport that is gone has nowhere left to go
+                    self.panning = self.panning.filter(|&viewport| viewport < count);
+                    if self.dragging.is_some_and(|viewport| viewport >= count) {
+                        self.dragging = None;
+                        responses.add(ToolMessage::Abort);
+                    }
+                    responses.add(PortfolioMessage::SetViewportCount { count });
+                }
+                self.viewport_bounds = viewport_bounds;
+            }
+            InputMapperMessage::KeyDown { key, modifiers } => {
+                self.keyboard.insert(key);
+                self.modifiers = modifiers;
+                if let Some(action) = self.keymap.action(KeyBinding::new(key, modifiers)) {
+                    responses.add(action.to_message());
+                }
+            }
+            InputMapperMessage::KeyUp { key, modifiers } => {
+                self.keyboard.remove(&key);
+                self.modifiers = modifiers;
+            }
+            InputMapperMessage::LoadKeymap { path } => match Keymap::load(&path) {
+                Ok(keymap) => {
+                    self.keymap = keymap;
+                    responses.add(InputMapperMessage::SendKeyBindings);
+                }
+                Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to load the keymap {}: {err}", path.display()) }),
+            },
+            InputMapperMessage::ResetKeymap => {
+                self.keymap = Keymap::default();
+                responses.add(InputMapperMessage::SendKeyBindings);
+            }
+            InputMapperMessage::SendKeyBindings => responses.add(FrontendMessage::UpdateKeyBindings { bindings: self.keymap.frontend_bindings() }),
+            InputMapperMessage::PointerDown { editor_position, button, modifiers } => {
+                let viewport = self.update_mouse(editor_position, modifiers, self.mouse.scroll_delta, active_viewport);
+                self.mouse.mouse_keys.insert(button.into());
+
+                let Some(viewport) = viewport else { return };
+                match button {
+                    // Selected first so the tool works on the image in the viewport that was clicked
+                    MouseButton::Left => {
+                        self.dragging = Some(viewport);
+                        responses.add(PortfolioMessage::SelectViewport { viewport });
+                        responses.add(ToolMessage::DragStart { position: self.mouse.position });
+                    }
+                    MouseButton::Middle => self.panning = Some(viewport),
+                    MouseButton::Right => {}
+                }
+            }
+            InputMapperMessage::PointerMove { editor_position, modifiers } => {
+                let previous_position = self.mouse.position;
+                let viewport = self.update_mouse(editor_position, modifiers, self.mouse.scroll_delta, active_viewport);
+
+                if let Some(viewport) = self.panning {
+                    responses.add(PortfolioMessage::Viewport { viewport, message: ImageMessage::PanCanvas { delta: self.mouse.position - previous_position } });
+                }
+
+                // Hovering over another viewport's image means nothing to the active tool
+                if self.dragging.is_some() || viewport == Some(active_viewport) {
+                    responses.add(ToolMessage::PointerMove { position: self.mouse.position });
+                }
+            }
+            InputMapperMessage::PointerUp { editor_position, button, modifiers } => {
+                self.update_mouse(editor_position, modifiers, self.mouse.scroll_delta, active_viewport);
+                self.mouse.mouse_keys.remove(button.into());
+
+                // Released outside the viewport the drag still ends, so the tool isn't left mid-drag
+                if button == MouseButton::Left && self.dragging.take().is_some() {
+                    responses.add(ToolMessage::DragStop { position: self.mouse.position });
+                }
+                if button == MouseButton::Middle {
+                    self.panning = None;
+                }
+            }
+            InputMapperMessage::DoubleClick { editor_position, button, modifiers } => {
+                let viewport = self.update_mouse(editor_position, modifiers, self.mouse.scroll_delta, active_viewport);
+
+                if button == MouseButton::Left && viewport == Some(active_viewport) {
+                    responses.add(ToolMessage::DoubleClick { position: self.mouse.position });
+                }
+            }
+            InputMapperMessage::WheelScroll { editor_position, delta, modifiers } => {
+                let viewport = self.update_mouse(editor_position, modifiers, delta, active_viewport);
+
+                // Scrolling up zooms in towards the cursor
+                if let Some(viewport) = viewport.filter(|_| delta.y != 0) {
+                    let zoom_factor = 1. + delta.y.abs() as f64 * VIEWPORT_ZOOM_WHEEL_RATE;
+                    let zoom_factor = if delta.y > 0 { 1. / zoom_factor } else { zoom_factor };
+                    responses.add(PortfolioMessage::Viewport { viewport, message: ImageMessage::ZoomCanvasAt { zoom_factor, position: self.mouse.position } });
                 }
             }
-            InputMapperMessage::DoubleClick(_) => todo!(),
-            InputMapperMessage::PointerMove => todo!(),
-            InputMapperMessage::WheelScroll => todo!(),
         }
     }
-}
\ No newline at end of file
+}
+
+impl InputMapperMessageHandler {
+    pub fn keymap(&self) -> &Keymap {
+        &self.keymap
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.keyboard.contains(&key)
+    }
+
+    // Input goes to the viewport a drag started in until it ends, otherwise to the one under the cursor
+    fn target_viewport(&self, editor_position: EditorPosition) -> Option<u32> {
+        self.dragging.or(self.panning).or_else(|| {
+            let viewport = self.viewport_bounds.iter().position(|bounds| bounds.in_bounds(bounds.editor_to_viewport(editor_position)))?;
+            Some(viewport as u32)
+        })
+    }
+
+    // Returns the viewport the input goes to, None when the cursor is outside every viewport. The position is then
+    // kept relative to the active viewport.
+    fn update_mouse(&mut self, editor_position: EditorPosition, modifiers: ModifierKeys, scroll_delta: ScrollDelta, active_viewport: u32) -> Option<u32> {
+        let viewport = self.target_viewport(editor_position);
+        let bounds = self.viewport_bounds.get(viewport.unwrap_or(active_viewport) as usize).copied().unwrap_or_default();
+        let editor_mouse_state = EditorMouseState { editor_position, mouse_keys: self.mouse.mouse_keys, scroll_delta };
+        self.mouse = editor_mouse_state.to_mouse_state(&bounds);
+        self.modifiers = modifiers;
+        viewport
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::{DVec2, IVec2};
+    use image::Luma;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+    use crate::messages::portfolio::image::utility_types::misc::{ImageFrame, ImagePosition};
+    use crate::messages::tool::tool_messages::select_tool::SelectTool;
+    use crate::messages::tool::utility_types::ToolTransition;
+
+    fn send(input_mapper: &mut InputMapperMessageHandler, message: InputMapperMessage) -> Vec<Message> {
+        send_with_active(input_mapper, message, 0)
+    }
+
+    fn send_with_active(input_mapper: &mut InputMapperMessageHandler, message: InputMapperMessage, active_viewport: u32) -> Vec<Message> {
+        let mut responses = VecDeque::new();
+        input_mapper.process_message(message, &mut responses, active_viewport);
+        responses.into()
+    }
+
+    fn key_down(key: Key, modifiers: ModifierKeys) -> InputMapperMessage {
+        InputMapperMessage::KeyDown { key, modifiers }
+    }
+
+    // Two viewports side by side, 200 pixels wide each
+    fn two_viewports() -> InputMapperMessageHandler {
+        let mut input_mapper = InputMapperMessageHandler::default();
+        let bounds = |left: f64| ViewportBounds { top_left: DVec2::new(left, 0.), bottom_right: DVec2::new(left + 200., 200.) };
+        send(&mut input_mapper, InputMapperMessage::ViewportBounds(vec![bounds(0.), bounds(200.)]));
+        input_mapper
+    }
+
+    #[test]
+    fn shortcuts_send_their_actions() {
+        let mut input_mapper = InputMapperMessageHandler::default();
+        let undo = send(&mut input_mapper, key_down(Key::KeyZ, ModifierKeys::accel()));
+        assert!(matches!(undo.as_slice(), [Message::Portfolio(PortfolioMessage::Image { image_id: None, message: ImageMessage::Undo })]), "{undo:?}");
+        let redo = send(&mut input_mapper, key_down(Key::KeyZ, ModifierKeys::accel().with_shift()));
+        assert!(matches!(redo.as_slice(), [Message::Portfolio(PortfolioMessage::Image { message: ImageMessage::Redo, .. })]), "{redo:?}");
+        let abort = send(&mut input_mapper, key_down(Key::Escape, ModifierKeys::NONE));
+        assert!(matches!(abort.as_slice(), [Message::Tool(ToolMessage::Abort)]), "{abort:?}");
+        assert!(send(&mut input_mapper, key_down(Key::KeyZ, ModifierKeys::NONE)).is_empty());
+    }
+
+    #[test]
+    fn drag_reaches_the_tool_in_image_coordinates() {
+        let mut input_mapper = two_viewports();
+        let pointer = |x, y| DVec2::new(x, y);
+        let mut events = send(&mut input_mapper, InputMapperMessage::PointerDown { editor_position: pointer(250., 30.), button: MouseButton::Left, modifiers: ModifierKeys::NONE });
+        assert!(matches!(events.remove(0), Message::Portfolio(PortfolioMessage::SelectViewport { viewport: 1 })));
+        events.extend(send_with_active(&mut input_mapper, InputMapperMessage::PointerMove { editor_position: pointer(300., 70.), modifiers: ModifierKeys::NONE }, 1));
+        // Released past the edge of the viewport, the drag still goes to the one it started in
+        events.extend(send_with_active(&mut input_mapper, InputMapperMessage::PointerUp { editor_position: pointer(450., 90.), button: MouseButton::Left, modifiers: ModifierKeys::NONE }, 1));
+
+        // Zoomed in twice with the image's top left corner at the viewport's origin
+        let mut image = ImageMessageHandler::new(ImageFrame::from_pixel(100, 100, Luma([0])), AcquisitionMetadata::default(), FrameMetadata::default());
+        let data = ImageMessageData { image_id: ImageId(Uuid::nil()), viewport_size: DVec2::new(200., 200.) };
+        image.process_message(ImageMessage::ZoomCanvasAt { zoom_factor: 2., position: DVec2::ZERO }, &mut VecDeque::new(), data);
+
+        let event_map = SelectTool::default().event_to_message_map();
+        let tool_messages: Vec<_> = events
+            .iter()
+            .map(|event| match event {
+                Message::Tool(event) => event_map.tool_message(event, &image).unwrap(),
+                event => panic!("{event:?} isn't a tool event"),
+            })
+            .collect();
+        let position = |x, y| ImagePosition(IVec2::new(x, y));
+        assert_eq!(
+            tool_messages,
+            [
+                ToolMessage::Select(SelectToolMessage::DragStart { position: position(25, 15) }),
+                ToolMessage::Select(SelectToolMessage::PointerMove { position: position(50, 35) }),
+                ToolMessage::Select(SelectToolMessage::DragStop { position: position(125, 45) }),
+            ]
+        );
+    }
+
+    #[test]
+    fn hovering_another_viewport_doesnt_reach_the_tool() {
+        let mut input_mapper = two_viewports();
+        assert!(send(&mut input_mapper, InputMapperMessage::PointerMove { editor_position: DVec2::new(250., 30.), modifiers: ModifierKeys::NONE }).is_empty());
+        let events = send(&mut input_mapper, InputMapperMessage::PointerMove { editor_position: DVec2::new(50., 30.), modifiers: ModifierKeys::NONE });
+        assert!(matches!(events.as_slice(), [Message::Tool(ToolMessage::PointerMove { position })] if *position == DVec2::new(50., 30.)), "{events:?}");
+    }
+
+    #[test]
+    fn pointer_input_goes_to_the_viewport_under_the_cursor() {
+        let mut input_mapper = two_viewports();
+        // The tool only hears about the active viewport, with positions relative to it
+        let events = send_with_active(&mut input_mapper, InputMapperMessage::PointerMove { editor_position: DVec2::new(250., 30.), modifiers: ModifierKeys::NONE }, 1);
+        assert!(matches!(events.as_slice(), [Message::Tool(ToolMessage::PointerMove { position })] if *position == DVec2::new(50., 30.)), "{events:?}");
+        assert!(send_with_active(&mut input_mapper, InputMapperMessage::DoubleClick { editor_position: DVec2::new(50., 30.), button: MouseButton::Left, modifiers: ModifierKeys::NONE }, 1).is_empty());
+
+        // Zooming and panning work on any viewport, whether it is active or not
+        let events = send(&mut input_mapper, InputMapperMessage::WheelScroll { editor_position: DVec2::new(260., 40.), delta: ScrollDelta { x: 0, y: -100 }, modifiers: ModifierKeys::NONE });
+        assert!(matches!(events.as_slice(), [Message::Portfolio(PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::ZoomCanvasAt { position, .. } })] if *position == DVec2::new(60., 40.)), "{events:?}");
+        assert!(send(&mut input_mapper, InputMapperMessage::WheelScroll { editor_position: DVec2::new(500., 40.), delta: ScrollDelta { x: 0, y: -100 }, modifiers: ModifierKeys::NONE }).is_empty());
+
+        send(&mut input_mapper, InputMapperMessage::PointerDown { editor_position: DVec2::new(210., 10.), button: MouseButton::Middle, modifiers: ModifierKeys::NONE });
+        // Moved over the first viewport, the pan stays with the one it started in
+        let events = send(&mut input_mapper, InputMapperMessage::PointerMove { editor_position: DVec2::new(190., 15.), modifiers: ModifierKeys::NONE });
+        assert!(matches!(events.as_slice(), [Message::Portfolio(PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::PanCanvas { delta } })] if *delta == DVec2::new(-20., 5.)), "{events:?}");
+    }
+
+    #[test]
+    fn removing_viewports_ends_drags_in_them() {
+        let mut input_mapper = two_viewports();
+        send(&mut input_mapper, InputMapperMessage::PointerDown { editor_position: DVec2::new(250., 30.), button: MouseButton::Left, modifiers: ModifierKeys::NONE });
+        let bounds = ViewportBounds { top_left: DVec2::ZERO, bottom_right: DVec2::new(400., 200.) };
+        let events = send_with_active(&mut input_mapper, InputMapperMessage::ViewportBounds(vec![bounds]), 1);
+        assert!(matches!(events.as_slice(), [Message::Tool(ToolMessage::Abort), Message::Portfolio(PortfolioMessage::SetViewportCount { count: 1 })]), "{events:?}");
+        assert!(send(&mut input_mapper, InputMapperMessage::PointerUp { editor_position: DVec2::new(250., 30.), button: MouseButton::Left, modifiers: ModifierKeys::NONE }).is_empty());
+        // Growing the layout leaves the portfolio to show images in the new viewports as they are picked
+        assert!(send(&mut input_mapper, InputMapperMessage::ViewportBounds(vec![bounds, bounds])).is_empty());
+    }
+}
@@ -0,0 +1,137 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::utility_types::misc::{variant_from_name, InputAction, KeyBinding};
+use crate::messages::frontend::utility_types::FrontendKeyBinding;
+use crate::messages::prelude::*;
+
+const DEFAULT_KEYMAP: &str = include_str!("default_keymap.toml");
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeymapError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Parse(#[from] toml::de::Error),
+    #[error("{0:?} is not an action")]
+    UnknownAction(String),
+    #[error("{0}")]
+    InvalidShortcut(String),
+    #[error("{binding} is bound to both {first:?} and {second:?}")]
+    Conflict { binding: KeyBinding, first: InputAction, second: InputAction },
+}
+
+// The shortcuts of every action, resolved into a lookup from key binding to action
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    actions: BTreeMap<InputAction, Vec<KeyBinding>>,
+    bindings: HashMap<KeyBinding, InputAction>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let actions = parse_actions(DEFAULT_KEYMAP).expect("The default keymap is invalid");
+        Self::from_actions(actions).expect("The default keymap has conflicting bindings")
+    }
+}
+
+impl Keymap {
+    // The defaults with the actions listed in the file taking the file's shortcuts instead
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, KeymapError> {
+        Self::with_overrides(&std::fs::read_to_string(path)?)
+    }
+
+    pub fn with_overrides(source: &str) -> Result<Self, KeymapError> {
+        let mut actions = Self::default().actions;
+        actions.extend(parse_actions(source)?);
+        Self::from_actions(actions)
+    }
+
+    fn from_actions(actions: BTreeMap<InputAction, Vec<KeyBinding>>) -> Result<Self, KeymapError> {
+        let mut bindings = HashMap::new();
+        for (&action, action_bindings) in &actions {
+            for &binding in action_bindings {
+                match bindings.insert(binding, action) {
+                    Some(first) if first != action => return Err(KeymapError::Conflict { binding, first, second: action }),
+                    _ => {}
+                }
+            }
+        }
+        Ok(Self { actions, bindings })
+    }
+
+    pub fn action(&self, binding: KeyBinding) -> Option<InputAction> {
+        self.bindings.get(&binding).copied()
+    }
+
+    pub fn frontend_bindings(&self) -> Vec<FrontendKeyBinding> {
+        self.actions
+            .iter()
+            .map(|(&action, bindings)| FrontendKeyBinding {
+                action,
+                bindings: bindings.clone(),
+                labels: bindings.iter().map(ToString::to_string).collect(),
+            })
+            .collect()
+    }
+}
+
+fn parse_actions(source: &str) -> Result<BTreeMap<InputAction, Vec<KeyBinding>>, KeymapError> {
+    let table: BTreeMap<String, Vec<String>> = toml::from_str(source)?;
+    table
+        .into_iter()
+        .map(|(name, shortcuts)| {
+            let action = variant_from_name(&name).ok_or(KeymapError::UnknownAction(name))?;
+            let bindings = shortcuts.iter().map(|shortcut| shortcut.parse().map_err(KeymapError::InvalidShortcut)).collect::<Result<_, _>>()?;
+            Ok((action, bindings))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::input_mapper::utility_types::input_keyboard::{Key, ModifierKeys};
+
+    fn binding(shortcut: &str) -> KeyBinding {
+        shortcut.parse().unwrap()
+    }
+
+    #[test]
+    fn default_keymap_binds_every_action() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(binding("Accel+Z")), Some(InputAction::Undo));
+        assert_eq!(keymap.action(binding("Accel+Shift+Z")), Some(InputAction::Redo));
+        assert_eq!(keymap.action(binding("Accel+Y")), Some(InputAction::Redo));
+        assert_eq!(keymap.action(KeyBinding::new(Key::Escape, ModifierKeys::NONE)), Some(InputAction::AbortTool));
+        assert_eq!(keymap.action(binding("Z")), None);
+        assert_eq!(keymap.frontend_bindings().len(), 12);
+        assert!(keymap.frontend_bindings().iter().all(|binding| !binding.bindings.is_empty()));
+    }
+
+    #[test]
+    fn overrides_replace_the_listed_actions_only() {
+        let keymap = Keymap::with_overrides("Undo = [\"Alt+Backspace\"]\nRedo = []").unwrap();
+        assert_eq!(keymap.action(binding("Alt+⌫")), Some(InputAction::Undo));
+        assert_eq!(keymap.action(binding("Accel+Z")), None);
+        assert_eq!(keymap.action(binding("Accel+Y")), None);
+        assert_eq!(keymap.action(binding("R")), Some(InputAction::RectangleTool));
+        let redo = keymap.frontend_bindings().into_iter().find(|binding| binding.action == InputAction::Redo).unwrap();
+        assert!(redo.bindings.is_empty());
+    }
+
+    #[test]
+    fn conflicting_and_invalid_keymaps_are_rejected() {
+        // Undo takes R, which RectangleTool keeps from the defaults
+        match Keymap::with_overrides("Undo = [\"R\"]") {
+            Err(KeymapError::Conflict { binding: conflict, first, second }) => {
+                assert_eq!(conflict, binding("R"));
+                assert_eq!((first, second), (InputAction::Undo, InputAction::RectangleTool));
+            }
+            result => panic!("expected a conflict, got {result:?}"),
+        }
+        assert!(matches!(Keymap::with_overrides("Jump = [\"J\"]"), Err(KeymapError::UnknownAction(name)) if name == "Jump"));
+        assert!(matches!(Keymap::with_overrides("Undo = [\"Ctrl+Nope\"]"), Err(KeymapError::InvalidShortcut(_))));
+        assert!(matches!(Keymap::with_overrides("Undo = \"Ctrl+Z\""), Err(KeymapError::Parse(_))));
+    }
+}
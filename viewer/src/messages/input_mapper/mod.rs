@@ -1,4 +1,8 @@
 mod input_mapper_message;
 mod input_mapper_message_handler;
 
-pub mod utility_types;
\ No newline at end of file
+pub mod keymap;
+pub mod utility_types;
+
+pub use input_mapper_message::InputMapperMessage;
+pub use input_mapper_message_handler::InputMapperMessageHandler;
@@ -2,7 +2,7 @@ use core::fmt;
 
 use serde::{Serialize, Deserialize};
 
-#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize, specta::Type)]
 #[repr(u8)]
 pub enum Key {
 	// Writing system keys
@@ -163,6 +163,56 @@ pub enum Key {
 	NumKeys,
 }
 
+// Keys displayed with something other than their name, keymap files may use these labels too
+const KEY_LABELS: &[(Key, &str)] = &[
+	// Writing system keys
+	(Key::Backquote, "`"),
+	(Key::Backslash, "\\"),
+	(Key::BracketLeft, "["),
+	(Key::BracketRight, "]"),
+	(Key::Comma, ","),
+	(Key::Equal, "="),
+	(Key::Minus, "-"),
+	(Key::Period, "."),
+	(Key::Quote, "'"),
+	(Key::Semicolon, ";"),
+	(Key::Slash, "/"),
+
+	(Key::Backspace, "⌫"),
+
+	// Control pad keys
+	(Key::Delete, "Del"),
+	(Key::PageDown, "PgDn"),
+	(Key::PageUp, "PgUp"),
+
+	// Arrow pad keys
+	(Key::ArrowDown, "↓"),
+	(Key::ArrowLeft, "←"),
+	(Key::ArrowRight, "→"),
+	(Key::ArrowUp, "↑"),
+
+	// Numpad keys
+	(Key::NumpadAdd, "Numpad +"),
+	(Key::NumpadHash, "Numpad #"),
+	(Key::NumpadMultiply, "Numpad *"),
+	(Key::NumpadParenLeft, "Numpad ("),
+	(Key::NumpadParenRight, "Numpad )"),
+
+	// Function keys
+	(Key::Escape, "Esc"),
+	(Key::PrintScreen, "PrtScr"),
+
+	// Other keys that aren't part of the W3C spec
+	(Key::Command, "⌘"),
+	(Key::Accel, "Ctrl"),
+];
+
+impl Key {
+	pub fn from_label(label: &str) -> Option<Self> {
+		KEY_LABELS.iter().find(|(_, key_label)| *key_label == label).map(|(key, _)| *key)
+	}
+}
+
 impl fmt::Display for Key {
 	// TODO: Relevant key labels should be localized when we get around to implementing localization/internationalization
 	fn fmt(&self, f: &mut fmt::Formatter) -> std::fmt::Result {
@@ -178,51 +228,34 @@ impl fmt::Display for Key {
 			return write!(f, "{}", key_name.chars().skip(KEY_PREFIX.len()).collect::<String>());
 		}
 
-		let name = match self {
-			// Writing system keys
-			Self::Backquote => "`",
-			Self::Backslash => "\\",
-			Self::BracketLeft => "[",
-			Self::BracketRight => "]",
-			Self::Comma => ",",
-			Self::Equal => "=",
-			Self::Minus => "-",
-			Self::Period => ".",
-			Self::Quote => "'",
-			Self::Semicolon => ";",
-			Self::Slash => "/",
-
-			Self::Backspace => "⌫",
-
-			// Control pad keys
-			Self::Delete => "Del",
-			Self::PageDown => "PgDn",
-			Self::PageUp => "PgUp",
-
-			// Arrow pad keys
-			Self::ArrowDown => "↓",
-			Self::ArrowLeft => "←",
-			Self::ArrowRight => "→",
-			Self::ArrowUp => "↑",
-
-			// Numpad keys
-			Self::NumpadAdd => "Numpad +",
-			Self::NumpadHash => "Numpad #",
-			Self::NumpadMultiply => "Numpad *",
-			Self::NumpadParenLeft => "Numpad (",
-			Self::NumpadParenRight => "Numpad )",
-
-			// Function keys
-			Self::Escape => "Esc",
-			Self::PrintScreen => "PrtScr",
-
-			// Other keys that aren't part of the W3C spec
-			Self::Command => "⌘",
-			Self::Accel =>  "Ctrl",
-
-			_ => key_name.as_str(),
-		};
+		let name = KEY_LABELS.iter().find(|(key, _)| key == self).map_or(key_name.as_str(), |(_, label)| label);
 
 		write!(f, "{name}")
 	}
+}
+
+// The modifiers held while a key or mouse button was pressed, as reported by the frontend
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize, specta::Type)]
+pub struct ModifierKeys {
+	pub shift: bool,
+	pub control: bool,
+	pub alt: bool,
+	pub meta: bool,
+}
+
+impl ModifierKeys {
+	pub const NONE: Self = Self { shift: false, control: false, alt: false, meta: false };
+
+	// Ctrl on Windows/Linux, Cmd on Mac
+	pub fn accel() -> Self {
+		if cfg!(target_os = "macos") {
+			Self { meta: true, ..Self::NONE }
+		} else {
+			Self { control: true, ..Self::NONE }
+		}
+	}
+
+	pub fn with_shift(self) -> Self {
+		Self { shift: true, ..self }
+	}
 }
\ No newline at end of file
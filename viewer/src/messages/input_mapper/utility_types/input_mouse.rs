@@ -1,34 +1,42 @@
 use std::ops::Div;
 
 use bitflags::bitflags;
-use nalgebra::Vector2;
+use glam::DVec2;
 use serde::{Deserialize, Serialize};
 
 // Origin is top left
-pub type ViewportPosition = Vector2<f64>;
-pub type EditorPosition = Vector2<f64>;
+pub type ViewportPosition = DVec2;
+pub type EditorPosition = DVec2;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct ViewportBounds {
-    pub top_left: Vector2<f64>,
-    pub bottom_right: Vector2<f64>
+    pub top_left: DVec2,
+    pub bottom_right: DVec2
 }
 
 impl ViewportBounds {
-    pub fn size(&self) -> Vector2<f64> {
+    pub fn size(&self) -> DVec2 {
 		self.bottom_right - self.top_left
 	}
 
-    pub fn centre(&self) -> Vector2<f64> {
+    pub fn centre(&self) -> DVec2 {
         (self.bottom_right - self.top_left).div(2.0)
     }
 
+	pub fn editor_to_viewport(&self, position: EditorPosition) -> ViewportPosition {
+		position - self.top_left
+	}
+
+	pub fn viewport_to_editor(&self, position: ViewportPosition) -> EditorPosition {
+		position + self.top_left
+	}
+
 	pub fn in_bounds(&self, position: ViewportPosition) -> bool {
-		position.x >= 0. && position.y >= 0. && position.x <= self.bottom_right.x && position.y <= self.bottom_right.y
+		position.x >= 0. && position.y >= 0. && position.x <= self.size().x && position.y <= self.size().y
 	}
 }
 
-#[derive(Copy, Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Copy, Debug, Clone, Default, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct ScrollDelta {
     pub x: i32,
     pub y: i32
@@ -48,7 +56,7 @@ impl MouseState {
 
     pub fn from_position(x: f64, y: f64) -> Self {
 		Self {
-			position: Vector2::new(x, y),
+			position: DVec2::new(x, y),
 			mouse_keys: MouseKeys::default(),
 			scroll_delta: ScrollDelta::default(),
 		}
@@ -69,7 +77,7 @@ impl EditorMouseState {
 
 	pub fn from_editor_position(x: f64, y: f64) -> Self {
 		Self {
-			editor_position: Vector2::new(x, y),
+			editor_position: DVec2::new(x, y),
 			mouse_keys: MouseKeys::default(),
 			scroll_delta: ScrollDelta::default(),
 		}
@@ -77,7 +85,7 @@ impl EditorMouseState {
 
 	pub fn to_mouse_state(&self, active_viewport_bounds: &ViewportBounds) -> MouseState {
 		MouseState {
-			position: self.editor_position - active_viewport_bounds.top_left,
+			position: active_viewport_bounds.editor_to_viewport(self.editor_position),
 			mouse_keys: self.mouse_keys,
 			scroll_delta: self.scroll_delta,
 		}
@@ -93,8 +101,20 @@ bitflags! {
 		const MIDDLE = 0b0000_0100;
 	}
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
 pub enum MouseButton {
     Left,
     Right,
     Middle
-}
\ No newline at end of file
+}
+
+impl From<MouseButton> for MouseKeys {
+    fn from(button: MouseButton) -> Self {
+        match button {
+            MouseButton::Left => MouseKeys::LEFT,
+            MouseButton::Right => MouseKeys::RIGHT,
+            MouseButton::Middle => MouseKeys::MIDDLE,
+        }
+    }
+}
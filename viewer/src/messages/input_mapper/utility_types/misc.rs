@@ -0,0 +1,179 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::de::{value::StrDeserializer, DeserializeOwned, IntoDeserializer};
+use serde::{Deserialize, Serialize};
+
+use crate::messages::prelude::*;
+use crate::messages::tool::utility_types::ToolType;
+
+use super::input_keyboard::{Key, ModifierKeys};
+
+// A key together with the exact modifiers that have to be held for the binding to apply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, specta::Type)]
+pub struct KeyBinding {
+    pub key: Key,
+    pub modifiers: ModifierKeys,
+}
+
+impl KeyBinding {
+    pub fn new(key: Key, modifiers: ModifierKeys) -> Self {
+        Self { key, modifiers }
+    }
+}
+
+// Written the way keymap files spell shortcuts, e.g. Ctrl+Shift+Z
+impl fmt::Display for KeyBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ModifierKeys { shift, control, alt, meta } = self.modifiers;
+        let meta_label = if cfg!(target_os = "macos") { "Cmd" } else { "Meta" };
+        let modifiers = [(control, "Ctrl"), (alt, "Alt"), (shift, "Shift"), (meta, meta_label)];
+        for (_, label) in modifiers.into_iter().filter(|(held, _)| *held) {
+            write!(f, "{label}+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+// Modifiers come first and the key last, joined with +. Accel is Ctrl, or Cmd on Mac.
+impl FromStr for KeyBinding {
+    type Err = String;
+
+    // Modifiers are taken off the front one at a time, so keys whose label has a + in it such as "Numpad +" stay whole
+    fn from_str(shortcut: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = ModifierKeys::NONE;
+        let mut key = shortcut.trim();
+        while let Some((modifier, rest)) = key.split_once('+') {
+            if !apply_modifier(&mut modifiers, modifier.trim()) {
+                break;
+            }
+            key = rest.trim();
+        }
+        if key.is_empty() {
+            return Err(format!("{shortcut:?} has no key"));
+        }
+
+        match parse_key(key) {
+            Some(key) => Ok(Self::new(key, modifiers)),
+            // Most likely a misspelt modifier rather than a key with a + in its label
+            None => match key.split_once('+').filter(|(modifier, _)| !modifier.trim().is_empty()) {
+                Some((modifier, _)) => Err(format!("{:?} in {shortcut:?} is not a modifier", modifier.trim())),
+                None => Err(format!("{key:?} in {shortcut:?} is not a key")),
+            },
+        }
+    }
+}
+
+// Returns false if the name isn't a modifier
+fn apply_modifier(modifiers: &mut ModifierKeys, name: &str) -> bool {
+    match name.to_ascii_lowercase().as_str() {
+        "accel" => {
+            let accel = ModifierKeys::accel();
+            modifiers.control |= accel.control;
+            modifiers.meta |= accel.meta;
+        }
+        "ctrl" | "control" => modifiers.control = true,
+        "shift" => modifiers.shift = true,
+        "alt" | "option" => modifiers.alt = true,
+        "meta" | "cmd" | "command" => modifiers.meta = true,
+        _ => return false,
+    }
+    true
+}
+
+// Accepts the variant names as well as the labels keys are displayed with, so KeyZ, Z and z are all the same key and
+// so are ArrowUp and ↑
+fn parse_key(name: &str) -> Option<Key> {
+    if let Some(key) = Key::from_label(name) {
+        return Some(key);
+    }
+    let name = match name.as_bytes() {
+        [letter] if letter.is_ascii_alphabetic() => format!("Key{}", letter.to_ascii_uppercase() as char),
+        [digit] if digit.is_ascii_digit() => format!("Digit{}", *digit as char),
+        _ => name.to_string(),
+    };
+    variant_from_name(&name).filter(|key| *key != Key::NumKeys)
+}
+
+// Looks up a unit variant by the name serde gives it
+pub fn variant_from_name<T: DeserializeOwned>(name: &str) -> Option<T> {
+    let deserializer: StrDeserializer<serde::de::value::Error> = name.into_deserializer();
+    T::deserialize(deserializer).ok()
+}
+
+// What a key binding does, kept separate from the messages it sends so bindings can be listed and compared
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, specta::Type)]
+pub enum InputAction {
+    AbortTool,
+    Undo,
+    Redo,
+    SelectTool,
+    RectangleTool,
+    ZoomCanvasToFit,
+    ZoomCanvasTo100Percent,
+    ZoomCanvasTo200Percent,
+    RotateCanvasClockwise,
+    RotateCanvasCounterclockwise,
+    FlipCanvasHorizontal,
+    FlipCanvasVertical,
+}
+
+impl InputAction {
+    pub fn to_message(self) -> Message {
+        match self {
+            InputAction::AbortTool => ToolMessage::Abort.into(),
+            InputAction::Undo => ImageMessage::Undo.into(),
+            InputAction::Redo => ImageMessage::Redo.into(),
+            InputAction::SelectTool => ToolMessage::ActivateTool { tool_type: ToolType::Select }.into(),
+            InputAction::RectangleTool => ToolMessage::ActivateTool { tool_type: ToolType::Rectangle }.into(),
+            InputAction::ZoomCanvasToFit => ImageMessage::ZoomCanvasToFitAll.into(),
+            InputAction::ZoomCanvasTo100Percent => ImageMessage::ZoomCanvasTo100Perecent.into(),
+            InputAction::ZoomCanvasTo200Percent => ImageMessage::ZoomCanvasTo200Percent.into(),
+            InputAction::RotateCanvasClockwise => ImageMessage::RotateCanvas { quarter_turns: 1 }.into(),
+            InputAction::RotateCanvasCounterclockwise => ImageMessage::RotateCanvas { quarter_turns: -1 }.into(),
+            InputAction::FlipCanvasHorizontal => ImageMessage::FlipCanvasHorizontal.into(),
+            InputAction::FlipCanvasVertical => ImageMessage::FlipCanvasVertical.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn binding(shortcut: &str) -> Result<KeyBinding, String> {
+        shortcut.parse()
+    }
+
+    #[test]
+    fn shortcuts_parse_names_and_labels() {
+        let ctrl = ModifierKeys { control: true, ..ModifierKeys::NONE };
+        assert_eq!(binding("Ctrl+Shift+Z"), Ok(KeyBinding::new(Key::KeyZ, ctrl.with_shift())));
+        assert_eq!(binding("ctrl + z"), Ok(KeyBinding::new(Key::KeyZ, ctrl)));
+        assert_eq!(binding("Accel+1"), Ok(KeyBinding::new(Key::Digit1, ModifierKeys::accel())));
+        assert_eq!(binding("Esc"), Ok(KeyBinding::new(Key::Escape, ModifierKeys::NONE)));
+        assert_eq!(binding("`"), Ok(KeyBinding::new(Key::Backquote, ModifierKeys::NONE)));
+        assert_eq!(binding("Alt+↑"), Ok(KeyBinding::new(Key::ArrowUp, ModifierKeys { alt: true, ..ModifierKeys::NONE })));
+        assert_eq!(binding("Numpad +"), Ok(KeyBinding::new(Key::NumpadAdd, ModifierKeys::NONE)));
+        assert_eq!(binding("Ctrl+Numpad +"), Ok(KeyBinding::new(Key::NumpadAdd, ctrl)));
+    }
+
+    #[test]
+    fn every_label_parses_back_to_its_binding() {
+        let keys = [Key::KeyA, Key::Digit7, Key::Backquote, Key::Backslash, Key::ArrowLeft, Key::NumpadAdd, Key::NumpadParenRight, Key::Escape, Key::PageDown, Key::F12];
+        for key in keys {
+            for modifiers in [ModifierKeys::NONE, ModifierKeys { control: true, alt: true, ..ModifierKeys::NONE }.with_shift()] {
+                let binding = KeyBinding::new(key, modifiers);
+                assert_eq!(binding.to_string().parse(), Ok(binding), "{binding}");
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_shortcuts_are_explained() {
+        assert_eq!(binding("Ctrl+"), Err(r#""Ctrl+" has no key"#.into()));
+        assert_eq!(binding("Hyper+Z"), Err(r#""Hyper" in "Hyper+Z" is not a modifier"#.into()));
+        assert_eq!(binding("Ctrl+Nope"), Err(r#""Nope" in "Ctrl+Nope" is not a key"#.into()));
+        assert_eq!(binding("NumKeys"), Err(r#""NumKeys" in "NumKeys" is not a key"#.into()));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod input_keyboard;
-pub mod input_mouse;
\ No newline at end of file
+pub mod input_mouse;
+pub mod misc;
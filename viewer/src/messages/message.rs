@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 
+use crate::messages::detector::utility_types::CaptureEvent;
+use crate::messages::portfolio::image::utility_types::misc::RawFrame;
 use crate::messages::prelude::*;
 
 #[derive(Debug, Serialize, Deserialize, specta::Type)]
@@ -7,11 +9,75 @@ pub enum Message {
     NoOp,
     Init,
 
-    // Debug(DebugMessage),
-    // Detector(DetectorMessage),
+    Debug(DebugMessage),
+    Detector(DetectorMessage),
     Dialog(DialogMessage),
     Frontend(FrontendMessage),
+    InputMapper(InputMapperMessage),
+    Portfolio(PortfolioMessage),
     Tool(ToolMessage)
 }
 
-pub struct MessageDiscriminant(pub u8);
\ No newline at end of file
+impl Message {
+    pub fn to_discriminant(&self) -> MessageDiscriminant {
+        MessageDiscriminant::of(self)
+    }
+
+    // The frames the message carries, recordings keep their pixel data out of the message
+    pub fn frames_mut(&mut self) -> Vec<&mut RawFrame> {
+        match self {
+            Message::Detector(DetectorMessage::CaptureEvent { event: CaptureEvent::Frame(frame), .. }) | Message::Portfolio(PortfolioMessage::LiveFrame { frame }) => vec![frame],
+            Message::Portfolio(PortfolioMessage::NewImageFromAcquisition { frames, .. }) => frames.iter_mut().collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl From<DebugMessage> for Message {
+    fn from(message: DebugMessage) -> Self {
+        Message::Debug(message)
+    }
+}
+
+impl From<DetectorMessage> for Message {
+    fn from(message: DetectorMessage) -> Self {
+        Message::Detector(message)
+    }
+}
+
+impl From<DialogMessage> for Message {
+    fn from(message: DialogMessage) -> Self {
+        Message::Dialog(message)
+    }
+}
+
+impl From<FrontendMessage> for Message {
+    fn from(message: FrontendMessage) -> Self {
+        Message::Frontend(message)
+    }
+}
+
+impl From<InputMapperMessage> for Message {
+    fn from(message: InputMapperMessage) -> Self {
+        Message::InputMapper(message)
+    }
+}
+
+impl From<PortfolioMessage> for Message {
+    fn from(message: PortfolioMessage) -> Self {
+        Message::Portfolio(message)
+    }
+}
+
+impl From<ToolMessage> for Message {
+    fn from(message: ToolMessage) -> Self {
+        Message::Tool(message)
+    }
+}
+
+// Image messages without an explicit target go to the active image
+impl From<ImageMessage> for Message {
+    fn from(message: ImageMessage) -> Self {
+        Message::Portfolio(PortfolioMessage::Image { image_id: None, message })
+    }
+}
\ No newline at end of file
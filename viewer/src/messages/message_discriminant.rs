@@ -0,0 +1,177 @@
+use std::fmt;
+
+use serde::ser::{self, Impossible, Serialize};
+
+// The variant names along a nested message, e.g. Portfolio::Image::SetWindow
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct MessageDiscriminant(pub Vec<&'static str>);
+
+impl MessageDiscriminant {
+    // Reads the names off the message's Serialize impl, following child messages through newtype variants and
+    // `message` fields while skipping every other field, so large payloads such as frames are never visited
+    pub fn of(message: &impl Serialize) -> Self {
+        let mut names = Vec::new();
+        let _ = message.serialize(NameCollector { names: &mut names });
+        Self(names)
+    }
+
+    pub fn starts_with(&self, prefix: &[&str]) -> bool {
+        self.0.len() >= prefix.len() && self.0.iter().zip(prefix).all(|(name, prefix)| name == prefix)
+    }
+}
+
+impl fmt::Display for MessageDiscriminant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join("::"))
+    }
+}
+
+#[derive(Debug)]
+struct Unsupported;
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a message")
+    }
+}
+
+impl std::error::Error for Unsupported {}
+
+impl ser::Error for Unsupported {
+    fn custom<T: fmt::Display>(_msg: T) -> Self {
+        Unsupported
+    }
+}
+
+struct NameCollector<'a> {
+    names: &'a mut Vec<&'static str>,
+}
+
+impl<'a> ser::Serializer for NameCollector<'a> {
+    type Ok = ();
+    type Error = Unsupported;
+    type SerializeSeq = Impossible<(), Unsupported>;
+    type SerializeTuple = Impossible<(), Unsupported>;
+    type SerializeTupleStruct = Impossible<(), Unsupported>;
+    type SerializeTupleVariant = Impossible<(), Unsupported>;
+    type SerializeMap = Impossible<(), Unsupported>;
+    type SerializeStruct = Impossible<(), Unsupported>;
+    type SerializeStructVariant = ChildMessageField<'a>;
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<(), Unsupported> {
+        self.names.push(variant);
+        Ok(())
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<(), Unsupported> {
+        self.names.push(variant);
+        value.serialize(self)
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<Self::SerializeTupleVariant, Unsupported> {
+        self.names.push(variant);
+        Err(Unsupported)
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<Self::SerializeStructVariant, Unsupported> {
+        self.names.push(variant);
+        Ok(ChildMessageField { names: self.names })
+    }
+
+    // Anything that isn't an enum ends the name
+    fn serialize_bool(self, _v: bool) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_i8(self, _v: i8) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_i16(self, _v: i16) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_i32(self, _v: i32) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_i64(self, _v: i64) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_u8(self, _v: u8) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_u16(self, _v: u16) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_u32(self, _v: u32) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_u64(self, _v: u64) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_f32(self, _v: f32) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_f64(self, _v: f64) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_char(self, _v: char) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_str(self, _v: &str) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_none(self) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_unit(self) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, _name: &'static str, _value: &T) -> Result<(), Unsupported> { Err(Unsupported) }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Unsupported> { Err(Unsupported) }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Unsupported> { Err(Unsupported) }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Unsupported> { Err(Unsupported) }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Unsupported> { Err(Unsupported) }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Unsupported> { Err(Unsupported) }
+}
+
+// Child messages held in struct variants, such as PortfolioMessage::Image, are always in a field named `message`
+struct ChildMessageField<'a> {
+    names: &'a mut Vec<&'static str>,
+}
+
+impl<'a> ser::SerializeStructVariant for ChildMessageField<'a> {
+    type Ok = ();
+    type Error = Unsupported;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T) -> Result<(), Unsupported> {
+        if key == "message" {
+            value.serialize(NameCollector { names: self.names })?;
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<(), Unsupported> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Serialize, Serializer};
+
+    use super::*;
+    use crate::messages::prelude::*;
+
+    // Stands in for a frame, the collector must skip it rather than serialize it
+    struct Payload;
+
+    impl Serialize for Payload {
+        fn serialize<S: Serializer>(&self, _serializer: S) -> Result<S::Ok, S::Error> {
+            panic!("payload was visited")
+        }
+    }
+
+    #[derive(Serialize)]
+    enum Parent {
+        Unit,
+        Newtype(Child),
+        Nested { payload: Payload, message: Child },
+        Tuple(u32, Payload),
+    }
+
+    #[derive(Serialize)]
+    enum Child {
+        Leaf,
+        Frame { payload: Payload },
+    }
+
+    #[test]
+    fn names_follow_unit_newtype_and_nested_variants() {
+        assert_eq!(MessageDiscriminant::of(&Parent::Unit).0, ["Unit"]);
+        assert_eq!(MessageDiscriminant::of(&Parent::Newtype(Child::Leaf)).0, ["Newtype", "Leaf"]);
+        assert_eq!(MessageDiscriminant::of(&Parent::Nested { payload: Payload, message: Child::Frame { payload: Payload } }).0, ["Nested", "Frame"]);
+        assert_eq!(MessageDiscriminant::of(&Parent::Tuple(1, Payload)).0, ["Tuple"]);
+    }
+
+    #[test]
+    fn message_discriminant_names_the_whole_path() {
+        let message: Message = PortfolioMessage::Image { image_id: None, message: ImageMessage::SetWindow { min: 1, max: 2 } }.into();
+        let discriminant = message.to_discriminant();
+        assert_eq!(discriminant.to_string(), "Portfolio::Image::SetWindow");
+        assert!(discriminant.starts_with(&["Portfolio", "Image"]));
+        assert!(!discriminant.starts_with(&["Portfolio", "LiveFrame"]));
+        assert!(!discriminant.starts_with(&["Portfolio", "Image", "SetWindow", "Extra"]));
+        assert_eq!(Message::Init.to_discriminant().to_string(), "Init");
+    }
+}
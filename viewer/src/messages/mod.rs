@@ -1,9 +1,10 @@
-//pub mod debug;
+pub mod debug;
 pub mod detector;
 pub mod dialog;
 pub mod frontend;
 pub mod input_mapper;
 pub mod message;
+pub mod message_discriminant;
 pub mod portfolio;
 pub mod prelude;
 pub mod tool;
\ No newline at end of file
@@ -1,5 +1,13 @@
-use super::utility_types::{annotations::{Annotation, AnnotationEnum}, misc::{AnnotationId, ImagePosition}};
+use std::path::PathBuf;
 
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+
+use crate::messages::input_mapper::utility_types::input_mouse::ViewportPosition;
+
+use super::utility_types::{annotations::AnnotationEnum, histogram::AutoStretch, misc::{AdjustmentLevels, AnnotationId, ImagePosition}, navigation::ViewportTransform};
+
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum ImageMessage {
     AddAnnotation {
         annotation: AnnotationEnum
@@ -7,13 +15,113 @@ pub enum ImageMessage {
     AdjustBrightness {
         new_brightness: f32
     },
+    AdjustContrast {
+        new_contrast: f32
+    },
+    AutoWindow {
+        low_percentile: f64,
+        high_percentile: f64
+    },
+    AutoStretch {
+        preset: AutoStretch
+    },
+    CommitTransaction,
+    ComputeLineProfile {
+        annotation_id: AnnotationId,
+        width: u32
+    },
+    ComputeHistogram {
+        annotation_id: Option<AnnotationId>,
+        bin_count: u32
+    },
+    ExportLineProfile {
+        annotation_id: AnnotationId,
+        width: u32,
+        path: PathBuf
+    },
+    FlipCanvasHorizontal,
+    FlipCanvasVertical,
+    // Takes on the window of a synchronised viewport's image, outside the undo history so it only follows the other image
+    FollowWindow {
+        min: u32,
+        max: u32
+    },
+    MeasureMtf {
+        annotation_id: AnnotationId
+    },
+    MoveAnnotation {
+        annotation_id: AnnotationId,
+        position: ImagePosition
+    },
+    // Moves the image by a distance in viewport pixels
+    PanCanvas {
+        delta: DVec2
+    },
+    // Pins the annotation to a single frame, None shows it on every frame
+    PinAnnotation {
+        annotation_id: AnnotationId,
+        frame: Option<u32>
+    },
+    PlaybackTick {
+        timestamp_ms: f64
+    },
+    Redo,
     RemoveAnnotation {
         annotation_id: AnnotationId
     },
+    // Clockwise for positive turns
+    RotateCanvas {
+        quarter_turns: i32
+    },
+    SeekFrame {
+        frame: u32
+    },
+    SetAdjustmentLevels {
+        adjustment_levels: AdjustmentLevels
+    },
+    SetGamma {
+        gamma: f32
+    },
+    SetInvert {
+        invert: bool
+    },
+    SetLooping {
+        looping: bool
+    },
+    SetPlaybackFps {
+        fps: f64
+    },
+    SetPlaying {
+        playing: bool
+    },
+    SetWindow {
+        min: u32,
+        max: u32
+    },
     SetValue {
+        positions: Vec<ImagePosition>,
+        value: u16
+    },
+    SetViewportTransform {
+        transform: ViewportTransform
+    },
+    StartTransaction,
+    StepFrame {
+        delta: i32
+    },
+    SubtractValue {
+        value: u16
+    },
+    UpdateAnnotation {
+        annotation_id: AnnotationId,
+        annotation: AnnotationEnum
     },
-    SubtractValue {},
     Undo,
+    // Multiplies the zoom, keeping the image point under the position in place
+    ZoomCanvasAt {
+        zoom_factor: f64,
+        position: ViewportPosition
+    },
     ZoomCanvasTo100Perecent,
     ZoomCanvasTo200Percent,
     ZoomCanvasToFitAll,
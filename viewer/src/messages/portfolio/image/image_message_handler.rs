@@ -1,76 +1,891 @@
-use image::{Luma, ImageBuffer};
+use glam::{DVec2, IVec2};
+use image::GrayImage;
 
+use crate::analysis::mtf::{self, MtfResult};
+use crate::analysis::Region;
+use crate::utility_functions::generate_uuid;
+use crate::consts::MAX_UNDO_HISTORY;
 use crate::messages::prelude::*;
 
-use super::utility_types::{misc::{Command, AnnotationId, AdjustmentLevels}, annotations::{Annotation, Shape}};
+use crate::io::{csv, ImageStack};
+use crate::messages::input_mapper::utility_types::input_mouse::ViewportPosition;
+
+use super::utility_types::{misc::{Command, AnnotationId, AdjustmentLevels, ImageFrame, ImagePosition, Percentage}, annotations::{Annotation, Shape}, metadata::{AcquisitionMetadata, FrameMetadata}};
+use super::utility_types::display::DisplayLut;
+use super::utility_types::navigation::ViewportTransform;
+use super::utility_types::playback::Playback;
+use super::utility_types::histogram::{Histogram, HistogramData};
+use super::utility_types::profile::LineProfile;
+use super::utility_types::statistics::RoiStatistics;
+use super::utility_types::command::{AddAnnotationCommand, CommandGroup, MoveAnnotationCommand, PinAnnotationCommand, PixelEdit, PixelEditCommand, RemoveAnnotationCommand, ReplaceAnnotationCommand, SetAdjustmentLevelsCommand};
+
+// What an image needs to know about where it is shown
+pub struct ImageMessageData {
+    pub image_id: ImageId,
+    pub viewport_size: DVec2,
+}
+
+// An annotation along with the state kept for it, so removing it and putting it back restores all of it
+pub struct StoredAnnotation {
+    pub annotation: Box<dyn Annotation>,
+    // Its place in the drawing order, None puts it on top
+    pub index: Option<usize>,
+    // The frame it is pinned to
+    pub frame: Option<usize>,
+    pub line_profile_width: Option<u32>,
+}
+
+impl From<Box<dyn Annotation>> for StoredAnnotation {
+    fn from(annotation: Box<dyn Annotation>) -> Self {
+        Self { annotation, index: None, frame: None, line_profile_width: None }
+    }
+}
 
 pub struct ImageMessageHandler {
-    image_buffer: ImageBuffer<Luma<u16>, Vec<u16>>,
+    name: String,
+    frames: Vec<ImageFrame>,
+    acquisition_metadata: AcquisitionMetadata,
+    frame_metadata: Vec<FrameMetadata>,
+    current_frame: usize,
+    playback: Playback,
     annotations: HashMap<AnnotationId, Box<dyn Annotation>>,
     annotation_ids: Vec<AnnotationId>,
+    // Annotations pinned to a single frame, the rest are shown on every frame
+    annotation_frames: HashMap<AnnotationId, usize>,
     adjustment_levels: AdjustmentLevels,
+    display_lut: DisplayLut,
+    viewport_transform: ViewportTransform,
+    // Line profiles the frontend is plotting, with the width they were requested at, kept up to date as the line moves
+    line_profile_widths: HashMap<AnnotationId, u32>,
+    // The latest MTF measured on each annotation, for DQE calculations
+    mtf_results: HashMap<AnnotationId, MtfResult>,
     image_redo_history: Vec<Box<dyn Command>>,
-    image_undo_history: Vec<Box<dyn Command>>,
+    image_undo_history: VecDeque<Box<dyn Command>>,
+    // Commands issued between StartTransaction and CommitTransaction, they enter the history as a single entry
+    transaction: Option<CommandGroup>,
 }
 
-impl MessageHandler<ImageMessage, ()> for ImageMessageHandler {
-    fn process_message(&mut self, message: ImageMessage, responses: &mut VecDeque<Message>, data: ()) {
+impl MessageHandler<ImageMessage, ImageMessageData> for ImageMessageHandler {
+    fn process_message(&mut self, message: ImageMessage, responses: &mut VecDeque<Message>, data: ImageMessageData) {
+        let ImageMessageData { image_id, viewport_size } = data;
+        let previous_levels = self.adjustment_levels.clone();
+        let previous_transform = self.viewport_transform;
+
         match message {
             ImageMessage::AddAnnotation { annotation } => {
-                todo!();
+                let annotation_id = AnnotationId(generate_uuid());
+                self.execute_command(Box::new(AddAnnotationCommand { annotation_id, annotation: Some(Box::<dyn Annotation>::from(annotation).into()) }));
+                self.send_annotation_updates(image_id, annotation_id, responses);
             },
+            ImageMessage::AdjustBrightness { new_brightness } => {
+                let Ok(brightness) = Percentage::try_from(new_brightness.round() as u32) else { return };
+                self.adjust_levels(AdjustmentLevels { brightness, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::AdjustContrast { new_contrast } => {
+                let Ok(contrast) = Percentage::try_from(new_contrast.round() as u32) else { return };
+                self.adjust_levels(AdjustmentLevels { contrast, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::AutoStretch { preset } => {
+                let Some((min, max)) = Histogram::from_image(self.image_buffer()).auto_stretch(preset) else { return };
+                self.adjust_levels(AdjustmentLevels { min, max, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::AutoWindow { low_percentile, high_percentile } => {
+                let Some((min, max)) = Histogram::from_image(self.image_buffer()).percentile_window(low_percentile, high_percentile) else { return };
+                self.adjust_levels(AdjustmentLevels { min, max, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::CommitTransaction => self.commit_transaction(),
+            ImageMessage::ComputeHistogram { annotation_id, bin_count } => {
+                let Some(histogram) = self.histogram(annotation_id, bin_count) else { return };
+                responses.add(FrontendMessage::UpdateHistogram { image_id, annotation_id, histogram });
+            }
+            ImageMessage::ComputeLineProfile { annotation_id, width } => {
+                if self.get_annotation(annotation_id).and_then(Annotation::as_line).is_none() {
+                    return;
+                }
+                self.line_profile_widths.insert(annotation_id, width);
+                self.send_annotation_updates(image_id, annotation_id, responses);
+            }
+            ImageMessage::ExportLineProfile { annotation_id, width, path } => {
+                let Some(profile) = self.line_profile(annotation_id, width) else { return };
+                if let Err(err) = csv::save_line_profile(&path, &profile) {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to export {}: {err}", path.display()) });
+                }
+            }
+            ImageMessage::FlipCanvasHorizontal => self.viewport_transform.flip(true),
+            ImageMessage::FlipCanvasVertical => self.viewport_transform.flip(false),
+            ImageMessage::FollowWindow { min, max } => {
+                let (min, max) = (min.min(max), max.max(min));
+                self.set_adjustment_levels(AdjustmentLevels { min, max, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::MeasureMtf { annotation_id } => {
+                let Some(region) = self.annotation_region(annotation_id) else { return };
+                match mtf::slanted_edge_mtf(&region, self.acquisition_metadata.pixel_pitch_mm) {
+                    Ok(result) => {
+                        responses.add(FrontendMessage::UpdateMtf { image_id, annotation_id, result: result.clone() });
+                        self.mtf_results.insert(annotation_id, result);
+                    }
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("MTF measurement failed: {err}") }),
+                }
+            }
+            ImageMessage::MoveAnnotation { annotation_id, position } => {
+                self.execute_command(Box::new(MoveAnnotationCommand { annotation_id, from: None, to: position }));
+                self.send_annotation_updates(image_id, annotation_id, responses);
+            }
+            ImageMessage::PanCanvas { delta } => self.viewport_transform.pan += delta,
+            ImageMessage::PinAnnotation { annotation_id, frame } => {
+                let frame = frame.map(|frame| (frame as usize).min(self.frames.len() - 1));
+                self.execute_command(Box::new(PinAnnotationCommand { annotation_id, frame, previous: None }));
+                self.send_annotation_updates(image_id, annotation_id, responses);
+            }
+            ImageMessage::PlaybackTick { timestamp_ms } => {
+                let frames = self.playback.tick(timestamp_ms);
+                if frames > 0 {
+                    self.step_frame(frames as i64, self.playback.looping, image_id, responses);
+                }
+            }
+            ImageMessage::Redo => {
+                let previous_annotation_ids = self.annotation_ids.clone();
+                self.redo();
+                // The entry may have been a pixel edit
+                self.send_display_image(image_id, responses);
+                self.send_removed_annotations(&previous_annotation_ids, image_id, responses);
+                self.send_all_annotation_updates(image_id, responses);
+            }
             ImageMessage::RemoveAnnotation { annotation_id } => {
-                self.annotations.remove(&annotation_id);
+                let previous_annotation_ids = self.annotation_ids.clone();
+                self.execute_command(Box::new(RemoveAnnotationCommand { annotation_id, removed: None }));
+                self.send_removed_annotations(&previous_annotation_ids, image_id, responses);
+            }
+            ImageMessage::RotateCanvas { quarter_turns } => self.viewport_transform.rotate(quarter_turns),
+            ImageMessage::SeekFrame { frame } => self.set_current_frame(frame as usize, image_id, responses),
+            ImageMessage::SetAdjustmentLevels { adjustment_levels } => self.adjust_levels(adjustment_levels),
+            ImageMessage::SetGamma { gamma } => {
+                if !gamma.is_finite() || gamma <= 0. {
+                    return;
+                }
+                self.adjust_levels(AdjustmentLevels { gamma, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::SetInvert { invert } => {
+                self.adjust_levels(AdjustmentLevels { invert, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::SetLooping { looping } => {
+                self.playback.looping = looping;
+            }
+            ImageMessage::SetPlaybackFps { fps } => {
+                if fps.is_finite() && fps > 0. {
+                    self.playback.fps = fps;
+                }
+            }
+            ImageMessage::SetPlaying { playing } => {
+                // Playing from the last frame without looping starts over
+                if playing && !self.playback.looping && self.current_frame == self.frames.len() - 1 {
+                    self.set_current_frame(0, image_id, responses);
+                }
+                self.playback.set_playing(playing && self.frames.len() > 1);
+                self.send_frame_info(image_id, responses);
             }
-            _ => {}
+            // Sent while dragging the histogram markers, wrapped in a transaction by the frontend
+            ImageMessage::SetWindow { min, max } => {
+                let (min, max) = (min.min(max), max.max(min));
+                self.adjust_levels(AdjustmentLevels { min, max, ..self.adjustment_levels.clone() });
+            }
+            ImageMessage::SetValue { positions, value } => {
+                self.execute_command(Box::new(PixelEditCommand { frame: self.current_frame, edit: PixelEdit::Set { positions, value }, previous: Vec::new() }));
+                self.send_display_image(image_id, responses);
+                self.send_all_annotation_updates(image_id, responses);
+            }
+            ImageMessage::SetViewportTransform { transform } => self.viewport_transform = transform,
+            ImageMessage::StartTransaction => {
+                self.commit_transaction();
+                self.transaction = Some(CommandGroup::default());
+            }
+            ImageMessage::StepFrame { delta } => self.step_frame(delta as i64, self.playback.looping, image_id, responses),
+            ImageMessage::SubtractValue { value } => {
+                self.execute_command(Box::new(PixelEditCommand { frame: self.current_frame, edit: PixelEdit::Subtract { value }, previous: Vec::new() }));
+                self.send_display_image(image_id, responses);
+                self.send_all_annotation_updates(image_id, responses);
+            }
+            ImageMessage::Undo => {
+                let previous_annotation_ids = self.annotation_ids.clone();
+                self.undo();
+                // The entry may have been a pixel edit
+                self.send_display_image(image_id, responses);
+                self.send_removed_annotations(&previous_annotation_ids, image_id, responses);
+                self.send_all_annotation_updates(image_id, responses);
+            }
+            ImageMessage::UpdateAnnotation { annotation_id, annotation } => {
+                self.execute_command(Box::new(ReplaceAnnotationCommand { annotation_id, annotation: Some(annotation.into()) }));
+                self.send_annotation_updates(image_id, annotation_id, responses);
+            }
+            ImageMessage::ZoomCanvasAt { zoom_factor, position } => {
+                if zoom_factor.is_finite() && zoom_factor > 0. {
+                    self.viewport_transform.zoom_around(self.viewport_transform.zoom * zoom_factor, position);
+                }
+            }
+            ImageMessage::ZoomCanvasTo100Perecent => self.viewport_transform.zoom_around(1., viewport_size / 2.),
+            ImageMessage::ZoomCanvasTo200Percent => self.viewport_transform.zoom_around(2., viewport_size / 2.),
+            ImageMessage::ZoomCanvasToFitAll => self.viewport_transform.fit(self.image_size(), viewport_size),
+        }
+
+        if self.adjustment_levels != previous_levels {
+            responses.add(FrontendMessage::UpdateAdjustmentLevels { image_id, adjustment_levels: self.adjustment_levels.clone() });
+            self.send_display_image(image_id, responses);
+        }
+        if self.viewport_transform != previous_transform {
+            self.send_viewport_transform(image_id, responses);
         }
     }
 }
 
 impl ImageMessageHandler {
+    pub fn new(image_buffer: ImageFrame, acquisition_metadata: AcquisitionMetadata, frame_metadata: FrameMetadata) -> Self {
+        let image_size = DVec2::new(image_buffer.width() as f64, image_buffer.height() as f64);
+        Self {
+            name: String::new(),
+            frames: vec![image_buffer],
+            acquisition_metadata,
+            frame_metadata: vec![frame_metadata],
+            current_frame: 0,
+            playback: Playback::default(),
+            annotations: HashMap::new(),
+            annotation_ids: Vec::new(),
+            annotation_frames: HashMap::new(),
+            adjustment_levels: AdjustmentLevels::default(),
+            display_lut: DisplayLut::default(),
+            viewport_transform: ViewportTransform::new(image_size),
+            line_profile_widths: HashMap::new(),
+            mtf_results: HashMap::new(),
+            image_redo_history: Vec::new(),
+            image_undo_history: VecDeque::new(),
+            transaction: None,
+        }
+    }
+
+    // Returns None for an empty stack, frames without metadata get the default
+    pub fn from_image_stack(image_stack: ImageStack) -> Option<Self> {
+        let ImageStack { frames, acquisition, mut frame_metadata } = image_stack;
+        let mut frames = frames.into_iter();
+        let mut image = Self::new(frames.next()?, acquisition, FrameMetadata::default());
+        image.frames.extend(frames);
+        frame_metadata.resize(image.frames.len(), FrameMetadata::default());
+        image.frame_metadata = frame_metadata;
+        Some(image)
+    }
+
+    // Copies the frames, annotations and display settings but starts with an empty history
+    pub fn duplicate(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            frames: self.frames.clone(),
+            acquisition_metadata: self.acquisition_metadata.clone(),
+            frame_metadata: self.frame_metadata.clone(),
+            current_frame: self.current_frame,
+            playback: Playback::default(),
+            annotations: self.annotations.iter().map(|(annotation_id, annotation)| (*annotation_id, annotation.boxed_clone())).collect(),
+            annotation_ids: self.annotation_ids.clone(),
+            annotation_frames: self.annotation_frames.clone(),
+            adjustment_levels: self.adjustment_levels.clone(),
+            display_lut: self.display_lut.clone(),
+            viewport_transform: self.viewport_transform,
+            line_profile_widths: HashMap::new(),
+            mtf_results: HashMap::new(),
+            image_redo_history: Vec::new(),
+            image_undo_history: VecDeque::new(),
+            transaction: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn set_name(&mut self, name: impl Into<String>) {
+        self.name = name.into();
+    }
+
+    pub fn to_image_stack(&self) -> ImageStack {
+        ImageStack {
+            frames: self.frames.clone(),
+            acquisition: self.acquisition_metadata.clone(),
+            frame_metadata: self.frame_metadata.clone(),
+        }
+    }
+
+    // The frame currently shown
+    pub fn image_buffer(&self) -> &ImageFrame {
+        &self.frames[self.current_frame]
+    }
+
+    pub fn image_size(&self) -> DVec2 {
+        let (width, height) = self.image_buffer().dimensions();
+        DVec2::new(width as f64, height as f64)
+    }
+
+    pub fn viewport_transform(&self) -> &ViewportTransform {
+        &self.viewport_transform
+    }
+
+    // The pixel under a viewport position, which may lie outside the image
+    pub fn viewport_to_image(&self, position: ViewportPosition) -> ImagePosition {
+        let position = self.viewport_transform.viewport_to_image(self.image_size()).transform_point2(position);
+        ImagePosition(position.floor().as_ivec2())
+    }
+
+    // The viewport position of the pixel's centre
+    pub fn image_to_viewport(&self, position: ImagePosition) -> ViewportPosition {
+        self.viewport_transform.image_to_viewport(self.image_size()).transform_point2(position.0.as_dvec2() + 0.5)
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn current_frame(&self) -> usize {
+        self.current_frame
+    }
+
+    pub fn frames(&self) -> &[ImageFrame] {
+        &self.frames
+    }
+
+    pub fn frame_mut(&mut self, index: usize) -> Option<&mut ImageFrame> {
+        self.frames.get_mut(index)
+    }
+
+    pub fn adjustment_levels(&self) -> &AdjustmentLevels {
+        &self.adjustment_levels
+    }
+
+    pub fn acquisition_metadata(&self) -> &AcquisitionMetadata {
+        &self.acquisition_metadata
+    }
+
+    pub fn set_acquisition_metadata(&mut self, acquisition_metadata: AcquisitionMetadata) {
+        self.acquisition_metadata = acquisition_metadata;
+    }
+
+    pub fn insert_annotation(&mut self, annotation_id: AnnotationId, stored: StoredAnnotation) {
+        let StoredAnnotation { annotation, index, frame, line_profile_width } = stored;
+        let index = index.unwrap_or(self.annotation_ids.len()).min(self.annotation_ids.len());
+        self.annotation_ids.insert(index, annotation_id);
+        self.annotations.insert(annotation_id, annotation);
+        if let Some(frame) = frame {
+            self.annotation_frames.insert(annotation_id, frame);
+        }
+        if let Some(width) = line_profile_width {
+            self.line_profile_widths.insert(annotation_id, width);
+        }
+    }
+
+    pub fn take_annotation(&mut self, annotation_id: AnnotationId) -> Option<StoredAnnotation> {
+        let index = self.annotation_ids.iter().position(|id| *id == annotation_id)?;
+        self.annotation_ids.remove(index);
+        let annotation = self.annotations.remove(&annotation_id)?;
+        // Its results are cleared in the frontend, an MTF has to be measured again
+        self.mtf_results.remove(&annotation_id);
+        Some(StoredAnnotation {
+            annotation,
+            index: Some(index),
+            frame: self.annotation_frames.remove(&annotation_id),
+            line_profile_width: self.line_profile_widths.remove(&annotation_id),
+        })
+    }
+
+    // Returns the annotation that was replaced, or the given one if there was nothing to replace
+    pub fn swap_annotation(&mut self, annotation_id: AnnotationId, annotation: Box<dyn Annotation>) -> Box<dyn Annotation> {
+        match self.annotations.get_mut(&annotation_id) {
+            Some(existing) => std::mem::replace(existing, annotation),
+            None => annotation,
+        }
+    }
+
+    pub fn annotation_mut(&mut self, annotation_id: AnnotationId) -> Option<&mut Box<dyn Annotation>> {
+        self.annotations.get_mut(&annotation_id)
+    }
+
+    // Returns the frame the annotation was previously pinned to
+    pub fn set_annotation_frame(&mut self, annotation_id: AnnotationId, frame: Option<usize>) -> Option<usize> {
+        match frame {
+            Some(frame) => self.annotation_frames.insert(annotation_id, frame),
+            None => self.annotation_frames.remove(&annotation_id),
+        }
+    }
+
+    pub fn visible_annotation_ids(&self) -> impl Iterator<Item = AnnotationId> + '_ {
+        self.annotation_ids.iter().copied().filter(|annotation_id| self.annotation_frames.get(annotation_id).map_or(true, |&frame| frame == self.current_frame))
+    }
+
+    // Pinned annotations are measured on their own frame, the rest on the current one
+    fn annotation_frame(&self, annotation_id: AnnotationId) -> &ImageFrame {
+        let frame = self.annotation_frames.get(&annotation_id).copied().unwrap_or(self.current_frame);
+        self.frames.get(frame).unwrap_or(self.image_buffer())
+    }
+
+    // Replaces the stack with a frame arriving from the detector, annotations stay in place and are measured again
+    pub fn set_live_frame(&mut self, frame: ImageFrame, frame_metadata: FrameMetadata, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        self.frames = vec![frame];
+        self.frame_metadata = vec![frame_metadata];
+        self.current_frame = 0;
+        self.annotation_frames.clear();
+        self.send_frame_info(image_id, responses);
+        self.send_display_image(image_id, responses);
+        self.send_all_annotation_updates(image_id, responses);
+    }
+
+    // Adds a frame of a sequence to the end of the stack and shows it
+    pub fn push_live_frame(&mut self, frame: ImageFrame, frame_metadata: FrameMetadata, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        self.frames.push(frame);
+        self.frame_metadata.push(frame_metadata);
+        self.current_frame = self.frames.len() - 1;
+        self.send_frame_info(image_id, responses);
+        self.send_display_image(image_id, responses);
+        self.send_all_annotation_updates(image_id, responses);
+    }
+
+    fn set_current_frame(&mut self, frame: usize, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        let frame = frame.min(self.frames.len() - 1);
+        if frame == self.current_frame {
+            return;
+        }
+        self.current_frame = frame;
+        self.send_frame_info(image_id, responses);
+        self.send_display_image(image_id, responses);
+        self.send_all_annotation_updates(image_id, responses);
+    }
+
+    fn step_frame(&mut self, delta: i64, looping: bool, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        let frame_count = self.frames.len() as i64;
+        let target = self.current_frame as i64 + delta;
+        let frame = if looping {
+            target.rem_euclid(frame_count)
+        } else {
+            // Playback stops at either end of the stack
+            if !(0..frame_count).contains(&target) && self.playback.playing {
+                self.playback.set_playing(false);
+                self.send_frame_info(image_id, responses);
+            }
+            target.clamp(0, frame_count - 1)
+        };
+        self.set_current_frame(frame as usize, image_id, responses);
+    }
+
+    fn send_viewport_transform(&self, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        responses.add(FrontendMessage::UpdateViewportTransform {
+            image_id,
+            transform: self.viewport_transform,
+            matrix: self.viewport_transform.image_to_viewport(self.image_size()).to_cols_array(),
+        });
+    }
+
+    // Only the size is sent, the host renders the pixels once for the latest of these in a tick
+    pub fn send_display_image(&self, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        let image_buffer = self.image_buffer();
+        responses.add(FrontendMessage::UpdateDisplayImage { image_id, width: image_buffer.width(), height: image_buffer.height() });
+    }
+
+    fn send_frame_info(&self, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        responses.add(FrontendMessage::UpdateFrameInfo {
+            image_id,
+            frame_index: self.current_frame as u32,
+            frame_count: self.frames.len() as u32,
+            metadata: self.frame_metadata.get(self.current_frame).copied().unwrap_or_default(),
+            playing: self.playback.playing,
+        });
+    }
+
+    pub fn set_adjustment_levels(&mut self, adjustment_levels: AdjustmentLevels) -> AdjustmentLevels {
+        self.display_lut = DisplayLut::new(&adjustment_levels);
+        std::mem::replace(&mut self.adjustment_levels, adjustment_levels)
+    }
+
+    // The raw buffer is left untouched, only the 8-bit copy reflects the current levels
+    pub fn display_image(&self) -> GrayImage {
+        self.display_lut.apply(self.image_buffer())
+    }
+
+    // Covers the whole image, or only the pixels inside the annotation if it is a shape
+    pub fn histogram(&self, annotation_id: Option<AnnotationId>, bin_count: u32) -> Option<HistogramData> {
+        let histogram = match annotation_id {
+            Some(annotation_id) => {
+                let shape = self.get_annotation(annotation_id)?.as_shape()?;
+                Histogram::from_values(Self::values_in_shape(self.annotation_frame(annotation_id), shape))
+            }
+            None => Histogram::from_image(self.image_buffer()),
+        };
+        Some(histogram.binned(bin_count))
+    }
+
+    fn adjust_levels(&mut self, adjustment_levels: AdjustmentLevels) {
+        if adjustment_levels == self.adjustment_levels {
+            return;
+        }
+        self.execute_command(Box::new(SetAdjustmentLevelsCommand { adjustment_levels, previous: None }));
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.image_undo_history.is_empty() || self.transaction.as_ref().is_some_and(|transaction| !transaction.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.image_redo_history.is_empty()
+    }
+
     fn execute_command(&mut self, mut command: Box<dyn Command>) {
-        command.execute();
-        self.image_undo_history.push(command);
+        command.execute(self);
+        self.image_redo_history.clear();
+
+        match &mut self.transaction {
+            Some(transaction) => transaction.push(command),
+            None => self.push_history(command),
+        }
     }
 
-    fn undo(&mut self, respones: &mut VecDeque<Message>) {
-        let Some(mut command) = self.image_undo_history.pop() else { return };
-        command.undo();
+    fn push_history(&mut self, command: Box<dyn Command>) {
+        self.image_undo_history.push_back(command);
+        if self.image_undo_history.len() > MAX_UNDO_HISTORY {
+            self.image_undo_history.pop_front();
+        }
+    }
+
+    fn commit_transaction(&mut self) {
+        let Some(transaction) = self.transaction.take() else { return };
+        if !transaction.is_empty() {
+            self.push_history(Box::new(transaction));
+        }
+    }
+
+    // An open transaction is committed first so it is undone as a whole
+    fn undo(&mut self) {
+        self.commit_transaction();
+        let Some(mut command) = self.image_undo_history.pop_back() else { return };
+        command.undo(self);
         self.image_redo_history.push(command);
     }
 
     fn redo(&mut self) {
+        self.commit_transaction();
         let Some(mut command) = self.image_redo_history.pop() else { return };
-        command.execute();
-        self.image_undo_history.push(command);
+        command.execute(self);
+        self.image_undo_history.push_back(command);
     }
 
-    fn get_mean() {
+    pub fn roi_statistics(&self, annotation_id: AnnotationId) -> Option<RoiStatistics> {
+        let shape = self.get_annotation(annotation_id)?.as_shape()?;
+        RoiStatistics::from_values(Self::values_in_shape(self.annotation_frame(annotation_id), shape).collect(), shape.get_area(), self.acquisition_metadata.pixel_pitch_mm)
     }
 
-    fn get_mean_in_shape(&self, shape: &dyn Shape) -> f64 {
-        let positions = shape.get_positions();
-        let mut mean = 0.0;
-        mean
+    pub fn mtf_result(&self, annotation_id: AnnotationId) -> Option<&MtfResult> {
+        self.mtf_results.get(&annotation_id)
+    }
+
+    pub fn line_profile(&self, annotation_id: AnnotationId, width: u32) -> Option<LineProfile> {
+        let line = self.get_annotation(annotation_id)?.as_line()?;
+        let (start, end) = (line.start_position().0.as_dvec2(), line.end_position().0.as_dvec2());
+        Some(LineProfile::sample(self.annotation_frame(annotation_id), start, end, width, self.acquisition_metadata.pixel_pitch_mm))
+    }
+
+    fn send_annotation_updates(&self, image_id: ImageId, annotation_id: AnnotationId, responses: &mut VecDeque<Message>) {
+        if let Some(statistics) = self.roi_statistics(annotation_id) {
+            responses.add(FrontendMessage::UpdateRoiStatistics { image_id, annotation_id, statistics });
+        }
+        let Some(&width) = self.line_profile_widths.get(&annotation_id) else { return };
+        if let Some(profile) = self.line_profile(annotation_id, width) {
+            responses.add(FrontendMessage::UpdateLineProfile { image_id, annotation_id, profile });
+        }
+    }
+
+    fn send_all_annotation_updates(&self, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        for annotation_id in self.visible_annotation_ids() {
+            self.send_annotation_updates(image_id, annotation_id, responses);
+        }
+    }
+
+    fn send_removed_annotations(&self, previous_annotation_ids: &[AnnotationId], image_id: ImageId, responses: &mut VecDeque<Message>) {
+        for &annotation_id in previous_annotation_ids.iter().filter(|annotation_id| !self.annotations.contains_key(annotation_id)) {
+            responses.add(FrontendMessage::ClearAnnotationResults { image_id, annotation_id });
+        }
+    }
+
+    // The pixels within the shape's bounding box, clipped to the image
+    pub fn annotation_region(&self, annotation_id: AnnotationId) -> Option<Region> {
+        let shape = self.get_annotation(annotation_id)?.as_shape()?;
+        let frame = self.annotation_frame(annotation_id);
+        let (width, height) = frame.dimensions();
+        let (min, max) = shape.get_positions().fold((None, None), |(min, max): (Option<IVec2>, Option<IVec2>), ImagePosition(position)| {
+            (Some(min.map_or(position, |min| min.min(position))), Some(max.map_or(position, |max| max.max(position))))
+        });
+        let min = min?.max(IVec2::ZERO);
+        let max = max?.min(IVec2::new(width as i32 - 1, height as i32 - 1));
+        if min.x > max.x || min.y > max.y {
+            return None;
+        }
+
+        let data = (min.y..=max.y).flat_map(|y| (min.x..=max.x).map(move |x| (x, y))).map(|(x, y)| frame.get_pixel(x as u32, y as u32).0[0] as f64).collect();
+        Some(Region { data, width: (max.x - min.x + 1) as usize, height: (max.y - min.y + 1) as usize })
+    }
+
+    // Positions outside the image are skipped
+    fn values_in_shape<'a>(frame: &'a ImageFrame, shape: &dyn Shape) -> impl Iterator<Item = u16> + 'a {
+        let (width, height) = frame.dimensions();
+        shape
+            .get_positions()
+            .filter(move |ImagePosition(position)| position.x >= 0 && position.y >= 0 && (position.x as u32) < width && (position.y as u32) < height)
+            .map(|ImagePosition(position)| frame.get_pixel(position.x as u32, position.y as u32).0[0])
     }
 
 
     fn get_annotation(&self, id: AnnotationId) -> Option<&dyn Annotation> {
         self.annotations.get(&id).map(|boxed| boxed.as_ref())
     }
+}
+#[cfg(test)]
+mod tests {
+    use image::Luma;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::messages::portfolio::image::utility_types::annotations::{AnnotationEnum, Line, Rectangle, ShapeEnum};
 
-    fn get_image(&self) -> ImageBuffer<Luma<u16>, Vec<u16>> {
-        let mut adjusted_image = self.image_buffer.clone();
-        self.adjust_brightness(&mut adjusted_image);
-        adjusted_image
+    fn image() -> ImageMessageHandler {
+        ImageMessageHandler::new(ImageFrame::from_pixel(4, 4, Luma([10])), AcquisitionMetadata::default(), FrameMetadata::default())
     }
 
-    fn adjust_brightness(&self, image: &mut ImageBuffer<Luma<u16>, Vec<u16>>) {
-        let brightness_factor = self.adjustment_levels.brightness.get() as f32 / 100.0;
-        for pixel in image.pixels_mut() {
-            let Luma([luma]) = *pixel;
-            let adjusted_luma = (luma as f32 * brightness_factor) as u16;
-            *pixel = Luma([adjusted_luma]);
+    fn send(image: &mut ImageMessageHandler, message: ImageMessage) -> VecDeque<Message> {
+        let mut responses = VecDeque::new();
+        image.process_message(message, &mut responses, ImageMessageData { image_id: ImageId(Uuid::nil()), viewport_size: DVec2::new(100., 100.) });
+        responses
+    }
+
+    fn position(x: i32, y: i32) -> ImagePosition {
+        ImagePosition(IVec2::new(x, y))
+    }
+
+    fn pixel(image: &ImageMessageHandler, x: u32, y: u32) -> u16 {
+        image.image_buffer().get_pixel(x, y).0[0]
+    }
+
+    fn add_rectangle(image: &mut ImageMessageHandler) -> AnnotationId {
+        let rectangle = Rectangle::from_corners(position(0, 0), position(2, 2));
+        send(image, ImageMessage::AddAnnotation { annotation: AnnotationEnum::Shape(ShapeEnum::Rectangle(rectangle)) });
+        *image.annotation_ids.last().unwrap()
+    }
+
+    fn annotation_position(image: &mut ImageMessageHandler, annotation_id: AnnotationId) -> Option<ImagePosition> {
+        image.annotation_mut(annotation_id).map(|annotation| annotation.get_position())
+    }
+
+    #[test]
+    fn pixel_edits_undo_and_redo() {
+        let mut image = image();
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(1, 1), position(9, 9)], value: 99 });
+        assert_eq!(pixel(&image, 1, 1), 99);
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(pixel(&image, 1, 1), 10);
+        assert!(image.can_redo());
+
+        send(&mut image, ImageMessage::Redo);
+        assert_eq!(pixel(&image, 1, 1), 99);
+        assert!(!image.can_redo());
+    }
+
+    fn sends_display_image(responses: &VecDeque<Message>) -> bool {
+        responses.iter().any(|message| matches!(message, Message::Frontend(FrontendMessage::UpdateDisplayImage { width: 4, height: 4, .. })))
+    }
+
+    #[test]
+    fn display_image_is_sent_when_the_displayed_pixels_change() {
+        let mut image = image();
+        assert!(sends_display_image(&send(&mut image, ImageMessage::SetWindow { min: 0, max: 20 })));
+        assert!(sends_display_image(&send(&mut image, ImageMessage::SetValue { positions: vec![position(1, 1)], value: 20 })));
+        assert_eq!((image.display_image().get_pixel(1, 1).0[0], image.display_image().get_pixel(0, 0).0[0] < 255), (255, true));
+        assert!(sends_display_image(&send(&mut image, ImageMessage::Undo)));
+
+        // Moving the canvas leaves the pixels as they are
+        assert!(!sends_display_image(&send(&mut image, ImageMessage::PanCanvas { delta: DVec2::new(1., 1.) })));
+        assert!(!sends_display_image(&send(&mut image, ImageMessage::SetWindow { min: 0, max: 20 })));
+    }
+
+    #[test]
+    fn subtraction_undo_restores_clipped_pixels() {
+        let mut image = image();
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value: 50 });
+        send(&mut image, ImageMessage::SubtractValue { value: 20 });
+        assert_eq!((pixel(&image, 0, 0), pixel(&image, 1, 0)), (30, 0));
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!((pixel(&image, 0, 0), pixel(&image, 1, 0)), (50, 10));
+    }
+
+    #[test]
+    fn new_command_clears_redo() {
+        let mut image = image();
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value: 1 });
+        send(&mut image, ImageMessage::Undo);
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value: 2 });
+        assert!(!image.can_redo());
+    }
+
+    #[test]
+    fn moves_merge_only_within_a_transaction() {
+        let mut image = image();
+        let annotation_id = add_rectangle(&mut image);
+
+        send(&mut image, ImageMessage::StartTransaction);
+        for x in 1..=3 {
+            send(&mut image, ImageMessage::MoveAnnotation { annotation_id, position: position(x, 0) });
+        }
+        send(&mut image, ImageMessage::CommitTransaction);
+        send(&mut image, ImageMessage::MoveAnnotation { annotation_id, position: position(3, 1) });
+        send(&mut image, ImageMessage::MoveAnnotation { annotation_id, position: position(3, 2) });
+        assert_eq!(image.image_undo_history.len(), 4);
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(annotation_position(&mut image, annotation_id), Some(position(3, 1)));
+        send(&mut image, ImageMessage::Undo);
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(annotation_position(&mut image, annotation_id), Some(position(0, 0)));
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(annotation_position(&mut image, annotation_id), None);
+    }
+
+    #[test]
+    fn transaction_is_undone_as_one_entry() {
+        let mut image = image();
+        let annotation_id = add_rectangle(&mut image);
+
+        send(&mut image, ImageMessage::StartTransaction);
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value: 1 });
+        send(&mut image, ImageMessage::SetWindow { min: 5, max: 500 });
+        send(&mut image, ImageMessage::MoveAnnotation { annotation_id, position: position(1, 1) });
+        send(&mut image, ImageMessage::CommitTransaction);
+        assert_eq!(image.image_undo_history.len(), 2);
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(pixel(&image, 0, 0), 10);
+        assert_eq!(*image.adjustment_levels(), AdjustmentLevels::default());
+        assert_eq!(annotation_position(&mut image, annotation_id), Some(position(0, 0)));
+
+        send(&mut image, ImageMessage::Redo);
+        assert_eq!(pixel(&image, 0, 0), 1);
+        assert_eq!((image.adjustment_levels().min, image.adjustment_levels().max), (5, 500));
+        assert_eq!(annotation_position(&mut image, annotation_id), Some(position(1, 1)));
+    }
+
+    #[test]
+    fn undo_commits_an_open_transaction() {
+        let mut image = image();
+        send(&mut image, ImageMessage::StartTransaction);
+        send(&mut image, ImageMessage::SetWindow { min: 1, max: 100 });
+        send(&mut image, ImageMessage::SetWindow { min: 2, max: 200 });
+        assert!(image.can_undo());
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(*image.adjustment_levels(), AdjustmentLevels::default());
+        // The transaction is closed, later changes are separate entries again
+        send(&mut image, ImageMessage::SetWindow { min: 3, max: 300 });
+        send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value: 1 });
+        assert_eq!(image.image_undo_history.len(), 2);
+    }
+
+    fn cleared_annotations(responses: &VecDeque<Message>) -> Vec<AnnotationId> {
+        responses
+            .iter()
+            .filter_map(|message| match message {
+                Message::Frontend(FrontendMessage::ClearAnnotationResults { annotation_id, .. }) => Some(*annotation_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn removed_annotations_clear_their_results() {
+        let mut image = image();
+        let annotation_id = add_rectangle(&mut image);
+
+        let responses = send(&mut image, ImageMessage::RemoveAnnotation { annotation_id });
+        assert_eq!(cleared_annotations(&responses), [annotation_id]);
+
+        // Bringing it back sends fresh statistics instead
+        let responses = send(&mut image, ImageMessage::Undo);
+        assert!(cleared_annotations(&responses).is_empty());
+        assert!(responses.iter().any(|message| matches!(message, Message::Frontend(FrontendMessage::UpdateRoiStatistics { .. }))));
+
+        assert_eq!(cleared_annotations(&send(&mut image, ImageMessage::Redo)), [annotation_id]);
+        send(&mut image, ImageMessage::Undo);
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(image.annotation_ids, []);
+        assert_eq!(cleared_annotations(&send(&mut image, ImageMessage::Undo)), []);
+    }
+
+    #[test]
+    fn undoing_an_addition_clears_its_results() {
+        let mut image = image();
+        let annotation_id = add_rectangle(&mut image);
+        assert_eq!(cleared_annotations(&send(&mut image, ImageMessage::Undo)), [annotation_id]);
+    }
+
+    #[test]
+    fn removal_undo_restores_the_line_profile() {
+        let mut image = image();
+        let line = Line::new(position(0, 0), position(3, 0));
+        send(&mut image, ImageMessage::AddAnnotation { annotation: AnnotationEnum::Shape(ShapeEnum::Line(line)) });
+        let annotation_id = image.annotation_ids[0];
+        send(&mut image, ImageMessage::ComputeLineProfile { annotation_id, width: 3 });
+
+        send(&mut image, ImageMessage::RemoveAnnotation { annotation_id });
+        assert!(image.line_profile_widths.is_empty());
+
+        let responses = send(&mut image, ImageMessage::Undo);
+        assert_eq!(image.line_profile_widths.get(&annotation_id), Some(&3));
+        assert!(responses.iter().any(|message| matches!(message, Message::Frontend(FrontendMessage::UpdateLineProfile { .. }))));
+
+        // Undoing the addition forgets the width as well, redoing it brings it back
+        send(&mut image, ImageMessage::Undo);
+        assert!(image.line_profile_widths.is_empty());
+        send(&mut image, ImageMessage::Redo);
+        assert_eq!(image.line_profile_widths.get(&annotation_id), Some(&3));
+    }
+
+    #[test]
+    fn removal_undo_restores_the_pinned_frame() {
+        let mut image = ImageMessageHandler::from_image_stack(ImageStack {
+            frames: vec![ImageFrame::from_pixel(4, 4, Luma([10])), ImageFrame::from_pixel(4, 4, Luma([20]))],
+            acquisition: AcquisitionMetadata::default(),
+            frame_metadata: Vec::new(),
+        })
+        .unwrap();
+        let annotation_id = add_rectangle(&mut image);
+        send(&mut image, ImageMessage::PinAnnotation { annotation_id, frame: Some(1) });
+
+        send(&mut image, ImageMessage::RemoveAnnotation { annotation_id });
+        assert!(image.annotation_frames.is_empty());
+
+        send(&mut image, ImageMessage::Undo);
+        assert_eq!(image.annotation_frames.get(&annotation_id), Some(&1));
+        assert_eq!(image.roi_statistics(annotation_id).unwrap().mean, 20.);
+        assert_eq!(image.visible_annotation_ids().count(), 0);
+    }
+
+    #[test]
+    fn line_profiles_are_only_kept_for_lines() {
+        let mut image = image();
+        let annotation_id = add_rectangle(&mut image);
+        send(&mut image, ImageMessage::ComputeLineProfile { annotation_id, width: 1 });
+        send(&mut image, ImageMessage::ComputeLineProfile { annotation_id: AnnotationId(Uuid::nil()), width: 1 });
+        assert!(image.line_profile_widths.is_empty());
+    }
+
+    #[test]
+    fn oldest_history_entries_are_dropped() {
+        let mut image = image();
+        for value in 1..=(MAX_UNDO_HISTORY + 50) as u16 {
+            send(&mut image, ImageMessage::SetValue { positions: vec![position(0, 0)], value });
+        }
+        for _ in 0..MAX_UNDO_HISTORY {
+            send(&mut image, ImageMessage::Undo);
         }
+        assert!(!image.can_undo());
+        assert_eq!(pixel(&image, 0, 0), 50);
     }
-}
\ No newline at end of file
+}
@@ -4,4 +4,4 @@ mod image_message_handler;
 pub mod utility_types;
 
 pub use image_message::ImageMessage;
-pub use image_message_handler::ImageMessageHandler;
\ No newline at end of file
+pub use image_message_handler::{ImageMessageData, ImageMessageHandler, StoredAnnotation};
\ No newline at end of file
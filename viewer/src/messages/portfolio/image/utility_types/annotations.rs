@@ -1,11 +1,22 @@
 use super::misc::ImagePosition;
 
+use glam::IVec2;
 use serde::{Deserialize, Serialize};
 
 
 pub trait Annotation {
     fn get_position(&self) -> ImagePosition;
     fn set_position(&mut self, new_position: ImagePosition);
+
+    fn boxed_clone(&self) -> Box<dyn Annotation>;
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        None
+    }
+
+    fn as_line(&self) -> Option<&Line> {
+        None
+    }
 }
 
 pub trait Shape: Annotation {    
@@ -13,7 +24,7 @@ pub trait Shape: Annotation {
     fn get_positions(&self) -> Box<dyn Iterator<Item = ImagePosition>>;
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct TextAnnotation {
     position: ImagePosition,
     content: String,
@@ -27,10 +38,28 @@ impl Annotation for TextAnnotation {
     fn set_position(&mut self, new_position: ImagePosition) {
         self.position = new_position;
     }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
-struct Circle {
+// Every pixel whose index lies within the ellipse centred on the given pixel
+fn ellipse_positions(centre: IVec2, radius_x: f64, radius_y: f64) -> Box<dyn Iterator<Item = ImagePosition>> {
+    let (radius_x, radius_y) = (radius_x.abs(), radius_y.abs());
+    if radius_x == 0. || radius_y == 0. {
+        return Box::new(std::iter::once(ImagePosition(centre)));
+    }
+    let (extent_x, extent_y) = (radius_x.floor() as i32, radius_y.floor() as i32);
+    Box::new((-extent_y..=extent_y).flat_map(move |dy| {
+        (-extent_x..=extent_x)
+            .filter(move |&dx| (dx as f64 / radius_x).powi(2) + (dy as f64 / radius_y).powi(2) <= 1.)
+            .map(move |dx| ImagePosition(centre + IVec2::new(dx, dy)))
+    }))
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct Circle {
     position: ImagePosition,
     radius: f64,
 }
@@ -43,15 +72,210 @@ impl Annotation for Circle {
     fn set_position(&mut self, new_position: ImagePosition) {
         self.position = new_position;
     }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        Some(self)
+    }
+}
+
+impl Shape for Circle {
+    fn get_area(&self) -> f64 {
+        std::f64::consts::PI * self.radius * self.radius
+    }
+
+    fn get_positions(&self) -> Box<dyn Iterator<Item = ImagePosition>> {
+        ellipse_positions(self.position.0, self.radius, self.radius)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct Ellipse {
+    position: ImagePosition,
+    radius_x: f64,
+    radius_y: f64,
+}
+
+impl Annotation for Ellipse {
+    fn get_position(&self) -> ImagePosition {
+        self.position
+    }
+
+    fn set_position(&mut self, new_position: ImagePosition) {
+        self.position = new_position;
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        Some(self)
+    }
+}
+
+impl Shape for Ellipse {
+    fn get_area(&self) -> f64 {
+        std::f64::consts::PI * self.radius_x.abs() * self.radius_y.abs()
+    }
+
+    fn get_positions(&self) -> Box<dyn Iterator<Item = ImagePosition>> {
+        ellipse_positions(self.position.0, self.radius_x, self.radius_y)
+    }
+}
+
+// The position is the first vertex, moving the polygon translates every vertex
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct Polygon {
+    vertices: Vec<ImagePosition>,
+}
+
+impl Polygon {
+    fn contains(vertices: &[IVec2], point: IVec2) -> bool {
+        let (x, y) = (point.x as f64, point.y as f64);
+        let mut inside = false;
+        for (index, current) in vertices.iter().enumerate() {
+            let previous = vertices[(index + vertices.len() - 1) % vertices.len()];
+            let (x0, y0, x1, y1) = (previous.x as f64, previous.y as f64, current.x as f64, current.y as f64);
+            if (y0 > y) != (y1 > y) && x < (x1 - x0) * (y - y0) / (y1 - y0) + x0 {
+                inside = !inside;
+            }
+        }
+        inside
+    }
+}
+
+impl Annotation for Polygon {
+    fn get_position(&self) -> ImagePosition {
+        self.vertices.first().copied().unwrap_or(ImagePosition(IVec2::ZERO))
+    }
+
+    fn set_position(&mut self, new_position: ImagePosition) {
+        let offset = new_position.0 - self.get_position().0;
+        for ImagePosition(vertex) in &mut self.vertices {
+            *vertex += offset;
+        }
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        Some(self)
+    }
+}
+
+impl Shape for Polygon {
+    // Shoelace formula
+    fn get_area(&self) -> f64 {
+        let twice_area: i64 = self.vertices
+            .iter()
+            .zip(self.vertices.iter().cycle().skip(1))
+            .map(|(ImagePosition(a), ImagePosition(b))| a.x as i64 * b.y as i64 - b.x as i64 * a.y as i64)
+            .sum();
+        twice_area.abs() as f64 / 2.
+    }
+
+    fn get_positions(&self) -> Box<dyn Iterator<Item = ImagePosition>> {
+        if self.vertices.len() < 3 {
+            return Box::new(std::iter::empty());
+        }
+        let vertices: Vec<IVec2> = self.vertices.iter().map(|vertex| vertex.0).collect();
+        let min = vertices.iter().copied().reduce(IVec2::min).unwrap();
+        let max = vertices.iter().copied().reduce(IVec2::max).unwrap();
+        Box::new((min.y..=max.y).flat_map(move |y| (min.x..=max.x).map(move |x| IVec2::new(x, y)))
+            .filter(move |&point| Self::contains(&vertices, point))
+            .map(ImagePosition))
+    }
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct Line {
     start_position: ImagePosition,
     end_position: ImagePosition,
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, specta::Type)]
+impl Line {
+    pub fn new(start_position: ImagePosition, end_position: ImagePosition) -> Self {
+        Self { start_position, end_position }
+    }
+
+    pub fn start_position(&self) -> ImagePosition {
+        self.start_position
+    }
+
+    pub fn end_position(&self) -> ImagePosition {
+        self.end_position
+    }
+}
+
+impl Annotation for Line {
+    fn get_position(&self) -> ImagePosition {
+        self.start_position
+    }
+
+    fn set_position(&mut self, new_position: ImagePosition) {
+        let offset = new_position.0 - self.start_position.0;
+        self.start_position = new_position;
+        self.end_position = ImagePosition(self.end_position.0 + offset);
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        Some(self)
+    }
+
+    fn as_line(&self) -> Option<&Line> {
+        Some(self)
+    }
+}
+
+impl Shape for Line {
+    // A line covers no area, its statistics are taken over the pixels it passes through
+    fn get_area(&self) -> f64 {
+        0.
+    }
+
+    // Bresenham's line algorithm
+    fn get_positions(&self) -> Box<dyn Iterator<Item = ImagePosition>> {
+        let (start, end) = (self.start_position.0, self.end_position.0);
+        let delta = IVec2::new((end.x - start.x).abs(), -(end.y - start.y).abs());
+        let step = IVec2::new((end.x - start.x).signum(), (end.y - start.y).signum());
+        let mut current = start;
+        let mut error = delta.x + delta.y;
+        let mut done = false;
+
+        Box::new(std::iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let position = current;
+            if current == end {
+                done = true;
+            } else {
+                let doubled_error = 2 * error;
+                if doubled_error >= delta.y {
+                    error += delta.y;
+                    current.x += step.x;
+                }
+                if doubled_error <= delta.x {
+                    error += delta.x;
+                    current.y += step.y;
+                }
+            }
+            Some(ImagePosition(position))
+        }))
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct Rectangle {
     position: ImagePosition,
     width: u32,
@@ -72,6 +296,14 @@ impl Annotation for Rectangle  {
     fn set_position(&mut self, new_position: ImagePosition) {
         self.position = new_position;
     }
+
+    fn boxed_clone(&self) -> Box<dyn Annotation> {
+        Box::new(self.clone())
+    }
+
+    fn as_shape(&self) -> Option<&dyn Shape> {
+        Some(self)
+    }
 }
 
 impl Shape for Rectangle {
@@ -85,6 +317,15 @@ impl Shape for Rectangle {
 }
 
 impl Rectangle {
+    pub fn from_corners(first: ImagePosition, second: ImagePosition) -> Self {
+        let (min, max) = (first.0.min(second.0), first.0.max(second.0));
+        Self {
+            position: ImagePosition(min),
+            width: (max.x - min.x) as u32,
+            height: (max.y - min.y) as u32,
+        }
+    }
+
     fn iter(&self) -> RectangleIterator {
         RectangleIterator {
             rectangle: *self,
@@ -98,30 +339,135 @@ impl Iterator for RectangleIterator {
     type Item = ImagePosition;
 
     fn next(&mut self) -> Option<Self::Item> {
-        None
-        // if self.current_y < self.rectangle.height {
-        //     let result = Some(ImagePosition((self.rectangle.position.0.x as u32 + self.current_x, self.rectangle.position.0.y as u32 + self.current_y)));
-        //     self.current_x += 1;
-        //     if self.current_x == self.rectangle.width {
-        //         self.current_x = 0;
-        //         self.current_y += 1;
-        //     }
-        //     result
-        // } else {
-        //     None
-        // }
+        if self.rectangle.width == 0 || self.current_y >= self.rectangle.height {
+            return None;
+        }
+        let result = ImagePosition(self.rectangle.position.0 + IVec2::new(self.current_x as i32, self.current_y as i32));
+        self.current_x += 1;
+        if self.current_x == self.rectangle.width {
+            self.current_x = 0;
+            self.current_y += 1;
+        }
+        Some(result)
     }
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 #[serde(tag = "type")]
 pub enum AnnotationEnum {
     Text(TextAnnotation),
     Shape(ShapeEnum)
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub enum ShapeEnum {
     Circle(Circle),
+    Ellipse(Ellipse),
+    Line(Line),
+    Polygon(Polygon),
     Rectangle(Rectangle)
-}
\ No newline at end of file
+}
+
+impl From<AnnotationEnum> for Box<dyn Annotation> {
+    fn from(annotation: AnnotationEnum) -> Self {
+        match annotation {
+            AnnotationEnum::Text(text) => Box::new(text),
+            AnnotationEnum::Shape(ShapeEnum::Circle(circle)) => Box::new(circle),
+            AnnotationEnum::Shape(ShapeEnum::Ellipse(ellipse)) => Box::new(ellipse),
+            AnnotationEnum::Shape(ShapeEnum::Line(line)) => Box::new(line),
+            AnnotationEnum::Shape(ShapeEnum::Polygon(polygon)) => Box::new(polygon),
+            AnnotationEnum::Shape(ShapeEnum::Rectangle(rectangle)) => Box::new(rectangle),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(x: i32, y: i32) -> ImagePosition {
+        ImagePosition(IVec2::new(x, y))
+    }
+
+    fn positions(shape: &dyn Shape) -> Vec<(i32, i32)> {
+        let mut positions: Vec<_> = shape.get_positions().map(|ImagePosition(position)| (position.x, position.y)).collect();
+        positions.sort_unstable_by_key(|&(x, y)| (y, x));
+        positions
+    }
+
+    #[test]
+    fn circle_covers_pixels_within_the_radius() {
+        let circle = Circle { position: position(5, 5), radius: 1. };
+        assert_eq!(positions(&circle), [(5, 4), (4, 5), (5, 5), (6, 5), (5, 6)]);
+
+        let point = Circle { position: position(5, 5), radius: 0. };
+        assert_eq!(positions(&point), [(5, 5)]);
+    }
+
+    #[test]
+    fn ellipse_uses_both_radii_regardless_of_sign() {
+        let ellipse = Ellipse { position: position(0, 0), radius_x: 2., radius_y: 1. };
+        assert_eq!(positions(&ellipse), [(0, -1), (-2, 0), (-1, 0), (0, 0), (1, 0), (2, 0), (0, 1)]);
+
+        let flipped = Ellipse { radius_x: -2., radius_y: -1., ..ellipse.clone() };
+        assert_eq!(positions(&flipped), positions(&ellipse));
+        assert_eq!(flipped.get_area(), ellipse.get_area());
+    }
+
+    #[test]
+    fn polygon_covers_its_interior() {
+        let square = Polygon { vertices: vec![position(0, 0), position(4, 0), position(4, 4), position(0, 4)] };
+        let covered = positions(&square);
+        assert_eq!(covered.len(), 16);
+        assert!(covered.iter().all(|&(x, y)| (0..4).contains(&x) && (0..4).contains(&y)));
+        assert_eq!(square.get_area(), 16.);
+
+        let triangle = Polygon { vertices: vec![position(0, 0), position(4, 0), position(0, 4)] };
+        assert_eq!(triangle.get_area(), 8.);
+        assert!(positions(&triangle).iter().all(|&(x, y)| x + y <= 4));
+
+        let degenerate = Polygon { vertices: vec![position(0, 0), position(4, 4)] };
+        assert!(positions(&degenerate).is_empty());
+    }
+
+    #[test]
+    fn moving_a_polygon_translates_every_vertex() {
+        let mut polygon = Polygon { vertices: vec![position(1, 1), position(3, 1), position(2, 3)] };
+        polygon.set_position(position(2, 0));
+        assert_eq!(polygon.vertices, [position(2, 0), position(4, 0), position(3, 2)]);
+    }
+
+    #[test]
+    fn line_visits_each_pixel_from_start_to_end() {
+        let line = Line { start_position: position(0, 0), end_position: position(3, 1) };
+        let visited: Vec<_> = line.get_positions().collect();
+        assert_eq!(visited, [position(0, 0), position(1, 0), position(2, 1), position(3, 1)]);
+
+        let reversed = Line { start_position: position(3, 1), end_position: position(0, 0) };
+        let visited: Vec<_> = reversed.get_positions().collect();
+        assert_eq!((visited.len(), visited.first(), visited.last()), (4, Some(&position(3, 1)), Some(&position(0, 0))));
+
+        let steep = Line { start_position: position(0, 0), end_position: position(1, -5) };
+        assert_eq!(steep.get_positions().count(), 6);
+
+        let point = Line { start_position: position(2, 2), end_position: position(2, 2) };
+        assert_eq!(point.get_positions().collect::<Vec<_>>(), [position(2, 2)]);
+    }
+
+    #[test]
+    fn moving_a_line_keeps_its_length() {
+        let mut line = Line { start_position: position(0, 0), end_position: position(3, 1) };
+        line.set_position(position(10, 10));
+        assert_eq!((line.start_position(), line.end_position()), (position(10, 10), position(13, 11)));
+    }
+
+    #[test]
+    fn rectangle_iterates_row_by_row() {
+        let rectangle = Rectangle::from_corners(position(3, 3), position(1, 1));
+        let visited: Vec<_> = rectangle.iter().collect();
+        assert_eq!(visited, [position(1, 1), position(2, 1), position(1, 2), position(2, 2)]);
+        assert_eq!(rectangle.get_area(), 4.);
+
+        let empty = Rectangle::from_corners(position(1, 1), position(1, 5));
+        assert_eq!(empty.iter().count(), 0);
+    }
+}
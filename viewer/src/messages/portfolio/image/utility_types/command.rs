@@ -1,17 +1,272 @@
+use std::any::Any;
+
+use crate::messages::portfolio::image::StoredAnnotation;
 use crate::messages::prelude::*;
 
-use super::{misc::{AnnotationId, Command}, annotations::{Annotation, Shape}};
+use super::{misc::{AdjustmentLevels, AnnotationId, Command, ImagePosition}, annotations::Annotation};
+
+// The commands issued during one transaction, undone and redone as a single history entry
+#[derive(Default)]
+pub struct CommandGroup {
+    commands: Vec<Box<dyn Command>>,
+}
+
+impl CommandGroup {
+    // Folds the command into the previous one when they can be combined, such as successive moves of a drag
+    pub fn push(&mut self, command: Box<dyn Command>) {
+        if let Some(last) = self.commands.last_mut() {
+            if last.merge(command.as_ref()) {
+                return;
+            }
+        }
+        self.commands.push(command);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+}
+
+impl Command for CommandGroup {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        for command in &mut self.commands {
+            command.execute(image);
+        }
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        for command in self.commands.iter_mut().rev() {
+            command.undo(image);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct AddAnnotationCommand {
+    pub annotation_id: AnnotationId,
+    pub annotation: Option<StoredAnnotation>,
+}
+
+impl Command for AddAnnotationCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        if let Some(annotation) = self.annotation.take() {
+            image.insert_annotation(self.annotation_id, annotation);
+        }
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        self.annotation = image.take_annotation(self.annotation_id);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
 
-pub struct AddAnnotationCommand<'a> {
-    pub annotation: Box<dyn Annotation>,
-    pub image_handler: &'a ImageMessageHandler,
+pub struct RemoveAnnotationCommand {
+    pub annotation_id: AnnotationId,
+    pub removed: Option<StoredAnnotation>,
 }
 
-impl<'a> Command for AddAnnotationCommand<'a> {
-    fn execute(&mut self) {
+impl Command for RemoveAnnotationCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        self.removed = image.take_annotation(self.annotation_id);
     }
 
-    fn undo(&self) {
-        
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        if let Some(removed) = self.removed.take() {
+            image.insert_annotation(self.annotation_id, removed);
+        }
     }
-}
\ No newline at end of file
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct MoveAnnotationCommand {
+    pub annotation_id: AnnotationId,
+    pub from: Option<ImagePosition>,
+    pub to: ImagePosition,
+}
+
+impl Command for MoveAnnotationCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        let Some(annotation) = image.annotation_mut(self.annotation_id) else { return };
+        self.from.get_or_insert(annotation.get_position());
+        annotation.set_position(self.to);
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        let (Some(annotation), Some(from)) = (image.annotation_mut(self.annotation_id), self.from) else { return };
+        annotation.set_position(from);
+    }
+
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        let Some(next) = next.as_any().downcast_ref::<Self>() else { return false };
+        if next.annotation_id != self.annotation_id {
+            return false;
+        }
+        self.to = next.to;
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct PixelEditCommand {
+    pub frame: usize,
+    pub edit: PixelEdit,
+    pub previous: Vec<(usize, u16)>,
+}
+
+#[derive(Clone)]
+pub enum PixelEdit {
+    Set { positions: Vec<ImagePosition>, value: u16 },
+    Subtract { value: u16 },
+}
+
+impl Command for PixelEditCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        let Some(frame) = image.frame_mut(self.frame) else { return };
+        let (width, height) = frame.dimensions();
+        let pixels: &mut [u16] = frame;
+        self.previous.clear();
+
+        match &self.edit {
+            PixelEdit::Set { positions, value } => {
+                for ImagePosition(position) in positions {
+                    if position.x < 0 || position.y < 0 || position.x as u32 >= width || position.y as u32 >= height {
+                        continue;
+                    }
+                    let index = position.y as usize * width as usize + position.x as usize;
+                    self.previous.push((index, pixels[index]));
+                    pixels[index] = *value;
+                }
+            }
+            // Only pixels that clip need remembering, every other pixel is restored by adding the value back
+            PixelEdit::Subtract { value } => {
+                for (index, pixel) in pixels.iter_mut().enumerate() {
+                    if *pixel < *value {
+                        self.previous.push((index, *pixel));
+                    }
+                    *pixel = pixel.saturating_sub(*value);
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        let Some(frame) = image.frame_mut(self.frame) else { return };
+        let pixels: &mut [u16] = frame;
+        if let PixelEdit::Subtract { value } = self.edit {
+            for pixel in pixels.iter_mut() {
+                *pixel = pixel.saturating_add(value);
+            }
+        }
+        // Reverse order so a pixel touched twice ends up with its original value
+        for &(index, value) in self.previous.iter().rev() {
+            pixels[index] = value;
+        }
+    }
+
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        let Some(next) = next.as_any().downcast_ref::<Self>().filter(|next| next.frame == self.frame) else { return false };
+        let (PixelEdit::Set { positions, value }, PixelEdit::Set { positions: next_positions, value: next_value }) = (&mut self.edit, &next.edit) else {
+            return false;
+        };
+        if value != next_value {
+            return false;
+        }
+        positions.extend_from_slice(next_positions);
+        self.previous.extend_from_slice(&next.previous);
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct SetAdjustmentLevelsCommand {
+    pub adjustment_levels: AdjustmentLevels,
+    pub previous: Option<AdjustmentLevels>,
+}
+
+impl Command for SetAdjustmentLevelsCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        let previous = image.set_adjustment_levels(self.adjustment_levels.clone());
+        self.previous.get_or_insert(previous);
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        if let Some(previous) = self.previous.clone() {
+            image.set_adjustment_levels(previous);
+        }
+    }
+
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        let Some(next) = next.as_any().downcast_ref::<Self>() else { return false };
+        self.adjustment_levels = next.adjustment_levels.clone();
+        true
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+// Swaps the stored annotation with the one held by the command, so execute and undo are the same operation
+pub struct ReplaceAnnotationCommand {
+    pub annotation_id: AnnotationId,
+    pub annotation: Option<Box<dyn Annotation>>,
+}
+
+impl Command for ReplaceAnnotationCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        if let Some(annotation) = self.annotation.take() {
+            self.annotation = Some(image.swap_annotation(self.annotation_id, annotation));
+        }
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        self.execute(image);
+    }
+
+    // Keeps the original annotation so undoing a whole resize drag restores it
+    fn merge(&mut self, next: &dyn Command) -> bool {
+        next.as_any().downcast_ref::<Self>().is_some_and(|next| next.annotation_id == self.annotation_id)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct PinAnnotationCommand {
+    pub annotation_id: AnnotationId,
+    pub frame: Option<usize>,
+    pub previous: Option<Option<usize>>,
+}
+
+impl Command for PinAnnotationCommand {
+    fn execute(&mut self, image: &mut ImageMessageHandler) {
+        let previous = image.set_annotation_frame(self.annotation_id, self.frame);
+        self.previous.get_or_insert(previous);
+    }
+
+    fn undo(&mut self, image: &mut ImageMessageHandler) {
+        if let Some(previous) = self.previous {
+            image.set_annotation_frame(self.annotation_id, previous);
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
@@ -0,0 +1,108 @@
+use image::{GrayImage, ImageBuffer, Luma};
+
+use super::misc::{AdjustmentLevels, ImageFrame};
+
+const LUT_SIZE: usize = u16::MAX as usize + 1;
+
+// Maps every possible 16-bit value to an 8-bit display value, so adjusting levels never touches the raw data
+#[derive(Debug, Clone)]
+pub struct DisplayLut {
+    table: Vec<u8>,
+}
+
+impl DisplayLut {
+    pub fn new(adjustment_levels: &AdjustmentLevels) -> Self {
+        let min = adjustment_levels.min.min(u16::MAX as u32) as f32;
+        let max = adjustment_levels.max.min(u16::MAX as u32) as f32;
+        let window = (max - min).max(1.);
+        let gamma = if adjustment_levels.gamma > 0. { adjustment_levels.gamma } else { 1. };
+        // 50% contrast leaves the window untouched, 0% flattens it and 100% doubles its slope
+        let contrast = adjustment_levels.contrast.get() as f32 / 50.;
+        let brightness = adjustment_levels.brightness.get() as f32 / 100.;
+
+        let table = (0..LUT_SIZE)
+            .map(|value| {
+                let normalised = ((value as f32 - min) / window).clamp(0., 1.);
+                let corrected = normalised.powf(1. / gamma);
+                let contrasted = (corrected - 0.5) * contrast + 0.5;
+                let mut display = (contrasted * brightness).clamp(0., 1.);
+                if adjustment_levels.invert {
+                    display = 1. - display;
+                }
+                (display * u8::MAX as f32).round() as u8
+            })
+            .collect();
+
+        Self { table }
+    }
+
+    pub fn map(&self, value: u16) -> u8 {
+        self.table[value as usize]
+    }
+
+    pub fn apply(&self, image: &ImageFrame) -> GrayImage {
+        let (width, height) = image.dimensions();
+        let pixels = image.as_raw().iter().map(|&value| self.map(value)).collect();
+        ImageBuffer::<Luma<u8>, Vec<u8>>::from_raw(width, height, pixels).unwrap()
+    }
+}
+
+impl Default for DisplayLut {
+    fn default() -> Self {
+        Self::new(&AdjustmentLevels::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::messages::portfolio::image::utility_types::misc::Percentage;
+
+    fn levels(min: u32, max: u32) -> AdjustmentLevels {
+        AdjustmentLevels { min, max, ..AdjustmentLevels::default() }
+    }
+
+    #[test]
+    fn default_levels_span_the_full_range() {
+        let lut = DisplayLut::default();
+        assert_eq!((lut.map(0), lut.map(u16::MAX)), (0, 255));
+        assert!((1..LUT_SIZE).all(|value| lut.map(value as u16) >= lut.map(value as u16 - 1)));
+    }
+
+    #[test]
+    fn window_clips_outside_values() {
+        let lut = DisplayLut::new(&levels(100, 200));
+        assert_eq!((lut.map(0), lut.map(100)), (0, 0));
+        assert_eq!(lut.map(150), 128);
+        assert_eq!((lut.map(200), lut.map(u16::MAX)), (255, 255));
+    }
+
+    #[test]
+    fn empty_window_does_not_divide_by_zero() {
+        let lut = DisplayLut::new(&levels(100, 100));
+        assert_eq!((lut.map(99), lut.map(101)), (0, 255));
+    }
+
+    #[test]
+    fn gamma_invert_contrast_and_brightness() {
+        let gamma = DisplayLut::new(&AdjustmentLevels { gamma: 2., ..levels(0, 200) });
+        assert_eq!(gamma.map(100), (0.5f32.sqrt() * 255.).round() as u8);
+
+        let inverted = DisplayLut::new(&AdjustmentLevels { invert: true, ..levels(0, 200) });
+        assert_eq!((inverted.map(0), inverted.map(200)), (255, 0));
+
+        let flat = DisplayLut::new(&AdjustmentLevels { contrast: Percentage::try_from(0).unwrap(), ..levels(0, 200) });
+        assert!([0, 100, 200].iter().all(|&value| flat.map(value) == 128));
+
+        let dark = DisplayLut::new(&AdjustmentLevels { brightness: Percentage::try_from(0).unwrap(), ..levels(0, 200) });
+        assert_eq!(dark.map(200), 0);
+    }
+
+    #[test]
+    fn apply_maps_every_pixel() {
+        let frame = ImageFrame::from_raw(3, 1, vec![100, 150, 300]).unwrap();
+        let display = DisplayLut::new(&levels(100, 200)).apply(&frame);
+        assert_eq!(display.dimensions(), (3, 1));
+        assert_eq!(display.into_raw(), [0, 128, 255]);
+    }
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+
+use super::misc::ImageFrame;
+
+const VALUE_COUNT: usize = u16::MAX as usize + 1;
+
+// Counts at full 16-bit resolution, binning only happens when the histogram is sent for display
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u32>,
+    total: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct HistogramData {
+    pub first_value: u32,
+    pub bin_width: u32,
+    pub counts: Vec<u32>,
+    pub min_value: u32,
+    pub max_value: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub enum AutoStretch {
+    MinMax,
+    Percentile1To99,
+    Percentile01To999,
+}
+
+impl AutoStretch {
+    pub fn percentiles(&self) -> (f64, f64) {
+        match self {
+            AutoStretch::MinMax => (0., 100.),
+            AutoStretch::Percentile1To99 => (1., 99.),
+            AutoStretch::Percentile01To999 => (0.1, 99.9),
+        }
+    }
+}
+
+impl Histogram {
+    pub fn from_values(values: impl IntoIterator<Item = u16>) -> Self {
+        let mut counts = vec![0u32; VALUE_COUNT];
+        let mut total = 0;
+        for value in values {
+            counts[value as usize] += 1;
+            total += 1;
+        }
+        Self { counts, total }
+    }
+
+    pub fn from_image(image: &ImageFrame) -> Self {
+        Self::from_values(image.as_raw().iter().copied())
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    pub fn min_value(&self) -> Option<u16> {
+        self.counts.iter().position(|&count| count > 0).map(|value| value as u16)
+    }
+
+    pub fn max_value(&self) -> Option<u16> {
+        self.counts.iter().rposition(|&count| count > 0).map(|value| value as u16)
+    }
+
+    // The value below which the given percentage (0-100) of pixels fall
+    pub fn percentile(&self, percentile: f64) -> Option<u16> {
+        if self.total == 0 {
+            return None;
+        }
+        let rank = ((percentile.clamp(0., 100.) / 100.) * (self.total - 1) as f64).round() as u64;
+
+        let mut cumulative = 0;
+        for (value, &count) in self.counts.iter().enumerate() {
+            cumulative += count as u64;
+            if cumulative > rank {
+                return Some(value as u16);
+            }
+        }
+        None
+    }
+
+    pub fn percentile_window(&self, low_percentile: f64, high_percentile: f64) -> Option<(u32, u32)> {
+        let low = self.percentile(low_percentile.min(high_percentile))?;
+        let high = self.percentile(high_percentile.max(low_percentile))?;
+        Some((low as u32, high as u32))
+    }
+
+    pub fn auto_stretch(&self, preset: AutoStretch) -> Option<(u32, u32)> {
+        let (low_percentile, high_percentile) = preset.percentiles();
+        self.percentile_window(low_percentile, high_percentile)
+    }
+
+    // Spreads bin_count bins over the occupied value range
+    pub fn binned(&self, bin_count: u32) -> HistogramData {
+        let (Some(min_value), Some(max_value)) = (self.min_value(), self.max_value()) else {
+            return HistogramData { first_value: 0, bin_width: 1, counts: Vec::new(), min_value: 0, max_value: 0 };
+        };
+        let (min_value, max_value) = (min_value as u32, max_value as u32);
+        let bin_width = (max_value - min_value + 1).div_ceil(bin_count.max(1));
+
+        let counts = self.counts[min_value as usize..=max_value as usize]
+            .chunks(bin_width as usize)
+            .map(|bin| bin.iter().sum())
+            .collect();
+
+        HistogramData { first_value: min_value, bin_width, counts, min_value, max_value }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Mostly mid-grey with a single dead and a single hot pixel
+    fn with_outliers() -> Histogram {
+        Histogram::from_values([0, u16::MAX].into_iter().chain([500; 998]))
+    }
+
+    #[test]
+    fn percentiles_are_taken_over_the_values() {
+        let histogram = Histogram::from_values(0..100);
+        assert_eq!(histogram.total(), 100);
+        assert_eq!(histogram.percentile(0.), Some(0));
+        assert_eq!(histogram.percentile(50.), Some(50));
+        assert_eq!(histogram.percentile(100.), Some(99));
+        assert_eq!(histogram.percentile(150.), Some(99));
+    }
+
+    #[test]
+    fn empty_histogram_has_no_window() {
+        let histogram = Histogram::from_values([]);
+        assert_eq!(histogram.percentile(50.), None);
+        assert_eq!(histogram.auto_stretch(AutoStretch::MinMax), None);
+        assert!(histogram.binned(16).counts.is_empty());
+    }
+
+    #[test]
+    fn auto_stretch_presets_ignore_outliers() {
+        let histogram = with_outliers();
+        assert_eq!(histogram.auto_stretch(AutoStretch::MinMax), Some((0, u16::MAX as u32)));
+        assert_eq!(histogram.auto_stretch(AutoStretch::Percentile1To99), Some((500, 500)));
+        assert_eq!(histogram.auto_stretch(AutoStretch::Percentile01To999), Some((500, 500)));
+    }
+
+    #[test]
+    fn percentile_window_accepts_swapped_bounds() {
+        let histogram = Histogram::from_values(0..100);
+        assert_eq!(histogram.percentile_window(90., 10.), histogram.percentile_window(10., 90.));
+    }
+
+    #[test]
+    fn bins_cover_the_occupied_range() {
+        let binned = Histogram::from_values(10..20).binned(5);
+        assert_eq!((binned.first_value, binned.bin_width, binned.min_value, binned.max_value), (10, 2, 10, 19));
+        assert_eq!(binned.counts, [2; 5]);
+
+        // A last partial bin still counts its values
+        let binned = Histogram::from_values(10..21).binned(5);
+        assert_eq!(binned.bin_width, 3);
+        assert_eq!(binned.counts, [3, 3, 3, 2]);
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub struct RegionOfInterest {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Settings shared by every frame of an acquisition
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct AcquisitionMetadata {
+    pub detector_id: Option<String>,
+    pub exposure_time_ms: Option<u32>,
+    pub exposure_mode: Option<String>,
+    pub full_well_mode: Option<String>,
+    pub roi: Option<RegionOfInterest>,
+    pub dds_on: Option<bool>,
+    pub temperature: Option<f32>,
+    pub pixel_pitch_mm: Option<f64>,
+    pub corrections: Vec<String>,
+}
+
+// Mirrors the per-frame fields of the detector's SLBufferInfo
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, specta::Type)]
+pub struct FrameMetadata {
+    pub frame_count: u32,
+    pub block_id: u64,
+    pub timestamp: u64,
+    pub missing_packets: u32,
+}
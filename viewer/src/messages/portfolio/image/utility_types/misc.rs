@@ -1,8 +1,15 @@
+use std::any::Any;
+
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 use glam::IVec2;
+use image::{ImageBuffer, Luma};
+
+use crate::messages::portfolio::image::ImageMessageHandler;
+
+use super::metadata::FrameMetadata;
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct Percentage(u32);
 
 impl TryFrom<u32> for Percentage {
@@ -23,24 +30,68 @@ impl Percentage {
     }
 }
 
-#[derive(Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct AdjustmentLevels {
     pub min: u32,
     pub max: u32,
     pub brightness: Percentage,
-    pub contrast: Percentage
+    pub contrast: Percentage,
+    pub gamma: f32,
+    pub invert: bool,
+}
+
+impl Default for AdjustmentLevels {
+    fn default() -> Self {
+        Self {
+            min: 0,
+            max: u16::MAX as u32,
+            brightness: Percentage(100),
+            contrast: Percentage(50),
+            gamma: 1.,
+            invert: false,
+        }
+    }
 }
 
 pub trait Command {
-    fn execute(&mut self);
-    fn undo(&self);
+    fn execute(&mut self, image: &mut ImageMessageHandler);
+    fn undo(&mut self, image: &mut ImageMessageHandler);
+
+    // Folds a command issued during the same transaction into this one, returns false if they can't be combined
+    fn merge(&mut self, _next: &dyn Command) -> bool {
+        false
+    }
+
+    fn as_any(&self) -> &dyn Any;
 }
 
-#[derive(Copy, Clone, Serialize, Deserialize, specta::Type)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
 pub struct ImagePosition(pub IVec2);
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, specta::Type)]
 pub struct ImageId(pub Uuid);
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, specta::Type)]
-pub struct AnnotationId(pub Uuid);
\ No newline at end of file
+pub struct AnnotationId(pub Uuid);
+
+pub type ImageFrame = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+// A frame as carried inside messages, for example when it arrives from the detector
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct RawFrame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u16>,
+    pub metadata: FrameMetadata,
+}
+
+impl RawFrame {
+    // None if the data doesn't match the dimensions
+    pub fn into_frame(self) -> Option<(ImageFrame, FrameMetadata)> {
+        // from_raw alone would accept a buffer longer than the dimensions need
+        if self.data.len() != self.width as usize * self.height as usize {
+            return None;
+        }
+        ImageFrame::from_raw(self.width, self.height, self.data).map(|frame| (frame, self.metadata))
+    }
+}
@@ -1,3 +1,10 @@
 pub mod annotations;
 pub mod command;
-pub mod misc;
\ No newline at end of file
+pub mod display;
+pub mod histogram;
+pub mod metadata;
+pub mod misc;
+pub mod navigation;
+pub mod playback;
+pub mod profile;
+pub mod statistics;
\ No newline at end of file
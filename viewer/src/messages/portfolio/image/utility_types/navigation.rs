@@ -0,0 +1,185 @@
+use glam::{DAffine2, DMat2, DVec2};
+use serde::{Deserialize, Serialize};
+
+use crate::consts::{VIEWPORT_ZOOM_MAX, VIEWPORT_ZOOM_MIN};
+use crate::messages::input_mapper::utility_types::input_mouse::ViewportPosition;
+
+// How an image is placed in the viewport. The image is flipped and rotated about its centre, scaled by the zoom and
+// its centre drawn at the pan position, so rotating or flipping never moves the image.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct ViewportTransform {
+    // Viewport position of the image's centre
+    pub pan: ViewportPosition,
+    pub zoom: f64,
+    // Clockwise, in quarter turns
+    pub rotation: u8,
+    pub flip_horizontal: bool,
+    pub flip_vertical: bool,
+}
+
+impl ViewportTransform {
+    // Unscaled with the image's top left corner at the viewport's origin
+    pub fn new(image_size: DVec2) -> Self {
+        Self {
+            pan: image_size / 2.,
+            zoom: 1.,
+            rotation: 0,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+
+    // Maps image pixel coordinates to viewport coordinates
+    pub fn image_to_viewport(&self, image_size: DVec2) -> DAffine2 {
+        // Quarter turns are built from exact matrices so pixel boundaries don't pick up rounding errors
+        let rotation = match self.rotation % 4 {
+            0 => DMat2::IDENTITY,
+            1 => DMat2::from_cols(DVec2::Y, -DVec2::X),
+            2 => -DMat2::IDENTITY,
+            _ => DMat2::from_cols(-DVec2::Y, DVec2::X),
+        };
+        let flip = DVec2::new(if self.flip_horizontal { -1. } else { 1. }, if self.flip_vertical { -1. } else { 1. });
+
+        DAffine2::from_translation(self.pan)
+            * DAffine2::from_scale(DVec2::splat(self.zoom))
+            * DAffine2::from_mat2(rotation)
+            * DAffine2::from_scale(flip)
+            * DAffine2::from_translation(-image_size / 2.)
+    }
+
+    pub fn viewport_to_image(&self, image_size: DVec2) -> DAffine2 {
+        self.image_to_viewport(image_size).inverse()
+    }
+
+    // Changes the zoom while keeping the image point under the pivot in place
+    pub fn zoom_around(&mut self, zoom: f64, pivot: ViewportPosition) {
+        let zoom = zoom.clamp(VIEWPORT_ZOOM_MIN, VIEWPORT_ZOOM_MAX);
+        self.pan = pivot + (self.pan - pivot) * (zoom / self.zoom);
+        self.zoom = zoom;
+    }
+
+    // Clockwise for positive turns
+    pub fn rotate(&mut self, quarter_turns: i32) {
+        self.rotation = (self.rotation as i32 + quarter_turns).rem_euclid(4) as u8;
+    }
+
+    // Flips are applied before the rotation, so flipping along the viewport's axes depends on how the image is turned
+    pub fn flip(&mut self, horizontal: bool) {
+        if horizontal == (self.rotation % 2 == 0) {
+            self.flip_horizontal = !self.flip_horizontal;
+        } else {
+            self.flip_vertical = !self.flip_vertical;
+        }
+    }
+
+    // Centres the whole image in the viewport as large as it fits, leaves the transform as it is while the viewport has no size
+    pub fn fit(&mut self, image_size: DVec2, viewport_size: DVec2) {
+        let rotated_size = if self.rotation % 2 == 0 { image_size } else { DVec2::new(image_size.y, image_size.x) };
+        if viewport_size.min_element() <= 0. || rotated_size.min_element() <= 0. {
+            return;
+        }
+        self.zoom = (viewport_size / rotated_size).min_element().clamp(VIEWPORT_ZOOM_MIN, VIEWPORT_ZOOM_MAX);
+        self.pan = viewport_size / 2.;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_SIZE: DVec2 = DVec2::new(400., 300.);
+
+    fn assert_near(actual: DVec2, expected: DVec2) {
+        assert!(actual.abs_diff_eq(expected, 1e-9), "{actual} != {expected}");
+    }
+
+    // Every combination of quarter turn and flip, panned and zoomed away from the defaults
+    fn orientations() -> impl Iterator<Item = ViewportTransform> {
+        (0..4).flat_map(|rotation| {
+            [(false, false), (true, false), (false, true), (true, true)].map(|(flip_horizontal, flip_vertical)| ViewportTransform {
+                pan: DVec2::new(120., -35.),
+                zoom: 2.5,
+                rotation,
+                flip_horizontal,
+                flip_vertical,
+            })
+        })
+    }
+
+    #[test]
+    fn viewport_to_image_undoes_image_to_viewport() {
+        for transform in orientations() {
+            let forward = transform.image_to_viewport(IMAGE_SIZE);
+            let inverse = transform.viewport_to_image(IMAGE_SIZE);
+            for point in [DVec2::ZERO, IMAGE_SIZE, DVec2::new(17., 250.)] {
+                assert_near(inverse.transform_point2(forward.transform_point2(point)), point);
+            }
+            // The centre stays at the pan position whatever the orientation
+            assert_near(forward.transform_point2(IMAGE_SIZE / 2.), transform.pan);
+        }
+    }
+
+    #[test]
+    fn quarter_turns_are_exact() {
+        let mut transform = ViewportTransform::new(IMAGE_SIZE);
+        transform.rotate(1);
+        // The top left corner ends up top right, with the image turned around its centre
+        let corner = transform.image_to_viewport(IMAGE_SIZE).transform_point2(DVec2::ZERO);
+        assert_eq!(corner, DVec2::new(350., -50.));
+        transform.rotate(-5);
+        assert_eq!(transform.rotation, 0);
+    }
+
+    #[test]
+    fn flips_mirror_along_the_viewport_axes() {
+        for mut transform in orientations() {
+            let before = transform.image_to_viewport(IMAGE_SIZE);
+            transform.flip(true);
+            let after = transform.image_to_viewport(IMAGE_SIZE);
+            for point in [DVec2::ZERO, DVec2::new(17., 250.)] {
+                let (before, after) = (before.transform_point2(point), after.transform_point2(point));
+                assert_near(DVec2::new(2. * transform.pan.x - after.x, after.y), before);
+            }
+        }
+    }
+
+    #[test]
+    fn zooming_keeps_the_pivot_fixed() {
+        for mut transform in orientations() {
+            let pivot = DVec2::new(310., 42.);
+            let under_pivot = transform.viewport_to_image(IMAGE_SIZE).transform_point2(pivot);
+            transform.zoom_around(7., pivot);
+            assert_eq!(transform.zoom, 7.);
+            assert_near(transform.image_to_viewport(IMAGE_SIZE).transform_point2(under_pivot), pivot);
+        }
+
+        let mut transform = ViewportTransform::new(IMAGE_SIZE);
+        transform.zoom_around(1000., DVec2::ZERO);
+        assert_eq!(transform.zoom, VIEWPORT_ZOOM_MAX);
+    }
+
+    #[test]
+    fn fit_centres_the_whole_image() {
+        let viewport_size = DVec2::new(800., 400.);
+        for mut transform in orientations() {
+            transform.fit(IMAGE_SIZE, viewport_size);
+            let forward = transform.image_to_viewport(IMAGE_SIZE);
+            let corners = [DVec2::ZERO, DVec2::new(IMAGE_SIZE.x, 0.), DVec2::new(0., IMAGE_SIZE.y), IMAGE_SIZE].map(|corner| forward.transform_point2(corner));
+            let min = corners.into_iter().reduce(DVec2::min).unwrap();
+            let max = corners.into_iter().reduce(DVec2::max).unwrap();
+            // Inside the viewport, centred and touching its edges along one axis
+            assert!(min.cmpge(DVec2::splat(-1e-9)).all() && max.cmple(viewport_size + 1e-9).all(), "{min} {max}");
+            assert_near((min + max) / 2., viewport_size / 2.);
+            assert!((max - min - viewport_size).abs().min_element() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn fit_ignores_an_empty_viewport() {
+        let mut transform = ViewportTransform::new(IMAGE_SIZE);
+        transform.zoom_around(3., DVec2::ZERO);
+        let before = transform;
+        transform.fit(IMAGE_SIZE, DVec2::new(0., 400.));
+        assert_eq!(transform, before);
+    }
+}
@@ -0,0 +1,43 @@
+// Playback is driven by timestamps from the frontend's animation frames, so it stays in step with what is drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct Playback {
+    pub playing: bool,
+    pub fps: f64,
+    pub looping: bool,
+    last_tick_ms: Option<f64>,
+    // Fraction of a frame carried over between ticks
+    pending_frames: f64,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            playing: false,
+            fps: 10.,
+            looping: true,
+            last_tick_ms: None,
+            pending_frames: 0.,
+        }
+    }
+}
+
+impl Playback {
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+        self.last_tick_ms = None;
+        self.pending_frames = 0.;
+    }
+
+    // Number of frames to advance since the previous tick
+    pub fn tick(&mut self, timestamp_ms: f64) -> usize {
+        if !self.playing {
+            return 0;
+        }
+        let Some(last_tick_ms) = self.last_tick_ms.replace(timestamp_ms) else { return 0 };
+
+        self.pending_frames += (timestamp_ms - last_tick_ms).max(0.) / 1000. * self.fps;
+        let frames = self.pending_frames.floor();
+        self.pending_frames -= frames;
+        frames as usize
+    }
+}
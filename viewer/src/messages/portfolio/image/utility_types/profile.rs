@@ -0,0 +1,60 @@
+use glam::DVec2;
+use serde::{Deserialize, Serialize};
+
+use super::misc::ImageFrame;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct LineProfile {
+    // Distance of each sample from the start of the line in pixels
+    pub distances: Vec<f64>,
+    pub values: Vec<f64>,
+    pub width: u32,
+    pub pixel_pitch_mm: Option<f64>,
+}
+
+impl LineProfile {
+    // Samples once per pixel of line length, averaging `width` parallel lines one pixel apart
+    pub fn sample(image: &ImageFrame, start: DVec2, end: DVec2, width: u32, pixel_pitch_mm: Option<f64>) -> Self {
+        let length = start.distance(end);
+        let sample_count = length.ceil() as usize + 1;
+        let direction = if length > 0. { (end - start) / length } else { DVec2::X };
+        let normal = direction.perp();
+        let width = width.max(1);
+        let first_offset = -((width - 1) as f64) / 2.;
+
+        let mut distances = Vec::with_capacity(sample_count);
+        let mut values = Vec::with_capacity(sample_count);
+        for index in 0..sample_count {
+            let distance = if sample_count > 1 { length * index as f64 / (sample_count - 1) as f64 } else { 0. };
+            let centre = start + direction * distance;
+
+            let samples: Vec<f64> = (0..width)
+                .filter_map(|line| sample_bilinear(image, centre + normal * (first_offset + line as f64)))
+                .collect();
+            if samples.is_empty() {
+                continue;
+            }
+            distances.push(distance);
+            values.push(samples.iter().sum::<f64>() / samples.len() as f64);
+        }
+
+        Self { distances, values, width, pixel_pitch_mm }
+    }
+}
+
+// Pixel values sit at integer coordinates, positions outside the image give None
+pub fn sample_bilinear(image: &ImageFrame, position: DVec2) -> Option<f64> {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 || position.x < 0. || position.y < 0. || position.x > (width - 1) as f64 || position.y > (height - 1) as f64 {
+        return None;
+    }
+
+    let (x0, y0) = (position.x.floor() as u32, position.y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (position.x - x0 as f64, position.y - y0 as f64);
+    let value = |x, y| image.get_pixel(x, y).0[0] as f64;
+
+    let top = value(x0, y0) * (1. - fx) + value(x1, y0) * fx;
+    let bottom = value(x0, y1) * (1. - fx) + value(x1, y1) * fx;
+    Some(top * (1. - fy) + bottom * fy)
+}
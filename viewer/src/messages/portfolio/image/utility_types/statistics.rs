@@ -0,0 +1,87 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, specta::Type)]
+pub struct RoiStatistics {
+    pub pixel_count: u32,
+    pub sum: f64,
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: u16,
+    pub max: u16,
+    pub median: f64,
+    // None when the region is perfectly flat
+    pub snr: Option<f64>,
+    pub area_px: f64,
+    pub area_mm2: Option<f64>,
+}
+
+impl RoiStatistics {
+    // The standard deviation is the sample standard deviation, matching what noise measurements expect
+    pub fn from_values(mut values: Vec<u16>, area_px: f64, pixel_pitch_mm: Option<f64>) -> Option<Self> {
+        if values.is_empty() {
+            return None;
+        }
+
+        let pixel_count = values.len();
+        let sum: f64 = values.iter().map(|&value| value as f64).sum();
+        let mean = sum / pixel_count as f64;
+        let squared_deviations: f64 = values.iter().map(|&value| (value as f64 - mean).powi(2)).sum();
+        let std_dev = if pixel_count > 1 { (squared_deviations / (pixel_count - 1) as f64).sqrt() } else { 0. };
+
+        let middle = pixel_count / 2;
+        let (lower, &mut upper_middle, _) = values.select_nth_unstable(middle);
+        let median = if pixel_count % 2 == 0 {
+            let lower_middle = *lower.iter().max().unwrap();
+            (lower_middle as f64 + upper_middle as f64) / 2.
+        } else {
+            upper_middle as f64
+        };
+
+        Some(Self {
+            pixel_count: pixel_count as u32,
+            sum,
+            mean,
+            std_dev,
+            min: *values.iter().min().unwrap(),
+            max: *values.iter().max().unwrap(),
+            median,
+            snr: (std_dev > 0.).then(|| mean / std_dev),
+            area_px,
+            area_mm2: pixel_pitch_mm.map(|pitch| area_px * pitch * pitch),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statistics_of_an_even_count() {
+        let statistics = RoiStatistics::from_values(vec![4, 1, 3, 2], 4., Some(0.1)).unwrap();
+        assert_eq!((statistics.pixel_count, statistics.sum, statistics.mean), (4, 10., 2.5));
+        assert_eq!((statistics.min, statistics.max, statistics.median), (1, 4, 2.5));
+        assert!((statistics.std_dev - (5f64 / 3.).sqrt()).abs() < 1e-12);
+        assert!((statistics.snr.unwrap() - 2.5 / (5f64 / 3.).sqrt()).abs() < 1e-12);
+        assert!((statistics.area_mm2.unwrap() - 0.04).abs() < 1e-12);
+    }
+
+    #[test]
+    fn median_of_an_odd_count_is_the_middle_value() {
+        let statistics = RoiStatistics::from_values(vec![5, 1, 3], 3., None).unwrap();
+        assert_eq!(statistics.median, 3.);
+        assert_eq!(statistics.area_mm2, None);
+    }
+
+    #[test]
+    fn flat_region_has_no_snr() {
+        let statistics = RoiStatistics::from_values(vec![7], 1., None).unwrap();
+        assert_eq!((statistics.std_dev, statistics.snr), (0., None));
+        assert_eq!(RoiStatistics::from_values(vec![7; 5], 5., None).unwrap().snr, None);
+    }
+
+    #[test]
+    fn empty_region_has_no_statistics() {
+        assert_eq!(RoiStatistics::from_values(Vec::new(), 0., None), None);
+    }
+}
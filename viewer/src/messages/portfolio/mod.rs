@@ -3,5 +3,5 @@ mod portfolio_message_handler;
 
 pub mod image;
 
-pub use portfolio_message::PortfolioMessage;
+pub use portfolio_message::{AnalysisExportFormat, PortfolioMessage, ViewportSync};
 pub use portfolio_message_handler::PortfolioMessageHandler;
\ No newline at end of file
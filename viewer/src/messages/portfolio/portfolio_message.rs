@@ -1,10 +1,129 @@
+use std::path::PathBuf;
+
 use serde::{Deserialize, Serialize};
 
-use super::image::utility_types::misc::ImageId;
+use crate::analysis::aggregate::FrameAggregate;
+use crate::analysis::nps::NpsOptions;
+use crate::io::dicom::DicomExportOptions;
+
+use super::image::ImageMessage;
+use super::image::utility_types::metadata::AcquisitionMetadata;
+use super::image::utility_types::misc::{AnnotationId, ImageId, RawFrame};
+
+#[derive(PartialEq, Clone, Copy, Debug, Serialize, Deserialize, specta::Type)]
+pub enum AnalysisExportFormat {
+    Csv,
+    Json,
+}
+
+// What is kept the same across the images shown side by side
+#[derive(PartialEq, Clone, Copy, Debug, Default, Serialize, Deserialize, specta::Type)]
+pub struct ViewportSync {
+    pub navigation: bool,
+    pub window_level: bool,
+}
 
-#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum PortfolioMessage {
+    // Child messages, sent to the given image or the active one if None
+    Image {
+        image_id: Option<ImageId>,
+        message: ImageMessage
+    },
+    // Child messages for the image shown in the viewport, dropped if it is empty
+    Viewport {
+        viewport: u32,
+        message: ImageMessage
+    },
+    // Adds the aggregate of frames first_frame..=last_frame as a new image
+    AggregateFrames {
+        image_id: ImageId,
+        first_frame: u32,
+        last_frame: u32,
+        aggregate: FrameAggregate
+    },
+    // Analyses frames first_frame..=last_frame of the image, which should be flat fields
+    AnalyseNoisePowerSpectrum {
+        image_id: ImageId,
+        first_frame: u32,
+        last_frame: u32,
+        options: NpsOptions
+    },
+    // Sent when a capture starts, live frames that follow are shown with this acquisition's settings
+    BeginLiveCapture {
+        acquisition: AcquisitionMetadata,
+        // Sequence frames are collected in the live image instead of replacing each other
+        keep_frames: bool
+    },
+    CloseAllImages,
+    CloseImage {
+        image_id: ImageId
+    },
+    // Combines the latest noise analysis with the MTF last measured on the annotation
+    ComputeDqe {
+        image_id: ImageId,
+        annotation_id: AnnotationId,
+        fluence_per_mm2: f64
+    },
+    DuplicateImage {
+        image_id: ImageId
+    },
+    // A finished sequence stays open as an image of its own
+    EndLiveCapture,
+    ExportDicom {
+        image_id: ImageId,
+        path: PathBuf,
+        options: DicomExportOptions
+    },
+    ExportNoiseAnalysis {
+        path: PathBuf,
+        format: AnalysisExportFormat
+    },
+    // Shown in the live image, which is opened on the first frame
+    LiveFrame {
+        frame: RawFrame
+    },
+    NewImageFromAcquisition {
+        name: Option<String>,
+        acquisition: AcquisitionMetadata,
+        frames: Vec<RawFrame>
+    },
+    OpenImage {
+        path: PathBuf
+    },
+    RenameImage {
+        image_id: ImageId,
+        name: String
+    },
+    SaveImage {
+        image_id: ImageId,
+        path: PathBuf
+    },
+    // Shows the image in the active viewport
     SelectImage {
         image_id: ImageId
-    }
+    },
+    // Makes the viewport active, along with the image it shows
+    SelectViewport {
+        viewport: u32
+    },
+    // Number of frames in the rolling average applied to live frames, 1 shows every frame as it arrives
+    SetLiveAveraging {
+        frames: u32
+    },
+    // Sent by the input mapper when the layout changes, the viewports past the count are gone along with the images they showed
+    SetViewportCount {
+        count: u32
+    },
+    SetViewportSync {
+        sync: ViewportSync
+    },
+    ShowImageInViewport {
+        viewport: u32,
+        image_id: ImageId
+    },
+    // The detector learned more about itself during the capture, such as a new temperature reading
+    UpdateLiveAcquisition {
+        acquisition: AcquisitionMetadata
+    },
 }
\ No newline at end of file
@@ -1,24 +1,411 @@
+use std::path::Path;
+
+use glam::DVec2;
+use serde::Serialize;
+
+use crate::analysis::aggregate::{self, RollingAverage};
+use crate::analysis::nps::{self, DqeResult, NpsResult};
+use crate::utility_functions::generate_uuid;
+use crate::io::{self, csv, dicom, tiff, ImageIoError, ImageStack};
 use crate::utility_traits::MessageHandler;
 use crate::messages::prelude::*;
-use super::image::utility_types::misc::ImageId;
+use crate::messages::frontend::utility_types::FrontendImageDetails;
+use crate::messages::input_mapper::utility_types::input_mouse::ViewportBounds;
+use super::image::utility_types::metadata::AcquisitionMetadata;
+use super::image::utility_types::misc::{ImageFrame, ImageId, RawFrame};
+use super::{AnalysisExportFormat, ViewportSync};
 
+#[derive(Default)]
 pub struct PortfolioMessageHandler {
     images: HashMap<ImageId, ImageMessageHandler>,
     image_ids: Vec<ImageId>,
-    active_image_id: Option<ImageId>
+    active_image_id: Option<ImageId>,
+    // The latest noise analysis, kept so DQE can be computed once an MTF is measured and so both can be exported
+    noise_power_spectrum: Option<NpsResult>,
+    dqe: Option<DqeResult>,
+    live_averaging: Option<RollingAverage>,
+    live_image_id: Option<ImageId>,
+    live_acquisition: AcquisitionMetadata,
+    live_keep_frames: bool,
+    // Frames received since the capture began
+    live_frames: usize,
+    // The image shown in each viewport, the active image is the one in the active viewport
+    viewport_images: Vec<Option<ImageId>>,
+    active_viewport: u32,
+    viewport_sync: ViewportSync,
 }
 
-impl MessageHandler<PortfolioMessage, ()> for PortfolioMessageHandler {
-    fn process_message(&mut self, message: PortfolioMessage, responses: &mut VecDeque<Message>, data: ()) {
+#[derive(Serialize)]
+struct NoiseAnalysisExport<'a> {
+    nps: &'a NpsResult,
+    dqe: Option<&'a DqeResult>,
+}
+
+// Images are shown in the viewports the input mapper keeps the bounds of
+impl<'a> MessageHandler<PortfolioMessage, &'a InputMapperMessageHandler> for PortfolioMessageHandler {
+    fn process_message(&mut self, message: PortfolioMessage, responses: &mut VecDeque<Message>, input: &'a InputMapperMessageHandler) {
         match message {
+            PortfolioMessage::Image { image_id, message } => {
+                let Some(image_id) = image_id.or(self.active_image_id) else { return };
+                self.process_image_message(image_id, message, responses, input);
+            }
+            PortfolioMessage::Viewport { viewport, message } => {
+                let Some(image_id) = self.viewport_image(viewport) else { return };
+                self.process_image_message(image_id, message, responses, input);
+            }
+            PortfolioMessage::AggregateFrames { image_id, first_frame, last_frame, aggregate } => {
+                let Some(image) = self.image(image_id) else { return };
+                let frames = image.frames().get(first_frame as usize..=last_frame as usize).unwrap_or_default();
+                match aggregate::aggregate_frames(frames, aggregate) {
+                    Ok(aggregated) => {
+                        let mut acquisition = image.acquisition_metadata().clone();
+                        acquisition.corrections.push(match aggregated.divisor {
+                            1 => format!("{aggregate:?} of frames {first_frame}-{last_frame}"),
+                            divisor => format!("{aggregate:?} of frames {first_frame}-{last_frame} divided by {divisor}"),
+                        });
+                        let mut aggregate_image = ImageMessageHandler::new(aggregated.frame, acquisition, Default::default());
+                        aggregate_image.set_name(format!("{} {aggregate:?}", image.name()));
+                        self.add_image(aggregate_image);
+                        self.send_open_images(responses);
+                    }
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to combine frames: {err}") }),
+                }
+            }
+            PortfolioMessage::AnalyseNoisePowerSpectrum { image_id, first_frame, last_frame, options } => {
+                let Some(image) = self.image(image_id) else { return };
+                let frames = image.frames().get(first_frame as usize..=last_frame as usize).unwrap_or_default();
+                match nps::noise_power_spectrum(frames, &options, image.acquisition_metadata().pixel_pitch_mm) {
+                    Ok(result) => {
+                        responses.add(FrontendMessage::UpdateNoisePowerSpectrum { result: result.clone() });
+                        self.noise_power_spectrum = Some(result);
+                        self.dqe = None;
+                    }
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("Noise analysis of {} failed: {err}", image.name()) }),
+                }
+            }
+            PortfolioMessage::BeginLiveCapture { acquisition, keep_frames } => {
+                // A sequence whose end never arrived keeps the frames it collected instead of being overwritten
+                self.end_live_capture(responses);
+                if let Some(rolling_average) = &mut self.live_averaging {
+                    rolling_average.reset();
+                }
+                self.live_acquisition = acquisition;
+                self.live_keep_frames = keep_frames;
+                self.live_frames = 0;
+            }
+            PortfolioMessage::CloseAllImages => {
+                self.images.clear();
+                self.image_ids.clear();
+                self.active_image_id = None;
+                self.viewport_images.fill(None);
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::CloseImage { image_id } => {
+                let Some(index) = self.image_ids.iter().position(|id| *id == image_id) else { return };
+                self.image_ids.remove(index);
+                self.images.remove(&image_id);
+                // Activate the neighbouring image, as tabbed editors do
+                if self.active_image_id == Some(image_id) {
+                    self.active_image_id = self.image_ids.get(index.min(self.image_ids.len().saturating_sub(1))).copied();
+                }
+                for viewport_image in &mut self.viewport_images {
+                    if *viewport_image == Some(image_id) {
+                        *viewport_image = None;
+                    }
+                }
+                if self.viewport_image(self.active_viewport).is_none() {
+                    self.show_image(self.active_viewport, self.active_image_id);
+                }
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::ComputeDqe { image_id, annotation_id, fluence_per_mm2 } => {
+                let Some(noise_power_spectrum) = &self.noise_power_spectrum else {
+                    responses.add(FrontendMessage::DisplayDialog { title: "Analyse a flat-field stack before computing DQE".into() });
+                    return;
+                };
+                let Some(mtf) = self.image(image_id).and_then(|image| image.mtf_result(annotation_id)) else {
+                    responses.add(FrontendMessage::DisplayDialog { title: "Measure the MTF of an edge before computing DQE".into() });
+                    return;
+                };
+                match nps::detective_quantum_efficiency(noise_power_spectrum, mtf, fluence_per_mm2) {
+                    Ok(result) => {
+                        responses.add(FrontendMessage::UpdateDqe { result: result.clone() });
+                        self.dqe = Some(result);
+                    }
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("DQE calculation failed: {err}") }),
+                }
+            }
+            PortfolioMessage::DuplicateImage { image_id } => {
+                let Some(image) = self.image(image_id) else { return };
+                let mut duplicate = image.duplicate();
+                duplicate.set_name(format!("{} copy", image.name()));
+                self.add_image(duplicate);
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::EndLiveCapture => self.end_live_capture(responses),
+            PortfolioMessage::ExportDicom { image_id, path, options } => {
+                let Some(image) = self.image(image_id) else { return };
+                if let Err(err) = dicom::save_dicom(&path, image.image_buffer(), image.adjustment_levels(), image.acquisition_metadata(), &options) {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to export {}: {err}", path.display()) });
+                }
+            }
+            PortfolioMessage::ExportNoiseAnalysis { path, format } => {
+                let Some(nps) = &self.noise_power_spectrum else { return };
+                let result = match format {
+                    AnalysisExportFormat::Csv => csv::save_noise_analysis(&path, nps, self.dqe.as_ref()),
+                    AnalysisExportFormat::Json => io::save_json(&path, &NoiseAnalysisExport { nps, dqe: self.dqe.as_ref() }),
+                };
+                if let Err(err) = result {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to export {}: {err}", path.display()) });
+                }
+            }
+            PortfolioMessage::LiveFrame { frame } => {
+                let Some((frame, frame_metadata)) = frame.into_frame() else {
+                    log::warn!("Dropped a live frame that doesn't match its dimensions");
+                    return;
+                };
+                // Sequence frames are kept as they were acquired
+                let frame = if self.live_keep_frames { frame } else { self.process_live_frame(frame) };
+                let first_frame = self.live_frames == 0;
+                self.live_frames += 1;
+                match self.live_image_id.filter(|image_id| self.images.contains_key(image_id)) {
+                    Some(image_id) => {
+                        let image = self.images.get_mut(&image_id).unwrap();
+                        if first_frame {
+                            image.set_acquisition_metadata(self.live_acquisition.clone());
+                            image.set_live_frame(frame, frame_metadata, image_id, responses);
+                        } else if !self.live_keep_frames {
+                            image.set_live_frame(frame, frame_metadata, image_id, responses);
+                        } else if frame.dimensions() == image.image_buffer().dimensions() {
+                            image.push_live_frame(frame, frame_metadata, image_id, responses);
+                        } else {
+                            log::warn!("Dropped a sequence frame with different dimensions to the frames before it");
+                        }
+                    }
+                    None => {
+                        let mut image = ImageMessageHandler::new(frame, self.live_acquisition.clone(), frame_metadata);
+                        image.set_name("Live");
+                        self.live_image_id = Some(self.add_image(image));
+                        self.send_open_images(responses);
+                    }
+                }
+            }
+            PortfolioMessage::NewImageFromAcquisition { name, acquisition, frames } => {
+                let frame_count = frames.len();
+                let (frames, frame_metadata): (Vec<_>, Vec<_>) = frames.into_iter().filter_map(RawFrame::into_frame).unzip();
+                if frames.len() < frame_count {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("{} of {frame_count} frames didn't match their dimensions and were left out", frame_count - frames.len()) });
+                }
+                let Some(mut image) = ImageMessageHandler::from_image_stack(ImageStack { frames, acquisition, frame_metadata }) else {
+                    responses.add(FrontendMessage::DisplayDialog { title: "The acquisition has no frames".into() });
+                    return;
+                };
+                image.set_name(name.unwrap_or_else(|| format!("Acquisition {}", self.image_ids.len() + 1)));
+                self.add_image(image);
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::OpenImage { path } => {
+                match self.open_image(&path) {
+                    Ok(_) => self.send_open_images(responses),
+                    Err(err) => responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to open {}: {err}", path.display()) }),
+                }
+            }
+            PortfolioMessage::RenameImage { image_id, name } => {
+                let Some(image) = self.image_mut(image_id) else { return };
+                image.set_name(name);
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::SaveImage { image_id, path } => {
+                let Some(image) = self.image(image_id) else { return };
+                if let Err(err) = tiff::save_tiff(&path, &image.to_image_stack()) {
+                    responses.add(FrontendMessage::DisplayDialog { title: format!("Failed to save {}: {err}", path.display()) });
+                }
+            }
             PortfolioMessage::SelectImage { image_id } => {
-                self.active_image_id = Some(image_id);
+                self.process_message(PortfolioMessage::ShowImageInViewport { viewport: self.active_viewport, image_id }, responses, input);
+            }
+            PortfolioMessage::SelectViewport { viewport } => {
+                if viewport == self.active_viewport {
+                    return;
+                }
+                self.active_viewport = viewport;
+                // An empty viewport keeps the active image, so there is still something for the tools to work on
+                if let Some(image_id) = self.viewport_image(viewport) {
+                    self.active_image_id = Some(image_id);
+                }
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::SetLiveAveraging { frames } => {
+                self.live_averaging = (frames > 1).then(|| RollingAverage::new(frames as usize));
+            }
+            PortfolioMessage::SetViewportCount { count } => {
+                // There is always an active viewport for new images to be shown in
+                self.viewport_images.truncate(count.max(1) as usize);
+                if self.active_viewport >= count {
+                    self.active_viewport = count.saturating_sub(1);
+                    if let Some(image_id) = self.viewport_image(self.active_viewport) {
+                        self.active_image_id = Some(image_id);
+                    }
+                }
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::SetViewportSync { sync } => {
+                self.viewport_sync = sync;
+                if let Some(image_id) = self.active_image_id {
+                    self.sync_viewports(image_id, responses);
+                }
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::ShowImageInViewport { viewport, image_id } => {
+                if !self.images.contains_key(&image_id) {
+                    return;
+                }
+                self.show_image(viewport, Some(image_id));
+                if viewport == self.active_viewport {
+                    self.active_image_id = Some(image_id);
+                }
+                // The newly shown image takes on the view of the others rather than the other way round
+                if let Some(source_id) = self.viewport_images.iter().flatten().copied().find(|id| *id != image_id) {
+                    self.sync_viewports(source_id, responses);
+                }
+                self.send_open_images(responses);
+            }
+            PortfolioMessage::UpdateLiveAcquisition { acquisition } => {
+                self.live_acquisition = acquisition;
+                // Before the first frame the live image still belongs to the previous capture
+                if self.live_frames == 0 {
+                    return;
+                }
+                if let Some(image) = self.live_image_id.and_then(|image_id| self.images.get_mut(&image_id)) {
+                    image.set_acquisition_metadata(self.live_acquisition.clone());
+                }
             }
         }
     }
 }
 
 impl PortfolioMessageHandler {
+    pub fn add_image(&mut self, image: ImageMessageHandler) -> ImageId {
+        let image_id = ImageId(generate_uuid());
+        self.images.insert(image_id, image);
+        self.image_ids.push(image_id);
+        self.active_image_id = Some(image_id);
+        self.show_image(self.active_viewport, Some(image_id));
+        image_id
+    }
+
+    fn viewport_image(&self, viewport: u32) -> Option<ImageId> {
+        self.viewport_images.get(viewport as usize).copied().flatten()
+    }
+
+    fn show_image(&mut self, viewport: u32, image_id: Option<ImageId>) {
+        let viewport = viewport as usize;
+        if self.viewport_images.len() <= viewport {
+            self.viewport_images.resize(viewport + 1, None);
+        }
+        self.viewport_images[viewport] = image_id;
+    }
+
+    // Navigation is relative to the viewport the image is shown in, the active one if it is in several or none
+    fn process_image_message(&mut self, image_id: ImageId, message: ImageMessage, responses: &mut VecDeque<Message>, input: &InputMapperMessageHandler) {
+        let viewport = match self.viewport_image(self.active_viewport) == Some(image_id) {
+            true => self.active_viewport as usize,
+            false => self.viewport_images.iter().position(|id| *id == Some(image_id)).unwrap_or(self.active_viewport as usize),
+        };
+        let viewport_size = input.viewport_bounds.get(viewport).map_or(DVec2::ZERO, ViewportBounds::size);
+
+        let Some(image) = self.images.get_mut(&image_id) else { return };
+        image.process_message(message, responses, ImageMessageData { image_id, viewport_size });
+        self.sync_viewports(image_id, responses);
+    }
+
+    // The images shown alongside the given one, each once and in viewport order so the messages are sent in a repeatable order
+    fn synced_images(&self, image_id: ImageId) -> Vec<ImageId> {
+        if !self.viewport_images.contains(&Some(image_id)) {
+            return Vec::new();
+        }
+        let mut image_ids = Vec::new();
+        for &target_id in self.viewport_images.iter().flatten() {
+            if target_id != image_id && !image_ids.contains(&target_id) {
+                image_ids.push(target_id);
+            }
+        }
+        image_ids
+    }
+
+    // Brings the other viewports' images to the view and window of the given one, for what is synchronised. Only the
+    // image the change was made on records it, undoing it there brings the others back along.
+    fn sync_viewports(&self, image_id: ImageId, responses: &mut VecDeque<Message>) {
+        let ViewportSync { navigation, window_level } = self.viewport_sync;
+        let Some(source) = self.images.get(&image_id).filter(|_| navigation || window_level) else { return };
+
+        for target_id in self.synced_images(image_id) {
+            let Some(target) = self.images.get(&target_id) else { continue };
+            if navigation && target.viewport_transform() != source.viewport_transform() {
+                let transform = *source.viewport_transform();
+                responses.add(PortfolioMessage::Image { image_id: Some(target_id), message: ImageMessage::SetViewportTransform { transform } });
+            }
+            let (source_levels, target_levels) = (source.adjustment_levels(), target.adjustment_levels());
+            if window_level && (target_levels.min, target_levels.max) != (source_levels.min, source_levels.max) {
+                responses.add(PortfolioMessage::Image { image_id: Some(target_id), message: ImageMessage::FollowWindow { min: source_levels.min, max: source_levels.max } });
+            }
+        }
+    }
+
+    // A finished sequence is detached from the live image, so the next capture opens a new one
+    fn end_live_capture(&mut self, responses: &mut VecDeque<Message>) {
+        if !self.live_keep_frames || self.live_frames == 0 {
+            return;
+        }
+        self.live_frames = 0;
+        let Some(image_id) = self.live_image_id.take() else { return };
+        let Some(image) = self.images.get_mut(&image_id) else { return };
+        image.set_name(format!("Sequence of {} frames", image.frame_count()));
+        self.send_open_images(responses);
+    }
+
+    // Applies live averaging, if enabled, to a frame arriving from the detector
+    pub fn process_live_frame(&mut self, frame: ImageFrame) -> ImageFrame {
+        match &mut self.live_averaging {
+            Some(rolling_average) => rolling_average.push(frame),
+            None => frame,
+        }
+    }
+
+    fn open_image(&mut self, path: &Path) -> Result<ImageId, ImageIoError> {
+        let image_stack = io::load_image_stack(path)?;
+        let mut image = ImageMessageHandler::from_image_stack(image_stack).ok_or_else(|| ImageIoError::Unsupported("image has no frames".into()))?;
+        image.set_name(path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default());
+        Ok(self.add_image(image))
+    }
+
+    fn send_open_images(&self, responses: &mut VecDeque<Message>) {
+        let images = self
+            .image_ids
+            .iter()
+            .filter_map(|id| self.images.get(id).map(|image| (id, image)))
+            .map(|(&id, image)| {
+                let (width, height) = image.image_buffer().dimensions();
+                FrontendImageDetails {
+                    id,
+                    name: image.name().to_string(),
+                    width,
+                    height,
+                    frame_count: image.frame_count() as u32,
+                    viewport_transform: *image.viewport_transform(),
+                }
+            })
+            .collect();
+        responses.add(FrontendMessage::UpdateOpenImages { images, active_image_id: self.active_image_id });
+        responses.add(FrontendMessage::UpdateViewports { viewports: self.viewport_images.clone(), active_viewport: self.active_viewport, sync: self.viewport_sync });
+        // A viewport that starts showing an image needs its pixels
+        for &image_id in self.viewport_images.iter().flatten() {
+            if let Some(image) = self.images.get(&image_id) {
+                image.send_display_image(image_id, responses);
+            }
+        }
+    }
+
     pub fn image(&self, image_id: ImageId) -> Option<&ImageMessageHandler> {
         self.images.get(&image_id)
     }
@@ -38,4 +425,372 @@ impl PortfolioMessageHandler {
     pub fn active_image_id(&self) -> Option<ImageId> {
         self.active_image_id
     }
-}
\ No newline at end of file
+
+    pub fn active_viewport(&self) -> u32 {
+        self.active_viewport
+    }
+}
+#[cfg(test)]
+mod tests {
+    use glam::IVec2;
+    use image::Luma;
+
+    use super::*;
+    use crate::analysis::aggregate::FrameAggregate;
+    use crate::analysis::nps::NpsOptions;
+    use crate::messages::portfolio::image::utility_types::metadata::FrameMetadata;
+    use crate::messages::portfolio::image::utility_types::annotations::{AnnotationEnum, Rectangle, ShapeEnum};
+    use crate::messages::portfolio::image::utility_types::misc::ImagePosition;
+
+    const SIZE: u32 = 64;
+
+    fn send(portfolio: &mut PortfolioMessageHandler, message: PortfolioMessage) -> VecDeque<Message> {
+        let mut responses = VecDeque::new();
+        portfolio.process_message(message, &mut responses, &InputMapperMessageHandler::default());
+        responses
+    }
+
+    // Also handles the portfolio messages sent in response, as the dispatcher would
+    fn send_all(portfolio: &mut PortfolioMessageHandler, message: PortfolioMessage) {
+        let mut queue = VecDeque::from([message]);
+        while let Some(message) = queue.pop_front() {
+            for response in send(portfolio, message) {
+                if let Message::Portfolio(message) = response {
+                    queue.push_back(message);
+                }
+            }
+        }
+    }
+
+    fn add_image(portfolio: &mut PortfolioMessageHandler, frames: Vec<ImageFrame>) -> ImageId {
+        let acquisition = AcquisitionMetadata { pixel_pitch_mm: Some(0.1), ..Default::default() };
+        portfolio.add_image(ImageMessageHandler::from_image_stack(ImageStack { frames, acquisition, frame_metadata: Vec::new() }).unwrap())
+    }
+
+    // Scrambled but repeatable values standing in for flat-field noise
+    fn flat_frames(count: u32) -> Vec<ImageFrame> {
+        (0..count).map(|frame| ImageFrame::from_fn(SIZE, SIZE, |x, y| Luma([(1000 + (x * 7919 + y * 104_729 + frame * 31) % 97) as u16]))).collect()
+    }
+
+    // An edge tilted by about 5 degrees
+    fn edge_frame() -> ImageFrame {
+        ImageFrame::from_fn(SIZE, SIZE, |x, y| Luma([if (x as f64) < 30. + 0.0875 * y as f64 { 100 } else { 1100 }]))
+    }
+
+    fn dialogs(responses: &VecDeque<Message>) -> Vec<&str> {
+        responses
+            .iter()
+            .filter_map(|message| match message {
+                Message::Frontend(FrontendMessage::DisplayDialog { title }) => Some(title.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn window(portfolio: &PortfolioMessageHandler, image_id: ImageId) -> (u32, u32) {
+        let levels = portfolio.image(image_id).unwrap().adjustment_levels();
+        (levels.min, levels.max)
+    }
+
+    fn raw_frame(width: u32, height: u32, value: u16) -> RawFrame {
+        RawFrame { width, height, data: vec![value; 4], metadata: FrameMetadata::default() }
+    }
+
+    #[test]
+    fn image_messages_go_to_the_given_or_active_image() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let first = add_image(&mut portfolio, flat_frames(1));
+        let second = add_image(&mut portfolio, flat_frames(1));
+        assert_eq!(portfolio.active_image_id(), Some(second));
+
+        send(&mut portfolio, PortfolioMessage::Image { image_id: None, message: ImageMessage::SetWindow { min: 1, max: 2 } });
+        send(&mut portfolio, PortfolioMessage::Image { image_id: Some(first), message: ImageMessage::SetWindow { min: 3, max: 4 } });
+        assert_eq!((window(&portfolio, first), window(&portfolio, second)), ((3, 4), (1, 2)));
+
+        // Unknown images and empty viewports are ignored
+        let unknown = ImageId(uuid::Uuid::nil());
+        assert!(send(&mut portfolio, PortfolioMessage::Image { image_id: Some(unknown), message: ImageMessage::SetWindow { min: 5, max: 6 } }).is_empty());
+        assert!(send(&mut portfolio, PortfolioMessage::Viewport { viewport: 3, message: ImageMessage::SetWindow { min: 5, max: 6 } }).is_empty());
+        send(&mut portfolio, PortfolioMessage::Viewport { viewport: 0, message: ImageMessage::SetWindow { min: 7, max: 8 } });
+        assert_eq!(window(&portfolio, second), (7, 8));
+    }
+
+    #[test]
+    fn closing_the_active_image_activates_its_neighbour() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let [first, second, third] = [(); 3].map(|_| add_image(&mut portfolio, flat_frames(1)));
+        send(&mut portfolio, PortfolioMessage::SelectImage { image_id: second });
+
+        let responses = send(&mut portfolio, PortfolioMessage::CloseImage { image_id: second });
+        assert_eq!(portfolio.image_ids, [first, third]);
+        assert_eq!(portfolio.active_image_id(), Some(third));
+        assert_eq!(portfolio.viewport_images, [Some(third)]);
+        assert!(responses.iter().any(|message| matches!(message, Message::Frontend(FrontendMessage::UpdateOpenImages { active_image_id: Some(id), .. }) if *id == third)));
+
+        // Closing an inactive image leaves the active one alone
+        send(&mut portfolio, PortfolioMessage::CloseImage { image_id: first });
+        assert_eq!(portfolio.active_image_id(), Some(third));
+        send(&mut portfolio, PortfolioMessage::CloseImage { image_id: third });
+        assert_eq!((portfolio.active_image_id(), portfolio.viewport_images.as_slice()), (None, [None].as_slice()));
+
+        add_image(&mut portfolio, flat_frames(1));
+        send(&mut portfolio, PortfolioMessage::CloseAllImages);
+        assert!(portfolio.images.is_empty() && portfolio.image_ids.is_empty());
+        assert_eq!(portfolio.active_image_id(), None);
+    }
+
+    #[test]
+    fn duplicate_is_a_separate_copy() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let image_id = add_image(&mut portfolio, flat_frames(2));
+        send(&mut portfolio, PortfolioMessage::RenameImage { image_id, name: "Flat".into() });
+        send(&mut portfolio, PortfolioMessage::Image { image_id: None, message: ImageMessage::SetWindow { min: 1, max: 2 } });
+
+        send(&mut portfolio, PortfolioMessage::DuplicateImage { image_id });
+        let duplicate_id = portfolio.active_image_id().unwrap();
+        let duplicate = portfolio.image(duplicate_id).unwrap();
+        assert_ne!(duplicate_id, image_id);
+        assert_eq!(duplicate.name(), "Flat copy");
+        assert_eq!(duplicate.frames(), portfolio.image(image_id).unwrap().frames());
+        assert_eq!(window(&portfolio, duplicate_id), (1, 2));
+        assert!(!duplicate.can_undo());
+
+        send(&mut portfolio, PortfolioMessage::Image { image_id: None, message: ImageMessage::SetWindow { min: 3, max: 4 } });
+        assert_eq!(window(&portfolio, image_id), (1, 2));
+    }
+
+    #[test]
+    fn rename_updates_the_open_images() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let image_id = add_image(&mut portfolio, flat_frames(1));
+
+        let responses = send(&mut portfolio, PortfolioMessage::RenameImage { image_id, name: "Dark".into() });
+        assert_eq!(portfolio.image(image_id).unwrap().name(), "Dark");
+        let Some(Message::Frontend(FrontendMessage::UpdateOpenImages { images, .. })) = responses.front() else { panic!("{responses:?}") };
+        assert_eq!(images[0].name, "Dark");
+    }
+
+    #[test]
+    fn acquisition_frames_that_dont_match_are_reported() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let frames = vec![raw_frame(2, 2, 1), raw_frame(3, 2, 2), raw_frame(2, 2, 3)];
+        let responses = send(&mut portfolio, PortfolioMessage::NewImageFromAcquisition { name: None, acquisition: AcquisitionMetadata::default(), frames });
+        assert_eq!(dialogs(&responses), ["1 of 3 frames didn't match their dimensions and were left out"]);
+        let image = portfolio.active_image().unwrap();
+        assert_eq!((image.name(), image.frame_count()), ("Acquisition 1", 2));
+
+        let responses = send(&mut portfolio, PortfolioMessage::NewImageFromAcquisition { name: None, acquisition: AcquisitionMetadata::default(), frames: vec![raw_frame(1, 1, 0)] });
+        assert_eq!(dialogs(&responses), ["1 of 1 frames didn't match their dimensions and were left out", "The acquisition has no frames"]);
+        assert_eq!(portfolio.image_ids.len(), 1);
+    }
+
+    #[test]
+    fn sequence_is_collected_in_the_live_image_and_kept_open() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let begin = |keep_frames| PortfolioMessage::BeginLiveCapture { acquisition: AcquisitionMetadata::default(), keep_frames };
+        send(&mut portfolio, begin(true));
+        for value in 1..=3 {
+            send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, value) });
+        }
+        send(&mut portfolio, PortfolioMessage::EndLiveCapture);
+        let sequence_id = portfolio.active_image_id().unwrap();
+        let sequence = portfolio.image(sequence_id).unwrap();
+        assert_eq!((sequence.name(), sequence.frame_count(), sequence.current_frame()), ("Sequence of 3 frames", 3, 2));
+        assert_eq!(sequence.frames().iter().map(|frame| frame.get_pixel(0, 0).0[0]).collect::<Vec<_>>(), [1, 2, 3]);
+
+        // A stream that follows opens a new live image and shows one frame at a time
+        send(&mut portfolio, begin(false));
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 4) });
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 5) });
+        send(&mut portfolio, PortfolioMessage::EndLiveCapture);
+        let live = portfolio.image(portfolio.live_image_id.unwrap()).unwrap();
+        assert_eq!((live.name(), live.frame_count()), ("Live", 1));
+        assert_eq!(portfolio.image(sequence_id).unwrap().frame_count(), 3);
+    }
+
+    #[test]
+    fn sequence_left_unfinished_is_kept_when_the_next_capture_begins() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let begin = || PortfolioMessage::BeginLiveCapture { acquisition: AcquisitionMetadata::default(), keep_frames: true };
+        send(&mut portfolio, begin());
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 1) });
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 2) });
+        let cut_short_id = portfolio.active_image_id().unwrap();
+
+        send(&mut portfolio, begin());
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 3) });
+        let cut_short = portfolio.image(cut_short_id).unwrap();
+        assert_eq!((cut_short.name(), cut_short.frame_count()), ("Sequence of 2 frames", 2));
+        assert_ne!(portfolio.live_image_id, Some(cut_short_id));
+        assert_eq!(portfolio.active_image().unwrap().frames()[0].get_pixel(0, 0).0[0], 3);
+    }
+
+    #[test]
+    fn live_image_keeps_the_latest_acquisition() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let acquisition = AcquisitionMetadata { detector_id: Some("SL-1234".into()), temperature: Some(20.), ..Default::default() };
+        send(&mut portfolio, PortfolioMessage::BeginLiveCapture { acquisition, keep_frames: false });
+        send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 1) });
+        let acquisition = AcquisitionMetadata { temperature: Some(25.5), ..portfolio.live_acquisition.clone() };
+        send(&mut portfolio, PortfolioMessage::UpdateLiveAcquisition { acquisition });
+        let metadata = portfolio.active_image().unwrap().acquisition_metadata();
+        assert_eq!((metadata.detector_id.as_deref(), metadata.temperature), (Some("SL-1234"), Some(25.5)));
+    }
+
+    fn display_images(responses: &VecDeque<Message>) -> Vec<ImageId> {
+        responses
+            .iter()
+            .filter_map(|message| match message {
+                Message::Frontend(FrontendMessage::UpdateDisplayImage { image_id, .. }) => Some(*image_id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn shown_and_live_images_send_their_display_image() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let first = add_image(&mut portfolio, flat_frames(1));
+        add_image(&mut portfolio, flat_frames(1));
+        assert_eq!(display_images(&send(&mut portfolio, PortfolioMessage::SelectImage { image_id: first })), [first]);
+
+        send(&mut portfolio, PortfolioMessage::BeginLiveCapture { acquisition: AcquisitionMetadata::default(), keep_frames: false });
+        let responses = send(&mut portfolio, PortfolioMessage::LiveFrame { frame: raw_frame(2, 2, 1) });
+        assert!(display_images(&responses).contains(&portfolio.live_image_id.unwrap()));
+    }
+
+    #[test]
+    fn aggregate_of_a_frame_range_is_a_new_image() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let frames = [10, 20, 60].map(|value| ImageFrame::from_pixel(2, 2, Luma([value]))).to_vec();
+        let image_id = add_image(&mut portfolio, frames);
+        portfolio.image_mut(image_id).unwrap().set_name("Flat");
+
+        send(&mut portfolio, PortfolioMessage::AggregateFrames { image_id, first_frame: 1, last_frame: 2, aggregate: FrameAggregate::Average });
+        let aggregate_id = portfolio.active_image_id().unwrap();
+        let aggregate_image = portfolio.image(aggregate_id).unwrap();
+        assert_ne!(aggregate_id, image_id);
+        assert_eq!(aggregate_image.name(), "Flat Average");
+        assert_eq!(aggregate_image.image_buffer().as_raw(), &[40; 4]);
+        assert_eq!(aggregate_image.acquisition_metadata().corrections, ["Average of frames 1-2"]);
+        assert_eq!(aggregate_image.acquisition_metadata().pixel_pitch_mm, Some(0.1));
+        assert_eq!(portfolio.image(image_id).unwrap().frame_count(), 3);
+    }
+
+    #[test]
+    fn aggregate_errors_are_shown() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let image_id = add_image(&mut portfolio, vec![ImageFrame::from_pixel(2, 2, Luma([u16::MAX])); 2]);
+
+        let responses = send(&mut portfolio, PortfolioMessage::AggregateFrames { image_id, first_frame: 1, last_frame: 0, aggregate: FrameAggregate::Sum });
+        assert_eq!(dialogs(&responses).len(), 1);
+        let responses = send(&mut portfolio, PortfolioMessage::AggregateFrames { image_id, first_frame: 1, last_frame: 4, aggregate: FrameAggregate::Average });
+        assert_eq!(dialogs(&responses).len(), 1);
+        assert_eq!(portfolio.image_ids, [image_id]);
+    }
+
+    #[test]
+    fn divided_sum_records_its_divisor() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let image_id = add_image(&mut portfolio, vec![ImageFrame::from_pixel(2, 2, Luma([u16::MAX])); 2]);
+
+        send(&mut portfolio, PortfolioMessage::AggregateFrames { image_id, first_frame: 0, last_frame: 1, aggregate: FrameAggregate::Sum });
+        let sum = portfolio.active_image().unwrap();
+        assert_eq!(sum.image_buffer().as_raw(), &[u16::MAX; 4]);
+        assert_eq!(sum.acquisition_metadata().corrections, ["Sum of frames 0-1 divided by 2"]);
+    }
+
+    #[test]
+    fn noise_analysis_uses_the_frames_of_an_open_image() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let flat_id = add_image(&mut portfolio, flat_frames(3));
+        let options = NpsOptions { roi_size: SIZE, ..Default::default() };
+
+        let responses = send(&mut portfolio, PortfolioMessage::AnalyseNoisePowerSpectrum { image_id: flat_id, first_frame: 1, last_frame: 2, options: options.clone() });
+        let Some(Message::Frontend(FrontendMessage::UpdateNoisePowerSpectrum { result })) = responses.front() else { panic!("no noise analysis in {responses:?}") };
+        assert_eq!((result.roi_count, result.pixel_pitch_mm), (2, 0.1));
+
+        let responses = send(&mut portfolio, PortfolioMessage::AnalyseNoisePowerSpectrum { image_id: flat_id, first_frame: 2, last_frame: 5, options });
+        assert_eq!(dialogs(&responses).len(), 1);
+        assert!(portfolio.noise_power_spectrum.is_some());
+    }
+
+    #[test]
+    fn dqe_uses_the_mtf_measured_on_the_annotation() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let flat_id = add_image(&mut portfolio, flat_frames(2));
+        let edge_id = add_image(&mut portfolio, vec![edge_frame()]);
+        let rectangle = Rectangle::from_corners(ImagePosition(IVec2::ZERO), ImagePosition(IVec2::splat(SIZE as i32)));
+        send(&mut portfolio, PortfolioMessage::Image { image_id: Some(edge_id), message: ImageMessage::AddAnnotation { annotation: AnnotationEnum::Shape(ShapeEnum::Rectangle(rectangle)) } });
+        let annotation_id = portfolio.image(edge_id).unwrap().visible_annotation_ids().next().unwrap();
+        let compute_dqe = PortfolioMessage::ComputeDqe { image_id: edge_id, annotation_id, fluence_per_mm2: 1e4 };
+
+        assert_eq!(dialogs(&send(&mut portfolio, compute_dqe.clone())), ["Analyse a flat-field stack before computing DQE"]);
+        let options = NpsOptions { roi_size: SIZE, ..Default::default() };
+        send(&mut portfolio, PortfolioMessage::AnalyseNoisePowerSpectrum { image_id: flat_id, first_frame: 0, last_frame: 1, options });
+        assert_eq!(dialogs(&send(&mut portfolio, compute_dqe.clone())), ["Measure the MTF of an edge before computing DQE"]);
+
+        send(&mut portfolio, PortfolioMessage::Image { image_id: Some(edge_id), message: ImageMessage::MeasureMtf { annotation_id } });
+        let responses = send(&mut portfolio, compute_dqe);
+        assert!(matches!(responses.front(), Some(Message::Frontend(FrontendMessage::UpdateDqe { .. }))), "{responses:?}");
+        assert!(portfolio.dqe.is_some());
+    }
+
+    // The second image shown alongside the first, which stays in the active viewport
+    fn side_by_side(portfolio: &mut PortfolioMessageHandler, sync: ViewportSync) -> (ImageId, ImageId) {
+        let second = add_image(portfolio, flat_frames(1));
+        let first = add_image(portfolio, flat_frames(1));
+        send_all(portfolio, PortfolioMessage::ShowImageInViewport { viewport: 1, image_id: second });
+        send_all(portfolio, PortfolioMessage::SetViewportSync { sync });
+        (first, second)
+    }
+
+    #[test]
+    fn synced_window_follows_outside_the_other_images_history() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let (first, second) = side_by_side(&mut portfolio, ViewportSync { navigation: false, window_level: true });
+        // Something to redo on the second image, following the first mustn't lose it
+        send_all(&mut portfolio, PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::SetInvert { invert: true } });
+        send_all(&mut portfolio, PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::Undo });
+        assert!(portfolio.image(second).unwrap().can_redo());
+        let initial_window = window(&portfolio, first);
+
+        for message in [ImageMessage::StartTransaction, ImageMessage::SetWindow { min: 10, max: 20 }, ImageMessage::SetWindow { min: 30, max: 40 }, ImageMessage::CommitTransaction] {
+            send_all(&mut portfolio, PortfolioMessage::Image { image_id: None, message });
+        }
+        assert_eq!((window(&portfolio, first), window(&portfolio, second)), ((30, 40), (30, 40)));
+        let second_image = portfolio.image(second).unwrap();
+        assert!(!second_image.can_undo() && second_image.can_redo());
+
+        // Undone where it was made, the change is undone in both
+        send_all(&mut portfolio, PortfolioMessage::Image { image_id: None, message: ImageMessage::Undo });
+        assert_eq!((window(&portfolio, first), window(&portfolio, second)), (initial_window, initial_window));
+    }
+
+    #[test]
+    fn synced_navigation_follows_the_changed_viewport() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let (first, second) = side_by_side(&mut portfolio, ViewportSync { navigation: true, window_level: false });
+        send_all(&mut portfolio, PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::PanCanvas { delta: DVec2::new(5., -3.) } });
+        send_all(&mut portfolio, PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::RotateCanvas { quarter_turns: 1 } });
+        let transform = *portfolio.image(second).unwrap().viewport_transform();
+        assert_eq!(transform.rotation, 1);
+        assert_eq!(*portfolio.image(first).unwrap().viewport_transform(), transform);
+
+        // Levels aren't synchronised unless asked for
+        send_all(&mut portfolio, PortfolioMessage::Viewport { viewport: 1, message: ImageMessage::SetWindow { min: 1, max: 2 } });
+        assert_ne!(window(&portfolio, first), (1, 2));
+    }
+
+    #[test]
+    fn removed_viewports_are_forgotten() {
+        let mut portfolio = PortfolioMessageHandler::default();
+        let (first, second) = side_by_side(&mut portfolio, ViewportSync::default());
+        send_all(&mut portfolio, PortfolioMessage::SelectViewport { viewport: 1 });
+        assert_eq!((portfolio.active_viewport(), portfolio.active_image_id()), (1, Some(second)));
+
+        send_all(&mut portfolio, PortfolioMessage::SetViewportCount { count: 1 });
+        assert_eq!(portfolio.viewport_images, [Some(first)]);
+        assert_eq!((portfolio.active_viewport(), portfolio.active_image_id()), (0, Some(first)));
+    }
+}
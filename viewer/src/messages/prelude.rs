@@ -3,17 +3,19 @@ pub use crate::utility_traits::MessageHandler;
 // Message, MessageHandler
 pub use crate::messages::dialog::select_detector_dialog::{SelectDetectorDialogMessage, SelectDetectorMessageHandler};
 pub use crate::messages::dialog::{DialogMessage, DialogMessageHandler};
-//pub use crate::messages::debug::{DebugMessage, DebugMessageHandler};
+pub use crate::messages::debug::{DebugMessage, DebugMessageHandler};
 pub use crate::messages::detector::{DetectorMessage, DetectorMessageHandler};
 pub use crate::messages::frontend::FrontendMessage;
-pub use crate::messages::portfolio::image::{ImageMessage, ImageMessageHandler};
-pub use crate::messages::portfolio::PortfolioMessage;
+pub use crate::messages::input_mapper::{InputMapperMessage, InputMapperMessageHandler};
+pub use crate::messages::portfolio::image::{ImageMessage, ImageMessageData, ImageMessageHandler};
+pub use crate::messages::portfolio::{PortfolioMessage, PortfolioMessageHandler};
 pub use crate::messages::tool::{ToolMessage, ToolMessageHandler};
 pub use crate::messages::tool::tool_messages::line_tool::LineToolMessage;
 pub use crate::messages::tool::tool_messages::select_tool::SelectToolMessage;
 pub use crate::messages::tool::tool_messages::rectangle_tool::RectangleToolMessage;
 
-pub use crate::messages::message::{Message, MessageDiscriminant};
+pub use crate::messages::message::Message;
+pub use crate::messages::message_discriminant::MessageDiscriminant;
 
 // Helper
 pub use crate::messages::portfolio::image::utility_types::misc::ImageId;
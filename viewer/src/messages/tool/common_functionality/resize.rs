@@ -1,9 +1,23 @@
-use crate::messages::input_mapper::utility_types::input_mouse::ViewportPosition;
+use crate::messages::portfolio::image::utility_types::annotations::{Rectangle, Shape};
+use crate::messages::portfolio::image::utility_types::misc::ImagePosition;
 
 #[derive(Clone, Debug, Default)]
 pub struct Resize {
-	drag_start: ViewportPosition,
+	drag_start: Option<ImagePosition>,
 }
 
 impl Resize {
-}
\ No newline at end of file
+	pub fn start(&mut self, position: ImagePosition) {
+		self.drag_start = Some(position);
+	}
+
+	// The rectangle spanned by the drag, None if it covers no pixels
+	pub fn finish(&mut self, position: ImagePosition) -> Option<Rectangle> {
+		let rectangle = Rectangle::from_corners(self.drag_start.take()?, position);
+		(rectangle.get_area() > 0.).then_some(rectangle)
+	}
+
+	pub fn cleanup(&mut self) {
+		self.drag_start = None;
+	}
+}
@@ -1,5 +1,6 @@
 use serde::{Deserialize, Serialize};
 
+use crate::messages::input_mapper::utility_types::input_mouse::ViewportPosition;
 use crate::messages::prelude::*;
 
 use super::utility_types::ToolType;
@@ -13,6 +14,21 @@ pub enum ToolMessage {
     Rectangle(RectangleToolMessage),
     Ellipse,
 
+    // Input events, passed on to the active tool as its own messages with the position in image coordinates
+    Abort,
+    DoubleClick {
+        position: ViewportPosition,
+    },
+    DragStart {
+        position: ViewportPosition,
+    },
+    DragStop {
+        position: ViewportPosition,
+    },
+    PointerMove {
+        position: ViewportPosition,
+    },
+
     // Messages
     ActivateTool {
         tool_type: ToolType,
@@ -7,8 +7,9 @@ pub struct ToolMessageHandler {
     pub tool_state: ToolFsmState,
 }
 
-impl MessageHandler<ToolMessage, ()> for ToolMessageHandler {
-    fn process_message(&mut self, message: ToolMessage, responses: &mut VecDeque<Message>, data: ()) {
+// Tools work on the active image, without one there is nothing for them to do
+impl<'a> MessageHandler<ToolMessage, Option<&'a ImageMessageHandler>> for ToolMessageHandler {
+    fn process_message(&mut self, message: ToolMessage, responses: &mut VecDeque<Message>, image: Option<&'a ImageMessageHandler>) {
         match message {
             ToolMessage::ActivateTool { tool_type } => {
                 let tool_data = &mut self.tool_state.tool_data;
@@ -18,27 +19,23 @@ impl MessageHandler<ToolMessage, ()> for ToolMessageHandler {
                 if tool_type == old_tool {
                     return;
                 }
+                if !tool_data.tools.contains_key(&tool_type) {
+                    log::warn!("{tool_type:?} is not available");
+                    return;
+                }
 
-                // let mut send_abort_to_tool = |tool_type| {
-                //     if let Some(tool) = tool_data.tools.get_mut(&tool_type) {
-                //         let mut data = ToolActionHandlerData {
-                //             image,
-                //         };
-                //         if let Some(tool_abort_message) = tool.event_to_message_map().tool_abort {
-                //             tool.process_message(tool_abort_message, responses, &mut data);
-                //         }
-                //     };
-                // };
+                // Stop whatever the old tool was in the middle of
+                let tool_abort = tool_data.active_tool().event_to_message_map().tool_abort;
+                self.send_to_active_tool(tool_abort, image, responses);
 
-                // send_abort_to_tool(tool_type);
-                // send_abort_to_tool(tool_type);
+                let tool_data = &mut self.tool_state.tool_data;
 
                 // Unsubscribe old tool from the broadcaster
-				tool_data.tools.get(&tool_type).unwrap().deactivate(responses);
+				tool_data.tools.get(&old_tool).unwrap().deactivate(responses);
 
                 // Store the new active tool
 				tool_data.active_tool_type = tool_type;
-                
+
                 tool_data.tools.get(&tool_type).unwrap().activate(responses);
 
                 responses.add(Message::Frontend(FrontendMessage::SetActiveTool(tool_data.active_tool_type)));
@@ -47,7 +44,20 @@ impl MessageHandler<ToolMessage, ()> for ToolMessageHandler {
                 let tool_data = &mut self.tool_state.tool_data;
                 responses.add(Message::Frontend(FrontendMessage::SetActiveTool(tool_data.active_tool_type)));
             }
-            _  => {}
+            ToolMessage::Abort | ToolMessage::DoubleClick { .. } | ToolMessage::DragStart { .. } | ToolMessage::DragStop { .. } | ToolMessage::PointerMove { .. } => {
+                let Some(image) = image else { return };
+                let tool_message = self.tool_state.tool_data.active_tool().event_to_message_map().tool_message(&message, image);
+                self.send_to_active_tool(tool_message, Some(image), responses);
+            }
+            message => self.send_to_active_tool(Some(message), image, responses),
         }
     }
-}
\ No newline at end of file
+}
+
+impl ToolMessageHandler {
+    fn send_to_active_tool(&mut self, message: Option<ToolMessage>, image: Option<&ImageMessageHandler>, responses: &mut VecDeque<Message>) {
+        let (Some(message), Some(image)) = (message, image) else { return };
+        let mut data = ToolActionHandlerData::new(image);
+        self.tool_state.tool_data.active_tool_mut().process_message(message, responses, &mut data);
+    }
+}
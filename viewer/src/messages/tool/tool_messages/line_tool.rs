@@ -24,8 +24,8 @@ pub enum LineToolMessage {
     Abort,
     WorkingColourChanged,
 
-    DragStart,
-    DragStop,
+    DragStart { position: ImagePosition },
+    DragStop { position: ImagePosition },
     UpdateOptions(LineOptionsUpdate),
 }
 
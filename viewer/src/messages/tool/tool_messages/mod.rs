@@ -5,6 +5,7 @@ pub mod select_tool;
 
 pub mod tool_prelude {
     pub use crate::messages::prelude::*;
+    pub use crate::messages::portfolio::image::utility_types::misc::ImagePosition;
     pub use crate::messages::tool::utility_types::{Colour, EventToMessageMap, Fsm, ToolActionHandlerData, ToolMetadata, ToolTransition, ToolType};
 
     pub use serde::{Serialize, Deserialize};
@@ -1,3 +1,4 @@
+use crate::messages::portfolio::image::utility_types::annotations::{AnnotationEnum, ShapeEnum};
 use crate::messages::tool::common_functionality::resize::Resize;
 
 use super::tool_prelude::*;
@@ -37,8 +38,8 @@ pub enum RectangleToolMessage {
     WorkingColourChanged,
 
     // Tool-specific messages
-    DragStart,
-    DragStop,
+    DragStart { position: ImagePosition },
+    DragStop { position: ImagePosition },
     UpdateOptions(RectangleOptionsUpdate)
 }
 
@@ -67,6 +68,9 @@ impl ToolTransition for RectangleTool {
 	fn event_to_message_map(&self) -> EventToMessageMap {
 		EventToMessageMap {
 			tool_abort: Some(ToolMessage::Rectangle(RectangleToolMessage::Abort)),
+			drag_start: Some(|position| ToolMessage::Rectangle(RectangleToolMessage::DragStart { position })),
+			drag_stop: Some(|position| ToolMessage::Rectangle(RectangleToolMessage::DragStop { position })),
+			..Default::default()
 		}
 	}
 }
@@ -99,13 +103,18 @@ impl Fsm for RectangleToolFsmState {
 			(_, RectangleToolMessage::Overlays) => {
 					self
 			},
-			(RectangleToolFsmState::Ready, RectangleToolMessage::DragStart) => {
+			(RectangleToolFsmState::Ready, RectangleToolMessage::DragStart { position }) => {
+				shape_data.start(position);
 				RectangleToolFsmState::Drawing
 			},
-			(RectangleToolFsmState::Drawing, RectangleToolMessage::DragStop) => {
+			(RectangleToolFsmState::Drawing, RectangleToolMessage::DragStop { position }) => {
+				if let Some(rectangle) = shape_data.finish(position) {
+					responses.add(ImageMessage::AddAnnotation { annotation: AnnotationEnum::Shape(ShapeEnum::Rectangle(rectangle)) });
+				}
 				RectangleToolFsmState::Ready
 			},
 			(RectangleToolFsmState::Drawing, RectangleToolMessage::Abort) => {
+				shape_data.cleanup();
 				RectangleToolFsmState::Ready
 			},
 			(_, RectangleToolMessage::WorkingColourChanged) => {
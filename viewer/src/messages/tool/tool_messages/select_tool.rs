@@ -10,14 +10,15 @@ pub struct SelectTool {
     tool_data: SelectToolData
 }
 
-#[derive(PartialEq, Eq, Clone, Debug, Serialize, Deserialize, specta::Type)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize, specta::Type)]
 pub enum SelectToolMessage {
     // Standard messages
     Abort,
+    PointerMove { position: ImagePosition },
 
     // Tool-specific messages
-    DragStart,
-    DragStop
+    DragStart { position: ImagePosition },
+    DragStop { position: ImagePosition }
 }
 
 impl<'a> MessageHandler<ToolMessage, &mut ToolActionHandlerData<'a>> for SelectTool {
@@ -55,6 +56,10 @@ impl ToolTransition for SelectTool {
 	fn event_to_message_map(&self) -> EventToMessageMap {
 		EventToMessageMap {
 			tool_abort: Some(ToolMessage::Select(SelectToolMessage::Abort)),
+			drag_start: Some(|position| ToolMessage::Select(SelectToolMessage::DragStart { position })),
+			drag_stop: Some(|position| ToolMessage::Select(SelectToolMessage::DragStop { position })),
+			pointer_move: Some(|position| ToolMessage::Select(SelectToolMessage::PointerMove { position })),
+			..Default::default()
 		}
 	}
 }
\ No newline at end of file
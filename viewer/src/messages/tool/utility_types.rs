@@ -1,4 +1,5 @@
 
+use crate::messages::portfolio::image::utility_types::misc::ImagePosition;
 use crate::messages::prelude::*;
 use super::tool_messages::*;
 // use crate::messages::layout::utility_types::widget_prelude::*;
@@ -63,6 +64,26 @@ pub trait Fsm {
 #[derive(Clone, Debug, Default)]
 pub struct EventToMessageMap {
     pub tool_abort: Option<ToolMessage>,
+    pub double_click: Option<fn(ImagePosition) -> ToolMessage>,
+    pub drag_start: Option<fn(ImagePosition) -> ToolMessage>,
+    pub drag_stop: Option<fn(ImagePosition) -> ToolMessage>,
+    pub pointer_move: Option<fn(ImagePosition) -> ToolMessage>,
+}
+
+impl EventToMessageMap {
+    // The tool's own message for an input event, None if the tool doesn't react to it
+    pub fn tool_message(&self, event: &ToolMessage, image: &ImageMessageHandler) -> Option<ToolMessage> {
+        let with_position = |message: Option<fn(ImagePosition) -> ToolMessage>, position| message.map(|message| message(image.viewport_to_image(position)));
+
+        match *event {
+            ToolMessage::Abort => self.tool_abort.clone(),
+            ToolMessage::DoubleClick { position } => with_position(self.double_click, position),
+            ToolMessage::DragStart { position } => with_position(self.drag_start, position),
+            ToolMessage::DragStop { position } => with_position(self.drag_stop, position),
+            ToolMessage::PointerMove { position } => with_position(self.pointer_move, position),
+            _ => None,
+        }
+    }
 }
 
 pub trait ToolTransition {
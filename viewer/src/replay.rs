@@ -0,0 +1,134 @@
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+use crate::application::Viewer;
+use crate::messages::debug::utility_types::{frames_path, read_frame_data, RecordedMessage, RecordingHeader};
+use crate::messages::prelude::*;
+use crate::utility_functions::{clear_uuid_seed, set_uuid_seed};
+
+#[derive(Debug, thiserror::Error)]
+pub enum ReplayError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("line {line} is invalid: {source}")]
+    InvalidLine { line: usize, source: serde_json::Error },
+    #[error("the recording is empty")]
+    MissingHeader,
+    #[error("the frames of message {line} are missing from the frame file: {source}")]
+    MissingFrames { line: usize, source: io::Error },
+    #[error("frontend message {index} differs from the golden file\n  expected: {expected}\n  actual:   {actual}")]
+    Mismatch { index: usize, expected: String, actual: String },
+}
+
+// The messages passed to the dispatcher during a recorded session, without the ones its handlers sent
+pub struct Recording {
+    pub header: RecordingHeader,
+    pub messages: Vec<Message>,
+}
+
+impl Recording {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, ReplayError> {
+        let path = path.as_ref();
+        let mut lines = BufReader::new(File::open(path)?).lines().enumerate();
+        // Opened on first use, recordings without frames may not have the file
+        let mut frames: Option<BufReader<File>> = None;
+        let (_, header) = lines.next().ok_or(ReplayError::MissingHeader)?;
+        let header = serde_json::from_str(&header?).map_err(|source| ReplayError::InvalidLine { line: 1, source })?;
+
+        let mut messages = Vec::new();
+        for (index, line) in lines {
+            let mut recorded: RecordedMessage<Message> = serde_json::from_str(&line?).map_err(|source| ReplayError::InvalidLine { line: index + 1, source })?;
+            if recorded.depth != 0 {
+                continue;
+            }
+            for frame in recorded.message.frames_mut() {
+                let frames = match &mut frames {
+                    Some(frames) => frames,
+                    None => frames.insert(BufReader::new(File::open(frames_path(path)).map_err(|source| ReplayError::MissingFrames { line: index + 1, source })?)),
+                };
+                frame.data = read_frame_data(frames).map_err(|source| ReplayError::MissingFrames { line: index + 1, source })?;
+            }
+            messages.push(recorded.message);
+        }
+        Ok(Self { header, messages })
+    }
+
+    // Plays the session against a fresh viewer and returns what it sent to the frontend. The viewer is never ticked,
+    // so events from background work only arrive as recorded and the replay doesn't depend on timing.
+    pub fn replay(self) -> Vec<FrontendMessage> {
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        set_uuid_seed(self.header.uuid_seed);
+        for message in self.messages {
+            viewer.handle_message(message);
+        }
+        clear_uuid_seed();
+        std::iter::from_fn(|| frontend_messages.try_recv().ok()).collect()
+    }
+}
+
+pub fn save_frontend_messages(path: impl AsRef<Path>, messages: &[FrontendMessage]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    for message in messages {
+        serde_json::to_writer(&mut writer, message)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.flush()
+}
+
+// Compared as JSON so values that don't equal themselves, such as NaN, still match
+pub fn compare_with_golden(messages: &[FrontendMessage], golden_path: impl AsRef<Path>) -> Result<(), ReplayError> {
+    let golden = fs::read_to_string(golden_path)?;
+    let mut expected = golden.lines();
+    for (index, message) in messages.iter().enumerate() {
+        let actual = serde_json::to_string(message).map_err(|source| ReplayError::InvalidLine { line: index + 1, source })?;
+        match expected.next() {
+            Some(expected) if expected == actual => {}
+            expected => return Err(ReplayError::Mismatch { index, expected: expected.unwrap_or("end of stream").into(), actual }),
+        }
+    }
+    match expected.next() {
+        Some(expected) => Err(ReplayError::Mismatch { index: messages.len(), expected: expected.into(), actual: "end of stream".into() }),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::messages::portfolio::image::utility_types::metadata::{AcquisitionMetadata, FrameMetadata};
+    use crate::messages::portfolio::image::utility_types::misc::RawFrame;
+
+    use super::*;
+
+    // Ids are generated from the recorded seed, so the replay reproduces the session message for message
+    #[test]
+    fn replayed_session_matches_the_recorded_one() {
+        let directory = std::env::temp_dir().join(format!("replay-{}", Uuid::new_v4()));
+        fs::create_dir_all(&directory).unwrap();
+        let recording_path = directory.join("session.jsonl");
+
+        let (mut viewer, mut frontend_messages) = Viewer::new();
+        viewer.handle_message(DebugMessage::StartRecording { path: recording_path.clone() });
+        viewer.handle_message(PortfolioMessage::NewImageFromAcquisition {
+            name: Some("Flat field".into()),
+            acquisition: AcquisitionMetadata::default(),
+            frames: vec![RawFrame { width: 8, height: 8, data: (0..64).collect(), metadata: FrameMetadata::default() }],
+        });
+        let annotation = serde_json::from_value(serde_json::json!({ "type": "Shape", "Rectangle": { "position": [2, 2], "width": 4, "height": 4 } })).unwrap();
+        viewer.handle_message(ImageMessage::AddAnnotation { annotation });
+        viewer.handle_message(ImageMessage::SetWindow { min: 10, max: 50 });
+        viewer.handle_message(ImageMessage::Undo);
+        viewer.handle_message(DebugMessage::StopRecording);
+        let recorded: Vec<_> = std::iter::from_fn(|| frontend_messages.try_recv().ok()).collect();
+
+        let replayed = Recording::load(&recording_path).unwrap().replay();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert!(recorded.iter().any(|message| matches!(message, FrontendMessage::UpdateRoiStatistics { .. })));
+        // Compared as JSON for the same reason as the golden files
+        let to_json = |messages: &[FrontendMessage]| messages.iter().map(|message| serde_json::to_string(message).unwrap()).collect::<Vec<_>>();
+        assert_eq!(to_json(&replayed), to_json(&recorded));
+    }
+}
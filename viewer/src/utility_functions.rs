@@ -0,0 +1,60 @@
+use std::cell::Cell;
+
+use uuid::Uuid;
+
+thread_local! {
+    // Seeded while recording or replaying a session so the same ids are generated again, random otherwise
+    static UUID_STATE: Cell<Option<u64>> = const { Cell::new(None) };
+}
+
+pub fn set_uuid_seed(seed: u64) {
+    UUID_STATE.with(|state| state.set(Some(seed)));
+}
+
+// Ids are random again from here on
+pub fn clear_uuid_seed() {
+    UUID_STATE.with(|state| state.set(None));
+}
+
+pub fn generate_uuid() -> Uuid {
+    UUID_STATE.with(|state| {
+        let Some(mut seed) = state.get() else { return Uuid::new_v4() };
+        let mut bytes = [0; 16];
+        for chunk in bytes.chunks_exact_mut(8) {
+            chunk.copy_from_slice(&splitmix64(&mut seed).to_le_bytes());
+        }
+        state.set(Some(seed));
+        uuid::Builder::from_random_bytes(bytes).into_uuid()
+    })
+}
+
+fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e3779b97f4a7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_ids_repeat_until_the_seed_is_cleared() {
+        set_uuid_seed(7);
+        let first = [generate_uuid(), generate_uuid()];
+        set_uuid_seed(7);
+        let second = [generate_uuid(), generate_uuid()];
+        clear_uuid_seed();
+        let random = generate_uuid();
+        set_uuid_seed(7);
+        let seeded = generate_uuid();
+        clear_uuid_seed();
+
+        assert_eq!(first, second);
+        assert_ne!(first[0], first[1]);
+        assert_ne!(random, first[0]);
+        assert_eq!(seeded, first[0]);
+    }
+}
@@ -0,0 +1,27 @@
+use std::path::PathBuf;
+
+use viewer::replay::{compare_with_golden, save_frontend_messages, Recording};
+
+// Set to write the golden files from the current behaviour instead of comparing against them
+const UPDATE_GOLDEN_VARIABLE: &str = "UPDATE_GOLDEN";
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+}
+
+fn check_against_golden(recording: &str, golden: &str) {
+    let replayed = Recording::load(fixture(recording)).unwrap().replay();
+    if std::env::var_os(UPDATE_GOLDEN_VARIABLE).is_some() {
+        save_frontend_messages(fixture(golden), &replayed).unwrap();
+        return;
+    }
+    if let Err(err) = compare_with_golden(&replayed, fixture(golden)) {
+        panic!("{err}\nrun with {UPDATE_GOLDEN_VARIABLE}=1 to accept the new output");
+    }
+}
+
+// A flat field with a rectangle and a line measured on it, and a window change that is undone
+#[test]
+fn annotation_session_matches_its_golden_file() {
+    check_against_golden("annotation_session.jsonl", "annotation_session.golden.jsonl");
+}
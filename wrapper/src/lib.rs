@@ -290,6 +290,10 @@ impl SLDevice {
         }
     }
 
+    pub fn device_id(&mut self) -> String {
+        sldevice_ffi::get_device_info(self.device.pin_mut()).id
+    }
+
     pub fn get_image_dims(&mut self) -> Result<(u32, u32), SLError> {
         let (x, y) = (self.device.pin_mut().GetImageXDim(), self.device.pin_mut().GetImageYDim());
         if x == -1 || y == -1 {